@@ -11,4 +11,28 @@ pub fn create<'a>() -> App<'a, 'a> {
         .author(AUTHORS)
         .about(ABOUT)
         .arg(Arg::with_name("PATH").takes_value(true).required(false))
+        .arg(
+            Arg::with_name("CHECK_KEYS")
+                .long("check-keys")
+                .value_name("KEYFILE")
+                .takes_value(true)
+                .help("Checks a keyfile for errors and exits"),
+        )
+        .arg(
+            Arg::with_name("KEYFILE")
+                .long("keyfile")
+                .value_name("PATH")
+                .takes_value(true)
+                .help("Loads key bindings from this keyfile instead of $KEYFILE"),
+        )
+        .arg(
+            Arg::with_name("READONLY")
+                .long("readonly")
+                .help("Opens the document for browsing, without edits or saving"),
+        )
+        .arg(
+            Arg::with_name("PRINT_CONFIG")
+                .long("print-config")
+                .help("Prints the key bindings in effect and exits"),
+        )
 }
@@ -1,6 +1,6 @@
 use fs2::FileExt;
-use std::{ffi::OsString, fs::OpenOptions, io::Read};
-use voidmap::{deserialize_screen, init_screen_log, Config, Screen};
+use std::{ffi::OsString, fs::OpenOptions};
+use voidmap::{init_screen_log, open_screen, Config, Screen};
 
 mod cli;
 
@@ -9,6 +9,28 @@ fn main() {
     let app = cli::create();
     let matches = app.get_matches();
 
+    if let Some(keyfile) = matches.value_of("CHECK_KEYS") {
+        if let Err(errors) = Config::validate_keyfile(keyfile) {
+            for e in errors {
+                eprintln!("{}", e);
+            }
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let keyfile = matches.value_of("KEYFILE");
+    if matches.is_present("PRINT_CONFIG") {
+        match Config::load(keyfile) {
+            Ok(config) => print!("{}", config),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     // Initialise screen logger
     init_screen_log().unwrap();
 
@@ -23,29 +45,25 @@ fn main() {
         })
         .unwrap();
 
-    // load from file if present
-    let mut data = vec![];
-    let mut f = OpenOptions::new()
+    // create the file if missing, and exclusively lock it
+    let f = OpenOptions::new()
         .write(true)
         .read(true)
         .create(true)
         .open(&path)
         .unwrap();
-
-    // exclusively lock the file
     f.try_lock_exclusive()
         .unwrap_or_else(|_| panic!("Another `void` process is using this path already!"));
 
-    f.read_to_end(&mut data).unwrap();
-    let saved_screen = deserialize_screen(data).ok();
-
-    // Initialise the main working screen
-    let mut screen = saved_screen.unwrap_or_else(Screen::default);
+    // Initialise the main working screen, saving back to the path
+    let path = path.into_string().unwrap();
+    let mut screen = open_screen(&path).unwrap_or_else(|_| {
+        let mut screen = Screen::default();
+        screen.work_path = Some(path.clone());
+        screen
+    });
 
-    screen.work_path = matches
-        .value_of("PATH")
-        .map(|s| s.into())
-        .or_else(|| Some(path.into_string().unwrap()));
+    screen.readonly = matches.is_present("READONLY");
 
     if let Some(autosave_every) = matches
         .value_of("AUTOSAVE_EVERY")
@@ -54,7 +72,8 @@ fn main() {
         screen.autosave_every = autosave_every;
     }
 
-    let config = Config::maybe_parsed_from_env().unwrap();
+    // bad keyfile lines have been logged, the rest of the keyfile applies
+    let (config, _) = Config::load_partial(keyfile);
     screen.config = config;
 
     screen.run();
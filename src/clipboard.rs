@@ -0,0 +1,33 @@
+/// Somewhere node text can be copied to and pasted from.
+pub trait Clipboard {
+    fn get_text(&mut self) -> Result<String, String>;
+    fn set_text(&mut self, text: String) -> Result<(), String>;
+}
+
+#[cfg(feature = "clipboard")]
+struct SystemClipboard(arboard::Clipboard);
+
+#[cfg(feature = "clipboard")]
+impl Clipboard for SystemClipboard {
+    fn get_text(&mut self) -> Result<String, String> {
+        self.0.get_text().map_err(|e| e.to_string())
+    }
+
+    fn set_text(&mut self, text: String) -> Result<(), String> {
+        self.0.set_text(text).map_err(|e| e.to_string())
+    }
+}
+
+/// Connects to the OS clipboard, which fails on headless systems and when
+/// void is built without the `clipboard` feature.
+#[cfg(feature = "clipboard")]
+pub fn system_clipboard() -> Result<Box<dyn Clipboard>, String> {
+    arboard::Clipboard::new()
+        .map(|c| Box::new(SystemClipboard(c)) as Box<dyn Clipboard>)
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(not(feature = "clipboard"))]
+pub fn system_clipboard() -> Result<Box<dyn Clipboard>, String> {
+    Err("void was built without the clipboard feature".to_owned())
+}
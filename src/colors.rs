@@ -1,3 +1,5 @@
+use std::{env, fmt, io};
+
 use rand::{self, seq::SliceRandom};
 
 pub fn random_fg_color() -> String {
@@ -15,3 +17,249 @@ pub fn random_fg_color() -> String {
     ];
     colors.choose(&mut rand::thread_rng()).unwrap().clone()
 }
+
+// the 16 colors every terminal has, in ANSI order
+const COLOR_NAMES: [&str; 16] = [
+    "black",
+    "red",
+    "green",
+    "yellow",
+    "blue",
+    "magenta",
+    "cyan",
+    "white",
+    "light_black",
+    "light_red",
+    "light_green",
+    "light_yellow",
+    "light_blue",
+    "light_magenta",
+    "light_cyan",
+    "light_white",
+];
+
+// roughly how xterm draws the named colors, to find the one nearest an
+// RGB color
+const NAMED_RGB: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+lazy_static! {
+    // terminals that can show any RGB color say so in COLORTERM
+    static ref TRUECOLOR: bool = env::var("COLORTERM")
+        .map(|v| v == "truecolor" || v == "24bit")
+        .unwrap_or(false);
+}
+
+/// Whether to draw in color, which is off when `NO_COLOR` is set to
+/// anything or stdout isn't a terminal.
+pub fn color_enabled() -> bool {
+    let no_color = env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    !no_color && termion::is_tty(&io::stdout())
+}
+
+/// A color a theme can use, written in a keyfile by name, as `#rrggbb` or
+/// as `color(N)` for one of the 256 terminal colors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Color {
+    /// The terminal's own foreground or background.
+    Reset,
+    /// One of the 256 terminal colors, the first 16 being the named ones.
+    Ansi(u8),
+    Rgb(u8, u8, u8),
+}
+
+impl Color {
+    /// The escape that switches the foreground to this color.
+    pub fn fg(self) -> String {
+        use termion::color::{AnsiValue, Fg, Reset, Rgb};
+        match self.shown(*TRUECOLOR) {
+            Color::Reset => format!("{}", Fg(Reset)),
+            Color::Ansi(value) => format!("{}", Fg(AnsiValue(value))),
+            Color::Rgb(r, g, b) => format!("{}", Fg(Rgb(r, g, b))),
+        }
+    }
+
+    /// The escape that switches the background to this color.
+    pub fn bg(self) -> String {
+        use termion::color::{AnsiValue, Bg, Reset, Rgb};
+        match self.shown(*TRUECOLOR) {
+            Color::Reset => format!("{}", Bg(Reset)),
+            Color::Ansi(value) => format!("{}", Bg(AnsiValue(value))),
+            Color::Rgb(r, g, b) => format!("{}", Bg(Rgb(r, g, b))),
+        }
+    }
+
+    // without truecolor, RGB colors are shown as the nearest named color
+    fn shown(self, truecolor: bool) -> Color {
+        match self {
+            Color::Rgb(r, g, b) if !truecolor => Color::Ansi(nearest_named(r, g, b)),
+            color => color,
+        }
+    }
+}
+
+fn nearest_named(r: u8, g: u8, b: u8) -> u8 {
+    let distance = |&(nr, ng, nb): &(u8, u8, u8)| {
+        let d = |x: u8, y: u8| (i32::from(x) - i32::from(y)).pow(2);
+        d(r, nr) + d(g, ng) + d(b, nb)
+    };
+    (0..16u8)
+        .min_by_key(|&i| distance(&NAMED_RGB[i as usize]))
+        .unwrap()
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Color::Reset => write!(f, "default"),
+            Color::Ansi(value) if value < 16 => write!(f, "{}", COLOR_NAMES[value as usize]),
+            Color::Ansi(value) => write!(f, "color({})", value),
+            Color::Rgb(r, g, b) => write!(f, "#{:02x}{:02x}{:02x}", r, g, b),
+        }
+    }
+}
+
+/// Parses a color name like `blue` or `light_red`, `default` for the
+/// terminal's own color, `#rrggbb`, or `color(N)` with N up to 255.
+pub fn parse_color(s: &str) -> Option<Color> {
+    let s = s.trim().to_lowercase();
+    if s == "default" {
+        return Some(Color::Reset);
+    }
+    if let Some(hex) = s.strip_prefix('#') {
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        return Some(Color::Rgb(channel(0)?, channel(2)?, channel(4)?));
+    }
+    if let Some(raw_value) = s.strip_prefix("color(").and_then(|s| s.strip_suffix(')')) {
+        return raw_value.parse().ok().map(Color::Ansi);
+    }
+    COLOR_NAMES
+        .iter()
+        .position(|&name| name == s)
+        .map(|i| Color::Ansi(i as u8))
+}
+
+/// Colors for parts of the drawing, set in the keyfile with lines like
+/// `theme.selected: blue`. Unset parts are drawn as they always were:
+/// selected nodes inverted, arrows in random colors and everything else in
+/// the terminal's colors.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Theme {
+    pub selected: Option<Color>,
+    pub completed: Option<Color>,
+    pub in_progress: Option<Color>,
+    pub arrows: Option<Color>,
+    pub background: Option<Color>,
+}
+
+impl Theme {
+    /// The parts `apply_color` knows, as named after `theme.` in the keyfile.
+    pub const NAMES: [&'static str; 5] = [
+        "selected",
+        "completed",
+        "in_progress",
+        "arrows",
+        "background",
+    ];
+
+    /// Sets the color of the part named `key`, leaving it alone if `value`
+    /// isn't a color.
+    pub fn apply_color(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let color = parse_color(value)
+            .ok_or_else(|| format!("invalid value for theme.{}: {:?}", key, value))?;
+        match key {
+            "selected" => self.selected = Some(color),
+            "completed" => self.completed = Some(color),
+            "in_progress" => self.in_progress = Some(color),
+            "arrows" => self.arrows = Some(color),
+            "background" => self.background = Some(color),
+            _ => return Err(format!("unknown theme part: {}", key)),
+        }
+        Ok(())
+    }
+
+    /// Each part with its color, in the order of `NAMES`.
+    pub fn parts(&self) -> [(&'static str, Option<Color>); 5] {
+        [
+            ("selected", self.selected),
+            ("completed", self.completed),
+            ("in_progress", self.in_progress),
+            ("arrows", self.arrows),
+            ("background", self.background),
+        ]
+    }
+}
+
+#[test]
+fn test_parse_color() {
+    assert_eq!(parse_color("red"), Some(Color::Ansi(1)));
+    assert_eq!(parse_color(" Light_Blue "), Some(Color::Ansi(12)));
+    assert_eq!(parse_color("default"), Some(Color::Reset));
+    assert_eq!(
+        parse_color("light_white").unwrap().to_string(),
+        "light_white"
+    );
+    assert_eq!(parse_color("mauve"), None);
+    assert_eq!(parse_color(""), None);
+}
+
+#[test]
+fn test_parse_hex_color() {
+    assert_eq!(parse_color("#ff8800"), Some(Color::Rgb(255, 136, 0)));
+    assert_eq!(parse_color("#FF8800"), Some(Color::Rgb(255, 136, 0)));
+    assert_eq!(parse_color("#ff8800").unwrap().to_string(), "#ff8800");
+    assert_eq!(parse_color("#ff880"), None);
+    assert_eq!(parse_color("#ff88000"), None);
+    assert_eq!(parse_color("#gg8800"), None);
+    assert_eq!(parse_color("#+f8800"), None);
+}
+
+#[test]
+fn test_parse_256_color() {
+    assert_eq!(parse_color("color(208)"), Some(Color::Ansi(208)));
+    assert_eq!(parse_color("color(208)").unwrap().to_string(), "color(208)");
+    // the first 16 are written by name
+    assert_eq!(parse_color("color(1)").unwrap().to_string(), "red");
+    assert_eq!(parse_color("color(256)"), None);
+    assert_eq!(parse_color("color(-1)"), None);
+    assert_eq!(parse_color("color()"), None);
+}
+
+#[test]
+fn test_truecolor_fallback() {
+    let orange = Color::Rgb(255, 136, 0);
+    assert_eq!(orange.shown(true), orange);
+    assert_eq!(orange.shown(false), Color::Ansi(3));
+    assert_eq!(Color::Rgb(250, 10, 10).shown(false), Color::Ansi(9));
+    assert_eq!(Color::Rgb(10, 10, 10).shown(false), Color::Ansi(0));
+    assert_eq!(Color::Ansi(208).shown(false), Color::Ansi(208));
+}
+
+#[test]
+fn test_apply_color() {
+    let mut theme = Theme::default();
+    theme.apply_color("selected", "blue").unwrap();
+    assert_eq!(theme.selected, Some(Color::Ansi(4)));
+    assert!(theme.apply_color("arrows", "mauve").is_err());
+    assert_eq!(theme.arrows, None);
+    assert!(theme.apply_color("border", "blue").is_err());
+}
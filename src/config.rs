@@ -1,97 +1,281 @@
 use std::{
-    collections::HashMap,
-    env, fmt,
+    collections::{HashMap, HashSet},
+    env, error, fmt,
     fs::File,
-    io::{self, Error, ErrorKind, Read},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
 };
 
 use termion::event::{Event, Key, MouseEvent};
 
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{history::DEFAULT_UNDO_DEPTH, Theme};
+
+/// The input mode determines which keymap an event is looked up in.
+#[derive(Debug, Default, Copy, Clone, Hash, PartialOrd, Ord, PartialEq, Eq)]
+pub enum Mode {
+    /// Nothing is selected, typed characters prefix-jump to nodes.
+    #[default]
+    Normal,
+    /// A node is selected, typed characters are appended to it.
+    Insert,
+    /// The command line is open, typed characters are appended to it.
+    Command,
+}
+
+impl Mode {
+    /// Modes a keyfile binding applies to when no mode prefix is given.
+    const UNPREFIXED: [Mode; 2] = [Mode::Normal, Mode::Insert];
+
+    /// The keyfile name of this mode.
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Normal => "normal",
+            Mode::Insert => "insert",
+            Mode::Command => "command",
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, Hash, PartialOrd, Ord, PartialEq, Eq)]
 pub enum Action {
     LeftClick(u16, u16),
     RightClick(u16, u16),
+    MiddleClick(u16, u16),
     Release(u16, u16),
+    Drag(u16, u16),
     Char(char),
     UnselectRet,
     ScrollUp,
     ScrollDown,
+    ScrollLeft,
+    ScrollRight,
+    ToggleMinimap,
+    CenterSelection,
     DeleteSelected,
     SelectUp,
     SelectDown,
     SelectLeft,
     SelectRight,
     EraseChar,
+    EraseWord,
+    InsertNewline,
+    RenameNode,
+    CursorLeft,
+    CursorRight,
+    CursorHome,
+    CursorEnd,
     CreateSibling,
     CreateChild,
     CreateFreeNode,
     ExecSelected,
+    OpenUrl,
+    EditInEditor,
+    CancelExec,
     DrillDown,
     PopUp,
     PrefixJump,
     ToggleCompleted,
+    ToggleInProgress,
     ToggleHideCompleted,
     Arrow,
+    StartArrow,
+    EndArrow,
+    RemoveArrow,
     AutoArrange,
+    ToggleLayout,
     ToggleCollapsed,
+    CollapseAll,
+    ExpandAll,
+    FocusBranch,
+    FilterByTag,
+    SetMark,
+    JumpMark,
     Quit,
     Save,
     ToggleShowLogs,
+    ScrollLogsUp,
+    ScrollLogsDown,
+    ToggleLogLevel,
     EnterCmd,
     FindTask,
+    FuzzyFind,
     YankPasteNode,
+    YankNode,
+    CutNode,
+    PasteAsChild,
+    CopyTextToClipboard,
+    PasteTextFromClipboard,
     RaiseSelected,
     LowerSelected,
+    Demote,
+    Promote,
+    DuplicateNode,
+    RaisePriority,
+    LowerPriority,
     Search,
+    SearchNext,
+    SearchPrev,
+    ToggleSearchMode,
     UndoDelete,
+    Undo,
+    Redo,
     Help,
     SelectParent,
     SelectNextSibling,
     SelectPrevSibling,
+    SelectFirstChild,
+    SelectLastChild,
+    SelectRoot,
+    SubmitCmd,
+    CompleteCmd,
 }
 
+// keyfile names of the actions that can be bound to keys
+const ACTION_NAMES: &[(&str, Action)] = &[
+    ("unselect", Action::UnselectRet),
+    ("scroll_up", Action::ScrollUp),
+    ("scroll_down", Action::ScrollDown),
+    ("scroll_left", Action::ScrollLeft),
+    ("scroll_right", Action::ScrollRight),
+    ("toggle_minimap", Action::ToggleMinimap),
+    ("center", Action::CenterSelection),
+    ("delete", Action::DeleteSelected),
+    ("select_up", Action::SelectUp),
+    ("select_down", Action::SelectDown),
+    ("select_left", Action::SelectLeft),
+    ("select_right", Action::SelectRight),
+    ("erase", Action::EraseChar),
+    ("erase_word", Action::EraseWord),
+    ("insert_newline", Action::InsertNewline),
+    ("rename", Action::RenameNode),
+    ("cursor_left", Action::CursorLeft),
+    ("cursor_right", Action::CursorRight),
+    ("cursor_home", Action::CursorHome),
+    ("cursor_end", Action::CursorEnd),
+    ("create_sibling", Action::CreateSibling),
+    ("create_child", Action::CreateChild),
+    ("create_free_node", Action::CreateFreeNode),
+    ("execute", Action::ExecSelected),
+    ("open_url", Action::OpenUrl),
+    ("edit_in_editor", Action::EditInEditor),
+    ("cancel_exec", Action::CancelExec),
+    ("drill_down", Action::DrillDown),
+    ("pop_up", Action::PopUp),
+    ("jump", Action::PrefixJump),
+    ("toggle_completed", Action::ToggleCompleted),
+    ("toggle_in_progress", Action::ToggleInProgress),
+    ("toggle_hide_completed", Action::ToggleHideCompleted),
+    ("arrow", Action::Arrow),
+    ("start_arrow", Action::StartArrow),
+    ("end_arrow", Action::EndArrow),
+    ("remove_arrow", Action::RemoveArrow),
+    ("auto_arrange", Action::AutoArrange),
+    ("toggle_layout", Action::ToggleLayout),
+    ("toggle_collapsed", Action::ToggleCollapsed),
+    ("collapse_all", Action::CollapseAll),
+    ("expand_all", Action::ExpandAll),
+    ("focus_branch", Action::FocusBranch),
+    ("filter_by_tag", Action::FilterByTag),
+    ("set_mark", Action::SetMark),
+    ("jump_mark", Action::JumpMark),
+    ("quit", Action::Quit),
+    ("save", Action::Save),
+    ("toggle_show_logs", Action::ToggleShowLogs),
+    ("scroll_logs_up", Action::ScrollLogsUp),
+    ("scroll_logs_down", Action::ScrollLogsDown),
+    ("toggle_log_level", Action::ToggleLogLevel),
+    ("enter_command", Action::EnterCmd),
+    ("submit_command", Action::SubmitCmd),
+    ("complete_command", Action::CompleteCmd),
+    ("find_task", Action::FindTask),
+    ("fuzzy_find", Action::FuzzyFind),
+    ("yank_paste_node", Action::YankPasteNode),
+    ("yank_node", Action::YankNode),
+    ("cut_node", Action::CutNode),
+    ("paste_as_child", Action::PasteAsChild),
+    ("copy_clipboard", Action::CopyTextToClipboard),
+    ("paste_clipboard", Action::PasteTextFromClipboard),
+    ("raise_selected", Action::RaiseSelected),
+    ("lower_selected", Action::LowerSelected),
+    ("demote", Action::Demote),
+    ("promote", Action::Promote),
+    ("duplicate", Action::DuplicateNode),
+    ("raise_priority", Action::RaisePriority),
+    ("lower_priority", Action::LowerPriority),
+    ("search", Action::Search),
+    ("search_next", Action::SearchNext),
+    ("search_prev", Action::SearchPrev),
+    ("toggle_search_mode", Action::ToggleSearchMode),
+    ("undo_delete", Action::UndoDelete),
+    ("undo", Action::Undo),
+    ("redo", Action::Redo),
+    ("help", Action::Help),
+    ("select_parent", Action::SelectParent),
+    ("select_next_sibling", Action::SelectNextSibling),
+    ("select_prev_sibling", Action::SelectPrevSibling),
+    ("select_first_child", Action::SelectFirstChild),
+    ("select_last_child", Action::SelectLastChild),
+    ("select_root", Action::SelectRoot),
+];
+
 fn to_action(input: String) -> Option<Action> {
-    match &*input {
-        "unselect" => Some(Action::UnselectRet),
-        "scroll_up" => Some(Action::ScrollUp),
-        "scroll_down" => Some(Action::ScrollDown),
-        "delete" => Some(Action::DeleteSelected),
-        "select_up" => Some(Action::SelectUp),
-        "select_down" => Some(Action::SelectDown),
-        "select_left" => Some(Action::SelectLeft),
-        "select_right" => Some(Action::SelectRight),
-        "erase" => Some(Action::EraseChar),
-        "create_sibling" => Some(Action::CreateSibling),
-        "create_child" => Some(Action::CreateChild),
-        "create_free_node" => Some(Action::CreateFreeNode),
-        "execute" => Some(Action::ExecSelected),
-        "drill_down" => Some(Action::DrillDown),
-        "pop_up" => Some(Action::PopUp),
-        "jump" => Some(Action::PrefixJump),
-        "toggle_completed" => Some(Action::ToggleCompleted),
-        "toggle_hide_completed" => Some(Action::ToggleHideCompleted),
-        "arrow" => Some(Action::Arrow),
-        "auto_arrange" => Some(Action::AutoArrange),
-        "toggle_collapsed" => Some(Action::ToggleCollapsed),
-        "quit" => Some(Action::Quit),
-        "save" => Some(Action::Save),
-        "toggle_show_logs" => Some(Action::ToggleShowLogs),
-        "enter_command" => Some(Action::EnterCmd),
-        "find_task" => Some(Action::FindTask),
-        "yank_paste_node" => Some(Action::YankPasteNode),
-        "raise_selected" => Some(Action::RaiseSelected),
-        "lower_selected" => Some(Action::LowerSelected),
-        "search" => Some(Action::Search),
-        "undo_delete" => Some(Action::UndoDelete),
-        "help" => Some(Action::Help),
-        "select_parent" => Some(Action::SelectParent),
-        "select_next_sibling" => Some(Action::SelectNextSibling),
-        "select_prev_sibling" => Some(Action::SelectPrevSibling),
+    ACTION_NAMES
+        .iter()
+        .find(|&&(name, _)| name == input)
+        .map(|&(_, action)| action)
+}
+
+impl Action {
+    /// The keyfile name of this action, or `None` for actions that are
+    /// produced directly from input events rather than bound to keys.
+    pub fn name(&self) -> Option<&'static str> {
+        ACTION_NAMES
+            .iter()
+            .find(|&&(_, action)| action == *self)
+            .map(|&(name, _)| name)
+    }
+}
+
+fn to_mode(input: &str) -> Option<Mode> {
+    match input {
+        "normal" => Some(Mode::Normal),
+        "insert" => Some(Mode::Insert),
+        "command" => Some(Mode::Command),
         _ => None,
     }
 }
 
-// Alt and Control must be specified with capital letters C- and A-
+// Terminals report shift as a different character rather than a modifier,
+// so `S-` resolves to the character a US layout produces with shift held.
+fn shifted(c: char) -> Option<char> {
+    const UNSHIFTED: &str = "`1234567890-=[]\\;',./";
+    const SHIFTED: &str = "~!@#$%^&*()_+{}|:\"<>?";
+
+    if c.is_alphabetic() {
+        c.to_uppercase().next()
+    } else {
+        UNSHIFTED
+            .chars()
+            .position(|u| u == c)
+            .and_then(|idx| SHIFTED.chars().nth(idx))
+    }
+}
+
+// Terminals send control+alt+x as escape followed by the control byte for x,
+// which termion reports as `Alt('\x18')`. Only letters have control bytes.
+fn control_code(c: char) -> Option<char> {
+    if c.is_ascii_alphabetic() {
+        Some((c.to_ascii_lowercase() as u8 - b'a' + 1) as char)
+    } else {
+        None
+    }
+}
+
+// Alt, Control and Shift must be specified with capital letters A-, C- and
+// S-. `S-k` is the same key as `K`. Control and alt may be combined as
+// `C-A-x` or `A-C-x`.
 fn to_key(raw_key: String) -> Option<Key> {
     use termion::event::Key::{Alt, Char, Ctrl};
 
@@ -109,98 +293,341 @@ fn to_key(raw_key: String) -> Option<Key> {
         "down" => Some(Key::Down),
         "left" => Some(Key::Left),
         "right" => Some(Key::Right),
+        "home" => Some(Key::Home),
+        "end" => Some(Key::End),
+        "insert" => Some(Key::Insert),
 
         "space" => Some(Char(' ')),
         "enter" => Some(Char('\n')),
         "tab" => Some(Char('\t')),
+        "comma" => Some(Char(',')),
+        "period" => Some(Char('.')),
+        "slash" => Some(Char('/')),
+        "semicolon" => Some(Char(';')),
+        "minus" => Some(Char('-')),
+        "plus" => Some(Char('+')),
+        "equals" => Some(Char('=')),
+
+        key if key.chars().count() == 1 => extract_key(key, 0).map(Char),
 
-        key if key.len() == 1 => extract_key(key, 0).map(Char),
+        key if key.starts_with('f') => key[1..]
+            .parse::<u8>()
+            .ok()
+            .filter(|n| (1..=12).contains(n))
+            .map(Key::F),
 
+        // a bare comma would split the key list
+        "A-comma" => Some(Alt(',')),
+        "A-backspace" => Some(Alt('\x7f')),
+        key if key.starts_with("C-A-") || key.starts_with("A-C-") => {
+            extract_key(key, 4).and_then(control_code).map(Alt)
+        }
         key if key.starts_with("A-") => extract_key(key, 2).map(Alt),
         key if key.starts_with("C-") => extract_key(key, 2).map(Ctrl),
+        key if key.starts_with("S-") => extract_key(key, 2).and_then(shifted).map(Char),
 
         _ => None,
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct Config {
-    config: HashMap<Key, Action>,
+// A key of a chord as written. `leader` stays unresolved until the whole
+// keyfile is parsed, so a `leader:` line anywhere in it, or in an include,
+// applies to every chord.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum ChordKey {
+    Leader,
+    Key(Key),
 }
 
-impl Default for Config {
-    fn default() -> Config {
-        use termion::event::Key::*;
-        Config {
-            config: vec![
-                (Esc, Action::UnselectRet),
-                (PageUp, Action::ScrollUp),
-                (PageDown, Action::ScrollDown),
-                (Delete, Action::DeleteSelected),
-                (Up, Action::SelectUp),
-                (Down, Action::SelectDown),
-                (Left, Action::SelectLeft),
-                (Right, Action::SelectRight),
-                (Backspace, Action::EraseChar),
-                (F(1), Action::PrefixJump),
-                (Char('\n'), Action::CreateSibling),
-                (Char('\t'), Action::CreateChild),
-                (Ctrl('n'), Action::CreateFreeNode),
-                (Ctrl('k'), Action::ExecSelected),
-                (Ctrl('w'), Action::DrillDown),
-                (Ctrl('q'), Action::PopUp),
-                (Ctrl('f'), Action::PrefixJump),
-                (Ctrl('a'), Action::ToggleCompleted),
-                (Ctrl('h'), Action::ToggleHideCompleted),
-                (Ctrl('r'), Action::Arrow),
-                (Ctrl('p'), Action::AutoArrange),
-                (Ctrl('t'), Action::ToggleCollapsed),
-                (Ctrl('c'), Action::Quit),
-                (Ctrl('x'), Action::Save),
-                (Ctrl('l'), Action::ToggleShowLogs),
-                (Ctrl('e'), Action::EnterCmd),
-                (Ctrl('v'), Action::FindTask),
-                (Ctrl('y'), Action::YankPasteNode),
-                (Ctrl('g'), Action::RaiseSelected),
-                (Ctrl('d'), Action::LowerSelected),
-                (Ctrl('u'), Action::Search),
-                (Ctrl('z'), Action::UndoDelete),
-                (Ctrl('?'), Action::Help),
-                (Alt('P'), Action::SelectParent),
-                (Alt('n'), Action::SelectNextSibling),
-                (Alt('p'), Action::SelectPrevSibling),
-            ]
-            .into_iter()
-            .collect(),
+impl ChordKey {
+    fn resolve(self, leader: Key) -> Key {
+        match self {
+            ChordKey::Leader => leader,
+            ChordKey::Key(key) => key,
         }
     }
 }
 
-impl fmt::Display for Config {
+// A chord is keys separated by spaces, like `C-x C-s`, or two letters or
+// digits, like `gg`. Longer runs are more likely a misspelt key name. In a
+// spaced chord, `leader` stands for the leader key.
+fn to_chord(raw_key: &str) -> Option<Vec<ChordKey>> {
+    let keys = if raw_key.contains(char::is_whitespace) {
+        raw_key
+            .split_whitespace()
+            .map(|k| {
+                if k == "leader" {
+                    Some(ChordKey::Leader)
+                } else {
+                    to_key(k.to_owned()).map(ChordKey::Key)
+                }
+            })
+            .collect()
+    } else if raw_key.chars().count() == 2 && raw_key.chars().all(char::is_alphanumeric) {
+        Some(raw_key.chars().map(Key::Char).map(ChordKey::Key).collect())
+    } else {
+        None
+    };
+    keys.filter(|keys: &Vec<ChordKey>| keys.len() > 1)
+}
+
+pub fn chord_to_string(keys: &[Key]) -> String {
+    let names: Vec<_> = keys
+        .iter()
+        .map(|&key| match key {
+            Key::Char(',') => "comma".to_owned(),
+            key => key_to_string(key),
+        })
+        .collect();
+    names.join(" ")
+}
+
+// The inverse of to_key, producing the keyfile spelling of a key.
+fn key_to_string(key: Key) -> String {
+    use termion::event::Key::*;
+
+    match key {
+        Esc => "esc".to_owned(),
+        PageUp => "pgup".to_owned(),
+        PageDown => "pgdn".to_owned(),
+        Delete => "del".to_owned(),
+        Backspace => "backspace".to_owned(),
+        Up => "up".to_owned(),
+        Down => "down".to_owned(),
+        Left => "left".to_owned(),
+        Right => "right".to_owned(),
+        Home => "home".to_owned(),
+        End => "end".to_owned(),
+        Insert => "insert".to_owned(),
+        F(n) => format!("f{}", n),
+
+        Char(' ') => "space".to_owned(),
+        Char('\n') => "enter".to_owned(),
+        Char('\t') => "tab".to_owned(),
+        Char(c) => c.to_string(),
+
+        Alt(c) if ('\x01'..='\x1a').contains(&c) => {
+            format!("C-A-{}", (c as u8 - 1 + b'a') as char)
+        }
+        Alt(',') => "A-comma".to_owned(),
+        Alt('\x7f') => "A-backspace".to_owned(),
+        Alt(c) => format!("A-{}", c),
+        Ctrl(c) => format!("C-{}", c),
+
+        other => format!("{:?}", other),
+    }
+}
+
+// A `#` preceded by whitespace starts a comment, unless it directly follows
+// the colon or a comma, in which case it is the key being bound.
+fn strip_comment(line: &str) -> &str {
+    let mut last_non_ws = None;
+    let mut prev_ws = false;
+    for (idx, c) in line.char_indices() {
+        if c == '#' && prev_ws && !matches!(last_non_ws, Some(':') | Some(',')) {
+            return line[..idx].trim_end();
+        }
+        prev_ws = c.is_whitespace();
+        if !prev_ws {
+            last_non_ws = Some(c);
+        }
+    }
+    line
+}
+
+// KEYFILE takes precedence, otherwise the first existing of
+// $XDG_CONFIG_HOME/void/keys and ~/.config/void/keys is used.
+fn keyfile_path() -> Option<PathBuf> {
+    if let Ok(p) = env::var("KEYFILE") {
+        return Some(PathBuf::from(p));
+    }
+
+    let mut candidates = vec![];
+    if let Ok(xdg) = env::var("XDG_CONFIG_HOME") {
+        if !xdg.is_empty() {
+            candidates.push(PathBuf::from(xdg).join("void").join("keys"));
+        }
+    }
+    if let Some(home) = dirs::home_dir() {
+        candidates.push(home.join(".config").join("void").join("keys"));
+    }
+    candidates.into_iter().find(|p| p.exists())
+}
+
+/// A problem found while loading a keyfile. Line numbers start at 1.
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    MissingColon {
+        line: usize,
+    },
+    UnknownKey {
+        line: usize,
+        raw: String,
+    },
+    UnknownAction {
+        line: usize,
+        raw: String,
+    },
+    UnknownMode {
+        line: usize,
+        raw: String,
+    },
+    InvalidValue {
+        line: usize,
+        raw: String,
+    },
+    IncludeCycle {
+        line: usize,
+        path: String,
+    },
+    /// An error reading or parsing the keyfile included at `line`.
+    Included {
+        line: usize,
+        path: String,
+        source: Box<ConfigError>,
+    },
+    /// Every problem in a keyfile with more than one.
+    Multiple(Vec<ConfigError>),
+}
+
+impl fmt::Display for ConfigError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        writeln!(f, "Configured Hotkeys:").unwrap();
-        for (key, action) in &self.config {
-            writeln!(f, "    {:?}: {:?}", action, key).unwrap();
+        use ConfigError::*;
+        match self {
+            Io(e) => write!(f, "{}", e),
+            MissingColon { line } => write!(f, "No colon found on line {}", line),
+            UnknownKey { line, raw } => write!(f, "invalid key {:?} at line {}", raw, line),
+            UnknownAction { line, raw } => write!(f, "unknown action {:?} at line {}", raw, line),
+            UnknownMode { line, raw } => write!(f, "unknown mode {:?} at line {}", raw, line),
+            InvalidValue { line, raw } => write!(f, "invalid value {:?} at line {}", raw, line),
+            IncludeCycle { line, path } => write!(f, "include cycle at line {}: {}", line, path),
+            Included { line, path, source } => {
+                write!(f, "{} (included at line {}): {}", path, line, source)
+            }
+            Multiple(errors) => {
+                let lines: Vec<_> = errors.iter().map(|e| e.to_string()).collect();
+                write!(f, "{}", lines.join("\n"))
+            }
         }
-        Ok(())
     }
 }
 
-impl Config {
-    pub fn maybe_parsed_from_env() -> io::Result<Config> {
-        if let Ok(p) = env::var("KEYFILE") {
-            Config::parse_keyfile(p)
-        } else {
-            Ok(Config::default())
+impl error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ConfigError::Io(e) => Some(e),
+            ConfigError::Included { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ConfigError {
+    fn from(e: io::Error) -> ConfigError {
+        ConfigError::Io(e)
+    }
+}
+
+// What one key of a keyfile line binds, a key on its own or a chord.
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Bound {
+    Key(Key),
+    Chord(Vec<ChordKey>),
+}
+
+impl Bound {
+    fn name(&self) -> String {
+        match self {
+            Bound::Key(key) => key_to_string(*key),
+            Bound::Chord(keys) => {
+                let names: Vec<_> = keys
+                    .iter()
+                    .map(|&key| match key {
+                        ChordKey::Leader => "leader".to_owned(),
+                        ChordKey::Key(key) => chord_to_string(&[key]),
+                    })
+                    .collect();
+                names.join(" ")
+            }
         }
     }
+}
 
-    pub fn parse_keyfile(p: String) -> io::Result<Config> {
-        let mut buf = String::new();
-        let mut f = File::open(p)?;
-        f.read_to_string(&mut buf)?;
-        let mut config = Config::default();
+// The outcome of parsing a keyfile. Errors are lines that were skipped,
+// warnings are lines that applied but clobbered an earlier line.
+struct Parsed {
+    config: Config,
+    // moved into the config, with the leader resolved, once parsing is done
+    chords: HashMap<(Mode, Vec<ChordKey>), Action>,
+    errors: Vec<ConfigError>,
+    warnings: Vec<String>,
+}
+
+impl Parsed {
+    fn new() -> Parsed {
+        Parsed {
+            config: Config::default(),
+            chords: HashMap::new(),
+            errors: vec![],
+            warnings: vec![],
+        }
+    }
+
+    fn finish(self) -> Result<Config, ConfigError> {
+        match self.into_parts() {
+            (config, None) => Ok(config),
+            (_, Some(e)) => Err(e),
+        }
+    }
+
+    // Every error has already been logged. The config still holds every
+    // line that applied.
+    fn into_parts(mut self) -> (Config, Option<ConfigError>) {
+        for w in &self.warnings {
+            warn!("{}", w);
+        }
+        let leader = self.config.leader;
+        for ((mode, keys), action) in self.chords.drain() {
+            let keys = keys.into_iter().map(|k| k.resolve(leader)).collect();
+            self.config.chords.insert((mode, keys), action);
+        }
+        let error = match self.errors.len() {
+            0 => None,
+            1 => self.errors.pop(),
+            _ => Some(ConfigError::Multiple(self.errors)),
+        };
+        (self.config, error)
+    }
+
+    fn error(&mut self, e: ConfigError) {
+        error!("{}", e);
+        self.errors.push(e);
+    }
+
+    // Each line is `[mode] action: key[, key...]`. Without a mode prefix the
+    // binding applies to both normal and insert mode, like the defaults do,
+    // but not to command mode.
+    //
+    // `unbind: key` removes a binding, or a chord. Unbinds are applied after the defaults
+    // but before any of the rebinds in the same file, regardless of the order
+    // the lines appear in, so `unbind: C-c` followed by `quit: C-c` keeps
+    // C-c bound to quit.
+    //
+    // `include path` parses another keyfile in place, so lines after the
+    // include override it. `files` is the chain of keyfiles currently being
+    // parsed, innermost last, and is used to resolve relative includes and
+    // to refuse include cycles.
+    //
+    // Invalid lines are skipped and reported, every valid line still applies.
+    // Binding one key to two different actions within a file is only a
+    // warning, and the later line wins.
+    fn parse_buf(&mut self, buf: &str, files: &mut Vec<PathBuf>) {
+        let mut unbinds = vec![];
+        let mut binds = vec![];
+        let mut bound_at = HashMap::new();
         for (mut line_num, line) in buf.lines().enumerate() {
+            let line = strip_comment(line);
             if line == "" || line.starts_with('#') {
                 continue;
             }
@@ -208,38 +635,773 @@ impl Config {
             // Zero based indexing inappropriate here.
             line_num += 1;
 
+            if let Some(raw_path) = line.strip_prefix("include ") {
+                // everything above the include applies before it
+                self.apply(&mut unbinds, &mut binds, &mut bound_at);
+                self.include(raw_path.trim(), line_num, files);
+                continue;
+            }
+
             let parts: Vec<_> = line.splitn(2, ':').map(|p| p.trim()).collect();
             if parts.len() != 2 {
-                let e = format!("No colon found on line {}", line_num);
-                error!("{}", e);
-                return Err(Error::new(ErrorKind::Other, e));
+                self.error(ConfigError::MissingColon { line: line_num });
+                continue;
             }
 
             let (raw_action, raw_key) = (parts[0], parts[1]);
 
-            let key_opt = to_key(raw_key.to_owned());
+            if raw_action == "startup_mode" {
+                match to_mode(raw_key) {
+                    Some(mode @ Mode::Normal) | Some(mode @ Mode::Insert) => {
+                        self.config.startup_mode = mode;
+                    }
+                    _ => self.error(ConfigError::UnknownMode {
+                        line: line_num,
+                        raw: raw_key.to_owned(),
+                    }),
+                }
+                continue;
+            }
+
+            if raw_action == "leader" {
+                match to_key(raw_key.to_owned()) {
+                    Some(key) => self.config.leader = key,
+                    None => self.error(ConfigError::UnknownKey {
+                        line: line_num,
+                        raw: raw_key.to_owned(),
+                    }),
+                }
+                continue;
+            }
+
+            if Settings::NAMES.contains(&raw_action) {
+                let settings = &mut self.config.settings;
+                if settings.apply_setting(raw_action, raw_key).is_err() {
+                    self.error(ConfigError::InvalidValue {
+                        line: line_num,
+                        raw: raw_key.to_owned(),
+                    });
+                }
+                continue;
+            }
+
+            if let Some(part) = raw_action.strip_prefix("theme.") {
+                if !Theme::NAMES.contains(&part) {
+                    self.error(ConfigError::UnknownAction {
+                        line: line_num,
+                        raw: raw_action.to_owned(),
+                    });
+                } else if self.config.theme.apply_color(part, raw_key).is_err() {
+                    self.error(ConfigError::InvalidValue {
+                        line: line_num,
+                        raw: raw_key.to_owned(),
+                    });
+                }
+                continue;
+            }
+
+            if let Some(name) = raw_action.strip_prefix("glyph.") {
+                if !Glyphs::NAMES.contains(&name) {
+                    self.error(ConfigError::UnknownAction {
+                        line: line_num,
+                        raw: raw_action.to_owned(),
+                    });
+                } else if self.config.glyphs.apply_glyph(name, raw_key).is_err() {
+                    self.error(ConfigError::InvalidValue {
+                        line: line_num,
+                        raw: raw_key.to_owned(),
+                    });
+                }
+                continue;
+            }
+
+            let mut lhs: Vec<_> = raw_action.split_whitespace().collect();
+            let raw_action = lhs.pop().unwrap_or("");
+            let modes_opt = match lhs.as_slice() {
+                [] => Some(Mode::UNPREFIXED.to_vec()),
+                [raw_mode] => to_mode(raw_mode).map(|mode| vec![mode]),
+                _ => None,
+            };
+            let modes = if let Some(modes) = modes_opt {
+                modes
+            } else {
+                self.error(ConfigError::UnknownMode {
+                    line: line_num,
+                    raw: lhs.join(" "),
+                });
+                continue;
+            };
+
+            let unbind = raw_action == "unbind";
             let action_opt = to_action(raw_action.to_owned());
 
-            if key_opt.is_none() || action_opt.is_none() {
-                let e = format!("invalid config at line {}: {}", line_num, line);
+            if action_opt.is_none() && !unbind {
+                self.error(ConfigError::UnknownAction {
+                    line: line_num,
+                    raw: raw_action.to_owned(),
+                });
+                continue;
+            }
+
+            // a lone comma is the comma key rather than an empty list
+            let raw_keys: Vec<_> = if raw_key == "," {
+                vec![raw_key]
+            } else {
+                raw_key.split(',').map(|k| k.trim()).collect()
+            };
+
+            let n_raw_keys = raw_keys.len();
+            let mut bounds = vec![];
+            for raw_key in raw_keys {
+                // to_key ignores trailing text, so `C-x C-s` would be C-x
+                let key_opt = if raw_key.contains(char::is_whitespace) {
+                    None
+                } else {
+                    to_key(raw_key.to_owned())
+                };
+                if let Some(key) = key_opt {
+                    bounds.push(Bound::Key(key));
+                } else if let Some(chord) = to_chord(raw_key) {
+                    bounds.push(Bound::Chord(chord));
+                } else {
+                    self.error(ConfigError::UnknownKey {
+                        line: line_num,
+                        raw: raw_key.to_owned(),
+                    });
+                }
+            }
+            if bounds.len() != n_raw_keys {
+                // one bad key rejects the whole line
+                continue;
+            }
+
+            for mode in modes {
+                for bound in &bounds {
+                    if let Some(action) = action_opt {
+                        binds.push(((mode, bound.clone()), action, line_num));
+                    } else {
+                        unbinds.push((mode, bound.clone()));
+                    }
+                }
+            }
+        }
+
+        self.apply(&mut unbinds, &mut binds, &mut bound_at);
+    }
+
+    fn apply(
+        &mut self,
+        unbinds: &mut Vec<(Mode, Bound)>,
+        binds: &mut Vec<((Mode, Bound), Action, usize)>,
+        bound_at: &mut HashMap<(Mode, Bound), (Action, usize)>,
+    ) {
+        for (mode, bound) in unbinds.drain(..) {
+            match bound {
+                Bound::Key(key) => self.config.config.remove(&(mode, key)),
+                Bound::Chord(keys) => self.chords.remove(&(mode, keys)),
+            };
+        }
+        for ((mode, bound), action, line_num) in binds.drain(..) {
+            let prev_opt = bound_at.insert((mode, bound.clone()), (action, line_num));
+            if let Some((prev, prev_line)) = prev_opt {
+                if prev != action {
+                    self.warnings.push(format!(
+                        "line {} binds {} in {} mode to {}, overriding {} from line {}",
+                        line_num,
+                        bound.name(),
+                        mode.name(),
+                        action.name().unwrap_or("?"),
+                        prev.name().unwrap_or("?"),
+                        prev_line
+                    ));
+                }
+            }
+            match bound {
+                Bound::Key(key) => self.config.config.insert((mode, key), action),
+                Bound::Chord(keys) => self.chords.insert((mode, keys), action),
+            };
+        }
+    }
+
+    fn include(&mut self, raw_path: &str, line_num: usize, files: &mut Vec<PathBuf>) {
+        let mut path = PathBuf::from(raw_path);
+        if path.is_relative() {
+            if let Some(dir) = files.last().and_then(|f| f.parent()) {
+                path = dir.join(path);
+            }
+        }
+
+        let included = |source| ConfigError::Included {
+            line: line_num,
+            path: raw_path.to_owned(),
+            source: Box::new(source),
+        };
+
+        let mut buf = String::new();
+        let path = match path.canonicalize() {
+            Ok(ref path) if files.contains(path) => {
+                self.error(ConfigError::IncludeCycle {
+                    line: line_num,
+                    path: raw_path.to_owned(),
+                });
+                return;
+            }
+            Ok(path) => File::open(&path)
+                .and_then(|mut f| f.read_to_string(&mut buf))
+                .map(|_| path),
+            Err(e) => Err(e),
+        };
+        let path = match path {
+            Ok(path) => path,
+            Err(e) => {
+                self.error(included(ConfigError::Io(e)));
+                return;
+            }
+        };
+
+        let (first_error, first_warning) = (self.errors.len(), self.warnings.len());
+        files.push(path);
+        self.parse_buf(&buf, files);
+        files.pop();
+        let errors: Vec<_> = self.errors.drain(first_error..).map(included).collect();
+        self.errors.extend(errors);
+        for msg in &mut self.warnings[first_warning..] {
+            *msg = format!("{}: {}", raw_path, msg);
+        }
+    }
+}
+
+/// Options that can be set from the keyfile or changed while running with
+/// `:set`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    /// Whether executing a command node adds its output as children.
+    pub exec_capture_output: bool,
+    /// The most output lines kept when capturing command output.
+    pub exec_max_lines: usize,
+    /// Seconds after which unsaved changes are saved, 0 to never.
+    pub autosave_secs: u64,
+    /// Keys that label nodes to prefix-jump to, most preferred first.
+    pub jump_keys: Vec<char>,
+    /// Column node text wraps at, 0 to never wrap.
+    pub node_width: usize,
+    /// Whether completing a node completes its descendants too.
+    pub cascade_complete: bool,
+    /// Whether parents complete once all their children are complete.
+    pub auto_complete_parents: bool,
+    /// Rows kept in view above and below the selected node.
+    pub scrolloff: u16,
+    /// The most steps undo can go back.
+    pub undo_depth: usize,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            exec_capture_output: false,
+            exec_max_lines: 100,
+            autosave_secs: 60,
+            jump_keys: "arstqwfpgdbvcxzoienyuljhkm1234567890ARSTQWFPGDVCXZOIENYULJHBKM"
+                .chars()
+                .collect(),
+            node_width: 0,
+            cascade_complete: false,
+            auto_complete_parents: false,
+            scrolloff: 2,
+            undo_depth: DEFAULT_UNDO_DEPTH,
+        }
+    }
+}
+
+impl Settings {
+    /// The settings `apply_setting` knows, as named in the keyfile.
+    pub const NAMES: [&'static str; 9] = [
+        "exec_capture_output",
+        "exec_max_lines",
+        "autosave_secs",
+        "jump_keys",
+        "node_width",
+        "cascade_complete",
+        "auto_complete_parents",
+        "scrolloff",
+        "undo_depth",
+    ];
+
+    /// Validates `value` for the setting named `key` and applies it, leaving
+    /// the setting alone if it is invalid.
+    pub fn apply_setting(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let invalid = || format!("invalid value for {}: {:?}", key, value);
+        let flag = || match value {
+            "true" => Ok(true),
+            "false" => Ok(false),
+            _ => Err(invalid()),
+        };
+        match key {
+            "exec_capture_output" => self.exec_capture_output = flag()?,
+            "cascade_complete" => self.cascade_complete = flag()?,
+            "auto_complete_parents" => self.auto_complete_parents = flag()?,
+            "exec_max_lines" => self.exec_max_lines = value.parse().map_err(|_| invalid())?,
+            "autosave_secs" => self.autosave_secs = value.parse().map_err(|_| invalid())?,
+            "node_width" => self.node_width = value.parse().map_err(|_| invalid())?,
+            "scrolloff" => self.scrolloff = value.parse().map_err(|_| invalid())?,
+            "undo_depth" => self.undo_depth = value.parse().map_err(|_| invalid())?,
+            "jump_keys" => {
+                let keys: Vec<char> = value.chars().filter(|c| !c.is_whitespace()).collect();
+                let unique: HashSet<char> = keys.iter().cloned().collect();
+                if keys.len() < 2 || unique.len() != keys.len() {
+                    return Err(invalid());
+                }
+                self.jump_keys = keys;
+            }
+            _ => return Err(format!("unknown setting: {}", key)),
+        }
+        Ok(())
+    }
+}
+
+/// The marks drawn before node text, set in the keyfile with lines like
+/// `glyph.completed: ✓`. Each is a single grapheme, so it takes the place
+/// of the default without moving anything.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Glyphs {
+    pub completed: String,
+    pub in_progress: String,
+    pub collapsed: String,
+    pub hide_completed: String,
+    pub free_text: String,
+}
+
+impl Default for Glyphs {
+    fn default() -> Glyphs {
+        Glyphs {
+            completed: "☠".to_owned(),
+            in_progress: "◐".to_owned(),
+            collapsed: "⊞".to_owned(),
+            hide_completed: "⚔".to_owned(),
+            free_text: "✏".to_owned(),
+        }
+    }
+}
+
+impl Glyphs {
+    /// The glyphs `apply_glyph` knows, as named after `glyph.` in the
+    /// keyfile.
+    pub const NAMES: [&'static str; 5] = [
+        "completed",
+        "in_progress",
+        "collapsed",
+        "hide_completed",
+        "free_text",
+    ];
+
+    /// Sets the glyph named `key`, leaving it alone unless `value` is a
+    /// single grapheme.
+    pub fn apply_glyph(&mut self, key: &str, value: &str) -> Result<(), String> {
+        if UnicodeSegmentation::graphemes(value, true).count() != 1 {
+            return Err(format!("invalid value for glyph.{}: {:?}", key, value));
+        }
+        let glyph = match key {
+            "completed" => &mut self.completed,
+            "in_progress" => &mut self.in_progress,
+            "collapsed" => &mut self.collapsed,
+            "hide_completed" => &mut self.hide_completed,
+            "free_text" => &mut self.free_text,
+            _ => return Err(format!("unknown glyph: {}", key)),
+        };
+        *glyph = value.to_owned();
+        Ok(())
+    }
+
+    /// Each glyph with its name, in the order of `NAMES`.
+    pub fn parts(&self) -> [(&'static str, &str); 5] {
+        [
+            ("completed", &self.completed),
+            ("in_progress", &self.in_progress),
+            ("collapsed", &self.collapsed),
+            ("hide_completed", &self.hide_completed),
+            ("free_text", &self.free_text),
+        ]
+    }
+}
+
+/// How keys typed so far relate to the chords bound in a mode.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChordMatch {
+    /// The keys make up a whole chord.
+    Complete(Action),
+    /// The keys start at least one chord, so more are needed.
+    Prefix,
+    /// No chord starts with the keys.
+    Unmatched,
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    config: HashMap<(Mode, Key), Action>,
+    // multi-key sequences like `gg`, matched before single key bindings
+    chords: HashMap<(Mode, Vec<Key>), Action>,
+    /// The mode void is in when it starts, either normal or insert.
+    pub startup_mode: Mode,
+    /// The key `leader` stands for in chords, space unless set otherwise.
+    pub leader: Key,
+    pub settings: Settings,
+    pub theme: Theme,
+    pub glyphs: Glyphs,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        use termion::event::Key::*;
+        let bindings = vec![
+            (Esc, Action::UnselectRet),
+            (PageUp, Action::ScrollUp),
+            (PageDown, Action::ScrollDown),
+            (Alt('{'), Action::ScrollLeft),
+            (Alt('}'), Action::ScrollRight),
+            (Alt('M'), Action::ToggleMinimap),
+            (Alt('='), Action::CenterSelection),
+            (Delete, Action::DeleteSelected),
+            (Up, Action::SelectUp),
+            (Down, Action::SelectDown),
+            (Left, Action::SelectLeft),
+            (Right, Action::SelectRight),
+            (Backspace, Action::EraseChar),
+            (Alt(','), Action::CursorLeft),
+            (Alt('.'), Action::CursorRight),
+            (Alt('0'), Action::CursorHome),
+            (Alt('$'), Action::CursorEnd),
+            (F(1), Action::PrefixJump),
+            (F(2), Action::RenameNode),
+            (Char('\n'), Action::CreateSibling),
+            (Char('\t'), Action::CreateChild),
+            (Ctrl('n'), Action::CreateFreeNode),
+            (Ctrl('k'), Action::ExecSelected),
+            (Alt('o'), Action::OpenUrl),
+            (Alt('E'), Action::EditInEditor),
+            (Alt('k'), Action::CancelExec),
+            (Ctrl('w'), Action::DrillDown),
+            (Ctrl('q'), Action::PopUp),
+            (Ctrl('f'), Action::PrefixJump),
+            (Ctrl('a'), Action::ToggleCompleted),
+            (Ctrl('b'), Action::ToggleInProgress),
+            (Ctrl('h'), Action::ToggleHideCompleted),
+            (Ctrl('r'), Action::Arrow),
+            (Alt('r'), Action::StartArrow),
+            (Alt('R'), Action::EndArrow),
+            (Alt('e'), Action::RemoveArrow),
+            (Ctrl('p'), Action::AutoArrange),
+            (Alt('l'), Action::ToggleLayout),
+            (Ctrl('t'), Action::ToggleCollapsed),
+            (Alt('t'), Action::CollapseAll),
+            (Alt('T'), Action::ExpandAll),
+            (Alt('f'), Action::FocusBranch),
+            (Alt('F'), Action::FilterByTag),
+            (Alt('m'), Action::SetMark),
+            (Alt('\''), Action::JumpMark),
+            (Ctrl('c'), Action::Quit),
+            (Ctrl('x'), Action::Save),
+            (Ctrl('l'), Action::ToggleShowLogs),
+            (Alt('['), Action::ScrollLogsUp),
+            (Alt(']'), Action::ScrollLogsDown),
+            (Alt('L'), Action::ToggleLogLevel),
+            (Ctrl('e'), Action::EnterCmd),
+            (Ctrl('v'), Action::FindTask),
+            (Ctrl('o'), Action::FuzzyFind),
+            (Ctrl('y'), Action::YankPasteNode),
+            (Alt('w'), Action::YankNode),
+            (Alt('W'), Action::CutNode),
+            (Alt('y'), Action::PasteAsChild),
+            (Alt('Y'), Action::CopyTextToClipboard),
+            (Alt('v'), Action::PasteTextFromClipboard),
+            (Ctrl('g'), Action::RaiseSelected),
+            (Ctrl('d'), Action::LowerSelected),
+            (Alt('>'), Action::Demote),
+            (Alt('<'), Action::Promote),
+            (Alt('d'), Action::DuplicateNode),
+            (Alt('+'), Action::RaisePriority),
+            (Alt('-'), Action::LowerPriority),
+            (Ctrl('u'), Action::Search),
+            (Alt('u'), Action::SearchNext),
+            (Alt('U'), Action::SearchPrev),
+            (Ctrl('z'), Action::UndoDelete),
+            (Alt('z'), Action::Undo),
+            (Alt('Z'), Action::Redo),
+            (Ctrl('?'), Action::Help),
+            (Alt('P'), Action::SelectParent),
+            (Alt('n'), Action::SelectNextSibling),
+            (Alt('p'), Action::SelectPrevSibling),
+            (Alt('c'), Action::SelectFirstChild),
+            (Alt('C'), Action::SelectLastChild),
+            (Home, Action::SelectRoot),
+        ];
+        let mut config = HashMap::new();
+        for &mode in &Mode::UNPREFIXED {
+            for &(key, action) in &bindings {
+                config.insert((mode, key), action);
+            }
+        }
+        // typed characters and erase edit the command line, esc cancels it,
+        // tab completes a command name, C-t cycles how a search query matches
+        let command_bindings = vec![
+            (Esc, Action::UnselectRet),
+            (Backspace, Action::EraseChar),
+            (Char('\n'), Action::SubmitCmd),
+            (Char('\t'), Action::CompleteCmd),
+            (Ctrl('t'), Action::ToggleSearchMode),
+        ];
+        for (key, action) in command_bindings {
+            config.insert((Mode::Command, key), action);
+        }
+        // terminals send the same enter for C-enter, so alt-enter it is,
+        // and C-w drills down so words are erased with alt-backspace
+        config.insert((Mode::Insert, Alt('\r')), Action::InsertNewline);
+        config.insert((Mode::Insert, Alt('\x7f')), Action::EraseWord);
+        Config {
+            config,
+            chords: HashMap::new(),
+            startup_mode: Mode::default(),
+            leader: Char(' '),
+            settings: Settings::default(),
+            theme: Theme::default(),
+            glyphs: Glyphs::default(),
+        }
+    }
+}
+
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut bindings: Vec<_> = self
+            .config
+            .iter()
+            .map(|(&(mode, key), action)| (mode, action.name().unwrap_or("?"), key_to_string(key)))
+            .chain(self.chord_lines())
+            .collect();
+        bindings.sort();
+
+        writeln!(f, "Startup mode: {:?}", self.startup_mode)?;
+        writeln!(f, "Leader: {}", key_to_string(self.leader))?;
+        writeln!(f, "Configured Hotkeys:")?;
+        let mut last_mode = None;
+        for (mode, name, key) in bindings {
+            if last_mode != Some(mode) {
+                writeln!(f, "  {:?} mode:", mode)?;
+                last_mode = Some(mode);
+            }
+            writeln!(f, "    {}: {}", name, key)?;
+        }
+        Ok(())
+    }
+}
+
+impl Config {
+    pub fn maybe_parsed_from_env() -> Result<Config, ConfigError> {
+        if let Some(p) = keyfile_path() {
+            info!("loading keys from {}", p.display());
+            Config::parse_keyfile(p.to_string_lossy().into_owned())
+        } else {
+            Ok(Config::default())
+        }
+    }
+
+    /// Loads the keyfile passed with `--keyfile`, falling back to the one
+    /// `maybe_parsed_from_env` would find without one.
+    pub fn load(keyfile: Option<&str>) -> Result<Config, ConfigError> {
+        match Config::load_partial(keyfile) {
+            (config, None) => Ok(config),
+            (_, Some(e)) => Err(e),
+        }
+    }
+
+    /// Like `load`, but the valid lines of a keyfile with bad lines still
+    /// apply, and what went wrong comes back next to the config. A keyfile
+    /// that cannot be read gives the defaults.
+    pub fn load_partial(keyfile: Option<&str>) -> (Config, Option<ConfigError>) {
+        let path = match keyfile.map(PathBuf::from).or_else(keyfile_path) {
+            Some(path) => path,
+            None => return (Config::default(), None),
+        };
+        info!("loading keys from {}", path.display());
+        match Config::parse_file(&path.to_string_lossy()) {
+            Ok(parsed) => parsed.into_parts(),
+            Err(e) => {
                 error!("{}", e);
-                return Err(Error::new(ErrorKind::Other, e));
+                (Config::default(), Some(e.into()))
             }
+        }
+    }
 
-            let key = key_opt.unwrap();
-            let action = action_opt.unwrap();
+    /// Write the bindings to `path` in the format read by `parse_keyfile`.
+    pub fn write_keyfile(&self, path: &str) -> io::Result<()> {
+        let mut f = File::create(path)?;
+        f.write_all(self.to_keyfile().as_bytes())?;
+        f.sync_all()
+    }
 
-            config.config.insert(key, action);
+    // Parsing starts from the defaults, so default bindings that have since
+    // been removed are written out as unbinds.
+    fn to_keyfile(&self) -> String {
+        let defaults = Config::default();
+        let mut lines: Vec<_> = self
+            .config
+            .iter()
+            .map(|(&(mode, key), action)| (mode, action.name().unwrap_or("?"), key_to_string(key)))
+            .chain(
+                defaults
+                    .config
+                    .keys()
+                    .filter(|mode_key| !self.config.contains_key(mode_key))
+                    .map(|&(mode, key)| (mode, "unbind", key_to_string(key))),
+            )
+            .chain(self.chord_lines())
+            .collect();
+        lines.sort();
+
+        let mut buf = String::new();
+        if self.startup_mode != Mode::default() {
+            buf.push_str(&format!("startup_mode: {}\n", self.startup_mode.name()));
+        }
+        if self.leader != defaults.leader {
+            buf.push_str(&format!("leader: {}\n", key_to_string(self.leader)));
+        }
+        if self.settings.exec_capture_output != defaults.settings.exec_capture_output {
+            buf.push_str(&format!(
+                "exec_capture_output: {}\n",
+                self.settings.exec_capture_output
+            ));
+        }
+        if self.settings.exec_max_lines != defaults.settings.exec_max_lines {
+            buf.push_str(&format!(
+                "exec_max_lines: {}\n",
+                self.settings.exec_max_lines
+            ));
+        }
+        if self.settings.autosave_secs != defaults.settings.autosave_secs {
+            buf.push_str(&format!("autosave_secs: {}\n", self.settings.autosave_secs));
+        }
+        if self.settings.node_width != defaults.settings.node_width {
+            buf.push_str(&format!("node_width: {}\n", self.settings.node_width));
+        }
+        if self.settings.cascade_complete != defaults.settings.cascade_complete {
+            buf.push_str(&format!(
+                "cascade_complete: {}\n",
+                self.settings.cascade_complete
+            ));
+        }
+        if self.settings.auto_complete_parents != defaults.settings.auto_complete_parents {
+            buf.push_str(&format!(
+                "auto_complete_parents: {}\n",
+                self.settings.auto_complete_parents
+            ));
+        }
+        if self.settings.scrolloff != defaults.settings.scrolloff {
+            buf.push_str(&format!("scrolloff: {}\n", self.settings.scrolloff));
+        }
+        if self.settings.undo_depth != defaults.settings.undo_depth {
+            buf.push_str(&format!("undo_depth: {}\n", self.settings.undo_depth));
+        }
+        if self.settings.jump_keys != defaults.settings.jump_keys {
+            let keys: String = self.settings.jump_keys.iter().collect();
+            buf.push_str(&format!("jump_keys: {}\n", keys));
+        }
+        for &(part, color) in self.theme.parts().iter() {
+            if let Some(color) = color {
+                buf.push_str(&format!("theme.{}: {}\n", part, color));
+            }
+        }
+        let default_glyphs = defaults.glyphs.parts();
+        for (&(name, glyph), &(_, default)) in self.glyphs.parts().iter().zip(&default_glyphs) {
+            if glyph != default {
+                buf.push_str(&format!("glyph.{}: {}\n", name, glyph));
+            }
         }
+        let mut last_mode = None;
+        for (mode, name, key) in lines {
+            if last_mode != Some(mode) {
+                buf.push_str(&format!("# {}\n", mode.name()));
+                last_mode = Some(mode);
+            }
+            buf.push_str(&format!("{} {}: {}\n", mode.name(), name, key));
+        }
+        buf
+    }
+
+    pub fn parse_keyfile(p: String) -> Result<Config, ConfigError> {
+        Config::parse_file(&p)?.finish()
+    }
+
+    /// Checks a keyfile without loading it, returning every problem found.
+    pub fn validate_keyfile(p: &str) -> Result<(), Vec<String>> {
+        match Config::parse_file(p) {
+            Ok(ref parsed) if parsed.errors.is_empty() => Ok(()),
+            Ok(parsed) => Err(parsed.errors.iter().map(|e| e.to_string()).collect()),
+            Err(e) => Err(vec![format!("cannot read {}: {}", p, e)]),
+        }
+    }
+
+    fn parse_file(p: &str) -> io::Result<Parsed> {
+        let mut buf = String::new();
+        let mut f = File::open(p)?;
+        f.read_to_string(&mut buf)?;
+        let mut parsed = Parsed::new();
+        parsed.parse_buf(&buf, &mut vec![Path::new(p).canonicalize()?]);
+        Ok(parsed)
+    }
 
-        Ok(config)
+    #[cfg(test)]
+    pub(crate) fn parse(buf: &str) -> Result<Config, ConfigError> {
+        Config::parse_lines(buf).finish()
     }
 
-    pub fn map(&self, e: Event) -> Option<Action> {
+    // Includes in a keyfile that did not come from disk are resolved
+    // against the working directory.
+    #[cfg(test)]
+    fn parse_lines(buf: &str) -> Parsed {
+        let mut parsed = Parsed::new();
+        parsed.parse_buf(buf, &mut vec![]);
+        parsed
+    }
+
+    fn chord_lines(&self) -> impl Iterator<Item = (Mode, &'static str, String)> + '_ {
+        self.chords.iter().map(|((mode, keys), action)| {
+            (*mode, action.name().unwrap_or("?"), chord_to_string(keys))
+        })
+    }
+
+    pub fn match_chord(&self, mode: Mode, keys: &[Key]) -> ChordMatch {
+        if let Some(&action) = self.chords.get(&(mode, keys.to_vec())) {
+            return ChordMatch::Complete(action);
+        }
+        let is_prefix = self
+            .chords
+            .keys()
+            .any(|(m, chord)| *m == mode && chord.len() > keys.len() && chord.starts_with(keys));
+        if is_prefix {
+            ChordMatch::Prefix
+        } else {
+            ChordMatch::Unmatched
+        }
+    }
+
+    /// The keys that finish a chord begun with `prefix`, with the action
+    /// each runs. Keys that lead on to a longer chord aren't listed.
+    pub fn chord_candidates(&self, mode: Mode, prefix: &[Key]) -> Vec<(Key, Action)> {
+        let mut candidates: Vec<_> = self
+            .chords
+            .iter()
+            .filter(|((m, chord), _)| {
+                *m == mode && chord.len() == prefix.len() + 1 && chord.starts_with(prefix)
+            })
+            .map(|((_, chord), &action)| (chord[prefix.len()], action))
+            .collect();
+        candidates.sort_by_key(|&(key, _)| key_to_string(key));
+        candidates
+    }
+
+    pub fn map(&self, e: Event, mode: Mode) -> Option<Action> {
         use termion::event::{Key::*, MouseButton};
         match e {
             Event::Key(Char(c)) => {
-                if let Some(action) = self.config.get(&Char(c)).cloned() {
+                if let Some(action) = self.config.get(&(mode, Char(c))).cloned() {
                     Some(action)
                 } else {
                     Some(Action::Char(c))
@@ -248,11 +1410,16 @@ impl Config {
             Event::Mouse(MouseEvent::Press(MouseButton::Right, x, y)) => {
                 Some(Action::RightClick(x, y))
             }
+            Event::Mouse(MouseEvent::Press(MouseButton::Middle, x, y)) => {
+                Some(Action::MiddleClick(x, y))
+            }
+            Event::Mouse(MouseEvent::Press(MouseButton::WheelUp, ..)) => Some(Action::ScrollUp),
+            Event::Mouse(MouseEvent::Press(MouseButton::WheelDown, ..)) => Some(Action::ScrollDown),
             Event::Mouse(MouseEvent::Press(_, x, y)) => Some(Action::LeftClick(x, y)),
             Event::Mouse(MouseEvent::Release(x, y)) => Some(Action::Release(x, y)),
-            Event::Mouse(MouseEvent::Hold(..)) => None,
+            Event::Mouse(MouseEvent::Hold(x, y)) => Some(Action::Drag(x, y)),
             Event::Key(other) => {
-                let lookup = self.config.get(&other).cloned();
+                let lookup = self.config.get(&(mode, other)).cloned();
                 if lookup.is_none() {
                     warn!("Weird event {:?}", other);
                 }
@@ -265,3 +1432,884 @@ impl Config {
         }
     }
 }
+
+#[test]
+fn test_parse_overrides_default_binding() {
+    use termion::event::Key::{Char, Ctrl};
+
+    let config = Config::parse("# remap\n\nquit: C-q\nsearch: /\n").unwrap();
+    assert_eq!(
+        config.map(Event::Key(Ctrl('q')), Mode::Normal),
+        Some(Action::Quit)
+    );
+    assert_eq!(
+        config.map(Event::Key(Ctrl('q')), Mode::Insert),
+        Some(Action::Quit)
+    );
+    assert_eq!(
+        config.map(Event::Key(Char('/')), Mode::Normal),
+        Some(Action::Search)
+    );
+    // untouched defaults survive
+    assert_eq!(
+        config.map(Event::Key(Ctrl('c')), Mode::Normal),
+        Some(Action::Quit)
+    );
+}
+
+#[test]
+fn test_parse_mode_prefix() {
+    use termion::event::Key::Char;
+
+    let config = Config::parse("normal select_down: j\ninsert erase: ~\n").unwrap();
+    assert_eq!(
+        config.map(Event::Key(Char('j')), Mode::Normal),
+        Some(Action::SelectDown)
+    );
+    assert_eq!(
+        config.map(Event::Key(Char('j')), Mode::Insert),
+        Some(Action::Char('j'))
+    );
+    assert_eq!(
+        config.map(Event::Key(Char('~')), Mode::Insert),
+        Some(Action::EraseChar)
+    );
+    assert_eq!(
+        config.map(Event::Key(Char('~')), Mode::Normal),
+        Some(Action::Char('~'))
+    );
+
+    assert!(Config::parse("visual select_down: j").is_err());
+    assert!(Config::parse("normal insert select_down: j").is_err());
+}
+
+#[test]
+fn test_parse_unbind() {
+    use termion::event::Key::{Char, Ctrl};
+
+    let config = Config::parse("unbind: C-c\nunbind: enter\n").unwrap();
+    assert_eq!(config.map(Event::Key(Ctrl('c')), Mode::Normal), None);
+    assert_eq!(config.map(Event::Key(Ctrl('c')), Mode::Insert), None);
+    // printable keys fall through to plain characters
+    assert_eq!(
+        config.map(Event::Key(Char('\n')), Mode::Insert),
+        Some(Action::Char('\n'))
+    );
+
+    // unbinds only touch the given mode
+    let config = Config::parse("insert unbind: C-c").unwrap();
+    assert_eq!(config.map(Event::Key(Ctrl('c')), Mode::Insert), None);
+    assert_eq!(
+        config.map(Event::Key(Ctrl('c')), Mode::Normal),
+        Some(Action::Quit)
+    );
+
+    // unbinds run before rebinds from the same file
+    let config = Config::parse("save: C-c\nunbind: C-c\n").unwrap();
+    assert_eq!(
+        config.map(Event::Key(Ctrl('c')), Mode::Normal),
+        Some(Action::Save)
+    );
+}
+
+#[test]
+fn test_parse_key_list() {
+    use termion::event::Key::{Char, Ctrl, Up};
+
+    let config = Config::parse("normal select_up: k, up, C-p").unwrap();
+    for &key in &[Char('k'), Up, Ctrl('p')] {
+        assert_eq!(
+            config.map(Event::Key(key), Mode::Normal),
+            Some(Action::SelectUp)
+        );
+    }
+
+    let config = Config::parse("jump: ,").unwrap();
+    assert_eq!(
+        config.map(Event::Key(Char(',')), Mode::Normal),
+        Some(Action::PrefixJump)
+    );
+
+    let err = Config::parse("\nselect_up: k, bogus, up").unwrap_err();
+    assert!(err.to_string().contains("line 2"));
+}
+
+#[test]
+fn test_function_keys() {
+    assert_eq!(to_key("f1".to_owned()), Some(Key::F(1)));
+    assert_eq!(to_key("f12".to_owned()), Some(Key::F(12)));
+    assert_eq!(to_key("f0".to_owned()), None);
+    assert_eq!(to_key("f13".to_owned()), None);
+    assert_eq!(to_key("fx".to_owned()), None);
+    assert_eq!(to_key("f".to_owned()), Some(Key::Char('f')));
+}
+
+#[test]
+fn test_home_end_insert_keys() {
+    assert_eq!(to_key("home".to_owned()), Some(Key::Home));
+    assert_eq!(to_key("end".to_owned()), Some(Key::End));
+    assert_eq!(to_key("insert".to_owned()), Some(Key::Insert));
+    assert_eq!(to_key("pause".to_owned()), None);
+}
+
+#[test]
+fn test_shift_prefix() {
+    use termion::event::Key::Char;
+
+    assert_eq!(to_key("S-a".to_owned()), Some(Char('A')));
+    assert_eq!(to_key("S-k".to_owned()), to_key("K".to_owned()));
+    assert_eq!(to_key("S-K".to_owned()), Some(Char('K')));
+    assert_eq!(to_key("S-1".to_owned()), Some(Char('!')));
+    assert_eq!(to_key("S-/".to_owned()), Some(Char('?')));
+    assert_eq!(to_key("S-\\".to_owned()), Some(Char('|')));
+    assert_eq!(to_key("S-!".to_owned()), None);
+}
+
+#[test]
+fn test_control_alt_prefix() {
+    use termion::event::Key::Alt;
+
+    assert_eq!(to_key("C-A-x".to_owned()), Some(Alt('\x18')));
+    assert_eq!(to_key("A-C-x".to_owned()), Some(Alt('\x18')));
+    assert_eq!(to_key("C-A-1".to_owned()), None);
+    assert_eq!(to_key("C-A-m".to_owned()), Some(Alt('\r')));
+    // punctuation after A- is the key itself, even - and <
+    assert_eq!(to_key("A--".to_owned()), Some(Alt('-')));
+    let config = Config::parse("demote: A->\npromote: A-<").unwrap();
+    assert_eq!(
+        config.map(Event::Key(Alt('<')), Mode::Insert),
+        Some(Action::Promote)
+    );
+
+    let config = Config::parse("save: C-A-x").unwrap();
+    assert_eq!(
+        config.map(Event::Key(Alt('\x18')), Mode::Normal),
+        Some(Action::Save)
+    );
+    // plain alt+x is left alone
+    assert_eq!(config.map(Event::Key(Alt('x')), Mode::Normal), None);
+}
+
+#[test]
+fn test_action_names_round_trip() {
+    for &(_, action) in ACTION_NAMES {
+        let name = action.name().unwrap();
+        assert_eq!(to_action(name.to_owned()), Some(action));
+    }
+    assert_eq!(Action::EraseChar.name(), Some("erase"));
+    assert_eq!(Action::Char('x').name(), None);
+    assert_eq!(Action::LeftClick(1, 1).name(), None);
+}
+
+#[test]
+fn test_display_grouped_by_mode() {
+    let rendered = Config::default().to_string();
+    let normal = rendered.find("Normal mode:").unwrap();
+    let insert = rendered.find("Insert mode:").unwrap();
+    assert!(normal < insert);
+
+    let names: Vec<_> = rendered[normal..insert]
+        .lines()
+        .skip(1)
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.trim().split(':').next().unwrap())
+        .collect();
+    let mut sorted = names.clone();
+    sorted.sort();
+    assert_eq!(names, sorted);
+    assert!(names.contains(&"quit"));
+
+    // rendering is stable across runs
+    assert_eq!(rendered, Config::default().to_string());
+}
+
+#[test]
+fn test_write_keyfile_round_trip() {
+    let config = Config::default();
+    let path = env::temp_dir().join(format!("void_keys_test.{}", std::process::id()));
+    let path = path.to_str().unwrap();
+    config.write_keyfile(path).unwrap();
+    let parsed = Config::parse_keyfile(path.to_owned()).unwrap();
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(parsed.config, config.config);
+
+    let config = Config::parse("unbind: C-c\nnormal save: C-A-s\nsearch: space").unwrap();
+    let parsed = Config::parse(&config.to_keyfile()).unwrap();
+    assert_eq!(parsed.config, config.config);
+}
+
+#[test]
+fn test_key_to_string_round_trip() {
+    use termion::event::Key::*;
+
+    let mut keys = vec![
+        Esc, PageUp, PageDown, Delete, Backspace, Up, Down, Left, Right, Home, End, Insert,
+    ];
+    keys.extend((1..=12).map(F));
+    for c in "azAZ09 \n\t,.:#-?é".chars() {
+        keys.push(Char(c));
+    }
+    for c in "apzP?".chars() {
+        keys.push(Ctrl(c));
+        keys.push(Alt(c));
+    }
+    keys.push(Alt('\x01'));
+    keys.push(Alt('\x1a'));
+
+    for key in keys {
+        assert_eq!(to_key(key_to_string(key)), Some(key), "{:?}", key);
+    }
+
+    assert_eq!(key_to_string(Char(' ')), "space");
+    assert_eq!(key_to_string(Ctrl('a')), "C-a");
+    assert_eq!(key_to_string(Alt('P')), "A-P");
+    assert_eq!(key_to_string(Alt(',')), "A-comma");
+    assert_eq!(to_key("A-comma".to_owned()), Some(Alt(',')));
+    assert_eq!(key_to_string(Alt('\x7f')), "A-backspace");
+    assert_eq!(to_key("A-backspace".to_owned()), Some(Alt('\x7f')));
+    assert_eq!(key_to_string(Alt('\x18')), "C-A-x");
+}
+
+// tests setting KEYFILE, XDG_CONFIG_HOME or HOME, and tests reading HOME,
+// take turns
+#[cfg(test)]
+pub(crate) static ENV_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+#[test]
+fn test_keyfile_from_xdg_config_home() {
+    use termion::event::Key::Ctrl;
+
+    let _env = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let xdg = env::temp_dir().join(format!("void_xdg_test.{}", std::process::id()));
+    std::fs::create_dir_all(xdg.join("void")).unwrap();
+    std::fs::write(xdg.join("void").join("keys"), "save: C-s\n").unwrap();
+
+    env::remove_var("KEYFILE");
+    env::set_var("XDG_CONFIG_HOME", &xdg);
+    assert_eq!(keyfile_path(), Some(xdg.join("void").join("keys")));
+    let config = Config::maybe_parsed_from_env().unwrap();
+    assert_eq!(
+        config.map(Event::Key(Ctrl('s')), Mode::Normal),
+        Some(Action::Save)
+    );
+
+    std::fs::remove_dir_all(&xdg).unwrap();
+    assert_ne!(keyfile_path(), Some(xdg.join("void").join("keys")));
+    env::remove_var("XDG_CONFIG_HOME");
+}
+
+#[test]
+fn test_print_config() {
+    let _env = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let path = env::temp_dir().join(format!("void_print_config.{}", std::process::id()));
+    std::fs::write(&path, "leader: comma\ntoggle_layout: C-s\n").unwrap();
+    env::set_var("KEYFILE", &path);
+
+    // what `void --print-config` prints
+    let printed = Config::maybe_parsed_from_env().unwrap().to_string();
+    assert!(printed.contains("Leader: ,"), "{}", printed);
+    assert!(printed.contains("    toggle_layout: C-s"), "{}", printed);
+    assert!(printed.contains("    save: C-x"), "{}", printed);
+
+    env::remove_var("KEYFILE");
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_keyfile_flag_precedence() {
+    use termion::event::Key::Ctrl;
+
+    let _env = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let dir = env::temp_dir().join(format!("void_keyfile_flag.{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let flag = dir.join("flag");
+    let from_env = dir.join("env");
+    std::fs::write(&flag, "save: C-s\n").unwrap();
+    std::fs::write(&from_env, "quit: C-s\n").unwrap();
+    let flag = flag.to_str().unwrap();
+    let ctrl_s = |config: Config| config.map(Event::Key(Ctrl('s')), Mode::Normal);
+
+    env::set_var("KEYFILE", &from_env);
+    assert_eq!(
+        ctrl_s(Config::load(Some(flag)).unwrap()),
+        Some(Action::Save)
+    );
+    assert_eq!(ctrl_s(Config::load(None).unwrap()), Some(Action::Quit));
+
+    // with neither, and no keyfile in either config dir, the defaults apply
+    env::remove_var("KEYFILE");
+    env::set_var("XDG_CONFIG_HOME", &dir);
+    let home = env::var_os("HOME");
+    env::set_var("HOME", &dir);
+    assert_eq!(keyfile_path(), None);
+    assert_eq!(
+        ctrl_s(Config::load(None).unwrap()),
+        ctrl_s(Config::default())
+    );
+    match home {
+        Some(home) => env::set_var("HOME", home),
+        None => env::remove_var("HOME"),
+    }
+    env::remove_var("XDG_CONFIG_HOME");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_load_partial() {
+    use termion::event::Key::Ctrl;
+
+    let path = env::temp_dir().join(format!("void_load_partial.{}", std::process::id()));
+    std::fs::write(&path, "save: C-s\nbogus_action: C-b\nquit: C-q, nope\n").unwrap();
+    let (config, err) = Config::load_partial(path.to_str());
+    std::fs::remove_file(&path).unwrap();
+    assert!(matches!(err, Some(ConfigError::Multiple(ref errors)) if errors.len() == 2));
+    assert_eq!(
+        config.map(Event::Key(Ctrl('s')), Mode::Normal),
+        Some(Action::Save)
+    );
+
+    let (config, err) = Config::load_partial(path.to_str());
+    assert!(matches!(err, Some(ConfigError::Io(_))));
+    assert_eq!(
+        config.map(Event::Key(Ctrl('s')), Mode::Normal),
+        Config::default().map(Event::Key(Ctrl('s')), Mode::Normal)
+    );
+}
+
+#[test]
+fn test_parse_reports_every_error() {
+    use termion::event::Key::Ctrl;
+
+    let buf = "no colon here\nsave: C-s\nbogus_action: C-b\n\nquit: C-q, nope\n";
+    let err = Config::parse(buf).unwrap_err().to_string();
+    assert!(err.contains("line 1"));
+    assert!(err.contains("line 3"));
+    assert!(err.contains("line 5"));
+    assert_eq!(err.lines().count(), 3);
+
+    let Parsed { config, errors, .. } = Config::parse_lines(buf);
+    assert_eq!(errors.len(), 3);
+    assert!(errors[1].to_string().contains("line 3"));
+    assert!(errors[2].to_string().contains("line 5"));
+    assert_eq!(
+        config.map(Event::Key(Ctrl('s')), Mode::Normal),
+        Some(Action::Save)
+    );
+    // the line with a bad key is rejected as a whole
+    assert_eq!(
+        config.map(Event::Key(Ctrl('q')), Mode::Normal),
+        Some(Action::PopUp)
+    );
+}
+
+#[test]
+fn test_inline_comments() {
+    use termion::event::Key::{Backspace, Char, Ctrl};
+
+    let buf = "erase: backspace  # only in insert mode\ntoggle_collapsed: #\n  # indented\n";
+    let config = Config::parse(buf).unwrap();
+    assert_eq!(
+        config.map(Event::Key(Backspace), Mode::Insert),
+        Some(Action::EraseChar)
+    );
+    assert_eq!(
+        config.map(Event::Key(Char('#')), Mode::Normal),
+        Some(Action::ToggleCollapsed)
+    );
+
+    // a # inside a key is not a comment
+    let err = Config::parse("search: a#b").unwrap_err();
+    assert!(err.to_string().contains("line 1"));
+
+    let config = Config::parse("save: C-s, #  # hash too").unwrap();
+    assert_eq!(
+        config.map(Event::Key(Ctrl('s')), Mode::Normal),
+        Some(Action::Save)
+    );
+    assert_eq!(
+        config.map(Event::Key(Char('#')), Mode::Normal),
+        Some(Action::Save)
+    );
+}
+
+#[test]
+fn test_command_mode_bindings() {
+    use termion::event::Key::{Char, Ctrl, Esc};
+
+    let config = Config::default();
+    assert_eq!(
+        config.map(Event::Key(Char('\n')), Mode::Command),
+        Some(Action::SubmitCmd)
+    );
+    assert_eq!(
+        config.map(Event::Key(Esc), Mode::Command),
+        Some(Action::UnselectRet)
+    );
+    assert_eq!(
+        config.map(Event::Key(Char('\t')), Mode::Command),
+        Some(Action::CompleteCmd)
+    );
+    // normal bindings don't leak into the command line
+    assert_eq!(config.map(Event::Key(Ctrl('c')), Mode::Command), None);
+
+    let config = Config::parse("command save: C-s").unwrap();
+    assert_eq!(
+        config.map(Event::Key(Ctrl('s')), Mode::Command),
+        Some(Action::Save)
+    );
+    assert_eq!(config.map(Event::Key(Ctrl('s')), Mode::Normal), None);
+    assert_eq!(config.map(Event::Key(Ctrl('s')), Mode::Insert), None);
+}
+
+#[test]
+fn test_map_mouse_buttons() {
+    use termion::event::MouseButton::*;
+
+    let config = Config::default();
+    let press =
+        |button, x, y| config.map(Event::Mouse(MouseEvent::Press(button, x, y)), Mode::Normal);
+    assert_eq!(press(WheelUp, 3, 4), Some(Action::ScrollUp));
+    assert_eq!(press(WheelDown, 3, 4), Some(Action::ScrollDown));
+    assert_eq!(press(Left, 3, 4), Some(Action::LeftClick(3, 4)));
+    assert_eq!(press(Right, 3, 4), Some(Action::RightClick(3, 4)));
+    assert_eq!(press(Middle, 3, 4), Some(Action::MiddleClick(3, 4)));
+    assert_eq!(
+        config.map(Event::Mouse(MouseEvent::Hold(5, 6)), Mode::Normal),
+        Some(Action::Drag(5, 6))
+    );
+}
+
+#[test]
+fn test_conflicting_bindings_warn() {
+    use termion::event::Key::Ctrl;
+
+    // overriding a default is what keyfiles are for, so C-s -> search is fine
+    let buf = "search: C-s\n# later\nsave: C-s\nquit: C-s\nquit: C-s\n";
+    let Parsed {
+        config,
+        errors,
+        warnings,
+        ..
+    } = Config::parse_lines(buf);
+    assert!(errors.is_empty());
+    assert_eq!(warnings.len(), 4);
+    assert!(warnings[0].contains("line 3"));
+    assert!(warnings[0].contains("save"));
+    assert!(warnings[0].contains("search from line 1"));
+    assert_eq!(
+        config.map(Event::Key(Ctrl('s')), Mode::Normal),
+        Some(Action::Quit)
+    );
+    assert!(Config::parse(buf).is_ok());
+}
+
+#[test]
+fn test_include_keyfile() {
+    use termion::event::Key::Ctrl;
+
+    let dir = env::temp_dir().join(format!("void_include_test.{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("machine")).unwrap();
+    std::fs::write(dir.join("base"), "save: C-s\nsearch: C-f\n").unwrap();
+    // relative to the including file rather than the working directory
+    let keys = dir.join("machine").join("keys");
+    std::fs::write(&keys, "include ../base\nquit: C-s\n").unwrap();
+
+    let config = Config::parse_keyfile(keys.to_str().unwrap().to_owned());
+    std::fs::remove_dir_all(&dir).unwrap();
+    let config = config.unwrap();
+    assert_eq!(
+        config.map(Event::Key(Ctrl('s')), Mode::Normal),
+        Some(Action::Quit)
+    );
+    assert_eq!(
+        config.map(Event::Key(Ctrl('f')), Mode::Normal),
+        Some(Action::Search)
+    );
+}
+
+#[test]
+fn test_include_cycle() {
+    let dir = env::temp_dir().join(format!("void_include_cycle.{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("keys"), "save: C-s\ninclude keys\n").unwrap();
+
+    let res = Config::parse_keyfile(dir.join("keys").to_str().unwrap().to_owned());
+    std::fs::remove_dir_all(&dir).unwrap();
+    let err = res.unwrap_err().to_string();
+    assert!(err.contains("include cycle at line 2"));
+}
+
+#[test]
+fn test_named_punctuation() {
+    use termion::event::Key::Char;
+
+    assert_eq!(to_key("comma".to_owned()), Some(Char(',')));
+    assert_eq!(to_key(",".to_owned()), Some(Char(',')));
+    assert_eq!(to_key("equals".to_owned()), Some(Char('=')));
+    assert_eq!(to_key("=".to_owned()), Some(Char('=')));
+
+    let config = Config::parse("select_down: period, slash").unwrap();
+    assert_eq!(
+        config.map(Event::Key(Char('/')), Mode::Normal),
+        Some(Action::SelectDown)
+    );
+}
+
+#[test]
+fn test_validate_keyfile() {
+    let path = env::temp_dir().join(format!("void_validate_test.{}", std::process::id()));
+    let path = path.to_str().unwrap();
+
+    std::fs::write(path, "save: C-s\nnormal quit: q\n").unwrap();
+    assert_eq!(Config::validate_keyfile(path), Ok(()));
+
+    std::fs::write(path, "save: C-s\nsave C-x\nquit: nope\n").unwrap();
+    let errors = Config::validate_keyfile(path).unwrap_err();
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(errors.len(), 2);
+    assert!(errors[0].contains("line 2"));
+    assert!(errors[1].contains("line 3"));
+
+    assert_eq!(Config::validate_keyfile(path).unwrap_err().len(), 1);
+}
+
+#[test]
+fn test_startup_mode() {
+    assert_eq!(Config::default().startup_mode, Mode::Normal);
+
+    let config = Config::parse("save: C-s\nstartup_mode: insert\n").unwrap();
+    assert_eq!(config.startup_mode, Mode::Insert);
+    let parsed = Config::parse(&config.to_keyfile()).unwrap();
+    assert_eq!(parsed.startup_mode, Mode::Insert);
+
+    let err = Config::parse("\nstartup_mode: command\n")
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("line 2"));
+    assert!(Config::parse("startup_mode: sideways").is_err());
+}
+
+#[test]
+fn test_exec_capture_settings() {
+    let config = Config::default();
+    assert!(!config.settings.exec_capture_output);
+    assert_eq!(config.settings.exec_max_lines, 100);
+
+    let config = Config::parse("exec_capture_output: true\nexec_max_lines: 5\n").unwrap();
+    assert!(config.settings.exec_capture_output);
+    assert_eq!(config.settings.exec_max_lines, 5);
+    let parsed = Config::parse(&config.to_keyfile()).unwrap();
+    assert!(parsed.settings.exec_capture_output);
+    assert_eq!(parsed.settings.exec_max_lines, 5);
+
+    let err = Config::parse("\nexec_capture_output: yes").unwrap_err();
+    assert!(matches!(err, ConfigError::InvalidValue { line: 2, ref raw } if raw == "yes"));
+    assert!(Config::parse("exec_max_lines: -1").is_err());
+}
+
+#[test]
+fn test_autosave_secs() {
+    assert_eq!(Config::default().settings.autosave_secs, 60);
+    let config = Config::parse("autosave_secs: 0").unwrap();
+    assert_eq!(config.settings.autosave_secs, 0);
+    let parsed = Config::parse(&config.to_keyfile()).unwrap();
+    assert_eq!(parsed.settings.autosave_secs, 0);
+    assert!(!Config::default().to_keyfile().contains("autosave_secs"));
+
+    let err = Config::parse("autosave_secs: soon").unwrap_err();
+    assert!(matches!(err, ConfigError::InvalidValue { line: 1, ref raw } if raw == "soon"));
+}
+
+#[test]
+fn test_jump_keys() {
+    let config = Config::parse("jump_keys: asdfghjkl").unwrap();
+    assert_eq!(
+        config.settings.jump_keys,
+        "asdfghjkl".chars().collect::<Vec<_>>()
+    );
+    let parsed = Config::parse(&config.to_keyfile()).unwrap();
+    assert_eq!(parsed.settings.jump_keys, config.settings.jump_keys);
+    assert!(!Config::default().to_keyfile().contains("jump_keys"));
+
+    assert!(Config::parse("jump_keys: a").is_err());
+    assert!(Config::parse("jump_keys: asdfa").is_err());
+}
+
+#[test]
+fn test_node_width() {
+    assert_eq!(Config::default().settings.node_width, 0);
+    let config = Config::parse("node_width: 30").unwrap();
+    assert_eq!(config.settings.node_width, 30);
+    let parsed = Config::parse(&config.to_keyfile()).unwrap();
+    assert_eq!(parsed.settings.node_width, 30);
+    assert!(Config::parse("node_width: wide").is_err());
+}
+
+#[test]
+fn test_scrolloff() {
+    assert_eq!(Config::default().settings.scrolloff, 2);
+    let config = Config::parse("scrolloff: 5").unwrap();
+    assert_eq!(config.settings.scrolloff, 5);
+    let parsed = Config::parse(&config.to_keyfile()).unwrap();
+    assert_eq!(parsed.settings.scrolloff, 5);
+    assert!(!Config::default().to_keyfile().contains("scrolloff"));
+    assert!(Config::parse("scrolloff: -1").is_err());
+}
+
+#[test]
+fn test_undo_depth() {
+    assert_eq!(Config::default().settings.undo_depth, DEFAULT_UNDO_DEPTH);
+    let config = Config::parse("undo_depth: 3").unwrap();
+    assert_eq!(config.settings.undo_depth, 3);
+    let parsed = Config::parse(&config.to_keyfile()).unwrap();
+    assert_eq!(parsed.settings.undo_depth, 3);
+    assert!(!Config::default().to_keyfile().contains("undo_depth"));
+    assert!(Config::parse("undo_depth: lots").is_err());
+}
+
+#[test]
+fn test_cascade_complete() {
+    assert!(!Config::default().settings.cascade_complete);
+    let config = Config::parse("cascade_complete: true").unwrap();
+    assert!(config.settings.cascade_complete);
+    let parsed = Config::parse(&config.to_keyfile()).unwrap();
+    assert!(parsed.settings.cascade_complete);
+    assert!(Config::parse("cascade_complete: yes").is_err());
+
+    let config = Config::parse("auto_complete_parents: true").unwrap();
+    assert!(config.settings.auto_complete_parents);
+    let parsed = Config::parse(&config.to_keyfile()).unwrap();
+    assert!(parsed.settings.auto_complete_parents);
+}
+
+#[test]
+fn test_config_error_variants() {
+    use ConfigError::*;
+
+    let err = Config::parse("save C-s").unwrap_err();
+    assert!(matches!(err, MissingColon { line: 1 }));
+    let err = Config::parse("\nsave: C-s, nope").unwrap_err();
+    assert!(matches!(err, UnknownKey { line: 2, ref raw } if raw == "nope"));
+    let err = Config::parse("\n\nbogus: C-b").unwrap_err();
+    assert!(matches!(err, UnknownAction { line: 3, ref raw } if raw == "bogus"));
+    let err = Config::parse("visual save: C-s").unwrap_err();
+    assert!(matches!(err, UnknownMode { line: 1, ref raw } if raw == "visual"));
+
+    let missing = env::temp_dir().join(format!("void_missing_keys.{}", std::process::id()));
+    let err = Config::parse_keyfile(missing.to_string_lossy().into_owned()).unwrap_err();
+    assert!(matches!(err, Io(ref e) if e.kind() == io::ErrorKind::NotFound));
+    let err = Config::parse(&format!("include {}", missing.display())).unwrap_err();
+    assert!(matches!(err, Included { line: 1, ref source, .. } if matches!(**source, Io(_))));
+}
+
+#[test]
+fn test_default_action_bindings() {
+    use termion::event::Key::{Alt, Char, Ctrl, Home, F};
+
+    let config = Config::default();
+    let rows = [
+        (Mode::Normal, Alt('c'), Some(Action::SelectFirstChild)),
+        (Mode::Normal, Alt('C'), Some(Action::SelectLastChild)),
+        (Mode::Normal, Home, Some(Action::SelectRoot)),
+        (Mode::Insert, Alt('>'), Some(Action::Demote)),
+        (Mode::Insert, Alt('<'), Some(Action::Promote)),
+        (Mode::Normal, Alt('d'), Some(Action::DuplicateNode)),
+        (Mode::Normal, Alt('+'), Some(Action::RaisePriority)),
+        (Mode::Normal, Alt('-'), Some(Action::LowerPriority)),
+        (Mode::Insert, Ctrl('b'), Some(Action::ToggleInProgress)),
+        (Mode::Normal, Alt('t'), Some(Action::CollapseAll)),
+        (Mode::Normal, Alt('T'), Some(Action::ExpandAll)),
+        (Mode::Normal, Alt('f'), Some(Action::FocusBranch)),
+        (Mode::Normal, Alt('F'), Some(Action::FilterByTag)),
+        (Mode::Normal, Alt('m'), Some(Action::SetMark)),
+        (Mode::Insert, Alt('\''), Some(Action::JumpMark)),
+        (Mode::Normal, Alt('{'), Some(Action::ScrollLeft)),
+        (Mode::Normal, Alt('}'), Some(Action::ScrollRight)),
+        (Mode::Normal, Alt('M'), Some(Action::ToggleMinimap)),
+        (Mode::Normal, Alt('='), Some(Action::CenterSelection)),
+        (Mode::Normal, Ctrl('o'), Some(Action::FuzzyFind)),
+        (Mode::Normal, Alt('u'), Some(Action::SearchNext)),
+        (Mode::Insert, Alt('U'), Some(Action::SearchPrev)),
+        (Mode::Command, Ctrl('t'), Some(Action::ToggleSearchMode)),
+        (Mode::Normal, Alt('z'), Some(Action::Undo)),
+        (Mode::Insert, Alt('Z'), Some(Action::Redo)),
+        (Mode::Insert, Alt('k'), Some(Action::CancelExec)),
+        (Mode::Insert, Alt('y'), Some(Action::PasteAsChild)),
+        (Mode::Insert, Alt('w'), Some(Action::YankNode)),
+        (Mode::Insert, Alt('W'), Some(Action::CutNode)),
+        (Mode::Insert, Alt('o'), Some(Action::OpenUrl)),
+        (Mode::Insert, Alt('E'), Some(Action::EditInEditor)),
+        (Mode::Insert, Alt('Y'), Some(Action::CopyTextToClipboard)),
+        (Mode::Normal, Alt('v'), Some(Action::PasteTextFromClipboard)),
+        (Mode::Insert, Alt('r'), Some(Action::StartArrow)),
+        (Mode::Insert, Alt('R'), Some(Action::EndArrow)),
+        (Mode::Insert, Alt('e'), Some(Action::RemoveArrow)),
+        (Mode::Normal, Alt('l'), Some(Action::ToggleLayout)),
+        (Mode::Normal, Alt('['), Some(Action::ScrollLogsUp)),
+        (Mode::Insert, Alt(']'), Some(Action::ScrollLogsDown)),
+        (Mode::Normal, Alt('L'), Some(Action::ToggleLogLevel)),
+        (Mode::Insert, Alt('\r'), Some(Action::InsertNewline)),
+        (Mode::Normal, Alt('\r'), None),
+        (Mode::Insert, Char('\n'), Some(Action::CreateSibling)),
+        (Mode::Insert, Alt('\x7f'), Some(Action::EraseWord)),
+        (Mode::Insert, Ctrl('w'), Some(Action::DrillDown)),
+        (Mode::Insert, F(2), Some(Action::RenameNode)),
+    ];
+    for &(mode, key, action) in &rows {
+        assert_eq!(config.map(Event::Key(key), mode), action, "{:?}", key);
+    }
+}
+
+#[test]
+fn test_apply_setting() {
+    let mut settings = Settings::default();
+    settings.apply_setting("node_width", "40").unwrap();
+    assert_eq!(settings.node_width, 40);
+    settings
+        .apply_setting("exec_capture_output", "true")
+        .unwrap();
+    assert!(settings.exec_capture_output);
+
+    // invalid values leave the setting as it was
+    let err = settings.apply_setting("node_width", "wide").unwrap_err();
+    assert_eq!(err, "invalid value for node_width: \"wide\"");
+    assert_eq!(settings.node_width, 40);
+    assert!(settings.apply_setting("jump_keys", "aa").is_err());
+
+    let err = settings.apply_setting("colour", "blue").unwrap_err();
+    assert_eq!(err, "unknown setting: colour");
+    assert_eq!(settings.node_width, 40);
+}
+
+#[test]
+fn test_chords() {
+    use termion::event::Key::{Char, Ctrl};
+
+    let config = Config::parse("normal toggle_layout: gg\nsave: C-x C-s").unwrap();
+    assert_eq!(
+        config.match_chord(Mode::Normal, &[Char('g')]),
+        ChordMatch::Prefix
+    );
+    assert_eq!(
+        config.match_chord(Mode::Normal, &[Char('g'), Char('g')]),
+        ChordMatch::Complete(Action::ToggleLayout)
+    );
+    assert_eq!(
+        config.match_chord(Mode::Normal, &[Char('g'), Char('x')]),
+        ChordMatch::Unmatched
+    );
+    // the mode prefix applies to chords as it does to keys
+    assert_eq!(
+        config.match_chord(Mode::Insert, &[Char('g')]),
+        ChordMatch::Unmatched
+    );
+    assert_eq!(
+        config.match_chord(Mode::Insert, &[Ctrl('x'), Ctrl('s')]),
+        ChordMatch::Complete(Action::Save)
+    );
+
+    let parsed = Config::parse(&config.to_keyfile()).unwrap();
+    assert_eq!(parsed.chords, config.chords);
+    // unbinds apply before the other lines, as they do for keys
+    let rebound = Config::parse("normal toggle_layout: gg\nnormal unbind: g g").unwrap();
+    assert_eq!(
+        rebound.match_chord(Mode::Normal, &[Char('g'), Char('g')]),
+        ChordMatch::Complete(Action::ToggleLayout)
+    );
+    let mut parsed = Config::parse_lines("normal toggle_layout: gg");
+    parsed.parse_buf("normal unbind: g g", &mut vec![]);
+    assert!(parsed.finish().unwrap().chords.is_empty());
+    let warnings = Config::parse_lines("normal save: gg\nnormal undo: g g").warnings;
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].contains("line 2 binds g g in normal mode to undo"));
+    assert!(Config::parse("save: g-").is_err());
+    assert!(Config::parse("save: ggg").is_err());
+}
+
+#[test]
+fn test_leader() {
+    use termion::event::Key::Char;
+
+    let spaced = Config::parse("normal save: leader s").unwrap();
+    assert_eq!(
+        spaced.match_chord(Mode::Normal, &[Char(' '), Char('s')]),
+        ChordMatch::Complete(Action::Save)
+    );
+
+    let config = Config::parse("leader: comma\nnormal save: leader s").unwrap();
+    assert_eq!(config.leader, Char(','));
+    assert_eq!(
+        config.match_chord(Mode::Normal, &[Char(','), Char('s')]),
+        ChordMatch::Complete(Action::Save)
+    );
+    let parsed = Config::parse(&config.to_keyfile()).unwrap();
+    assert_eq!(parsed.leader, Char(','));
+    assert_eq!(parsed.chords, config.chords);
+    // the leader applies to chords above the line that sets it too
+    let later = Config::parse("normal save: leader s\nleader: comma").unwrap();
+    assert_eq!(later.chords, config.chords);
+
+    assert!(Config::parse("leader: nope").is_err());
+}
+
+#[test]
+fn test_chord_candidates() {
+    use termion::event::Key::Char;
+
+    let config = Config::parse(
+        "normal save: leader s\nnormal toggle_show_logs: leader l\n\
+         normal toggle_layout: leader g g\ninsert undo: leader u",
+    )
+    .unwrap();
+    assert_eq!(
+        config.chord_candidates(Mode::Normal, &[Char(' ')]),
+        vec![
+            (Char('l'), Action::ToggleShowLogs),
+            (Char('s'), Action::Save)
+        ]
+    );
+    assert_eq!(
+        config.chord_candidates(Mode::Normal, &[Char(' '), Char('g')]),
+        vec![(Char('g'), Action::ToggleLayout)]
+    );
+    assert!(config
+        .chord_candidates(Mode::Normal, &[Char('x')])
+        .is_empty());
+}
+
+#[test]
+fn test_theme() {
+    use crate::Color;
+
+    let config = Config::parse("theme.selected: blue\ntheme.background: default").unwrap();
+    assert_eq!(config.theme.selected, Some(Color::Ansi(4)));
+    assert_eq!(config.theme.background, Some(Color::Reset));
+    assert_eq!(config.theme.arrows, None);
+    let parsed = Config::parse(&config.to_keyfile()).unwrap();
+    assert_eq!(parsed.theme, config.theme);
+
+    let err = Config::parse("theme.selected: mauve").unwrap_err();
+    assert!(err.to_string().contains("mauve"), "{}", err);
+    assert!(Config::parse("theme.border: blue").is_err());
+}
+
+#[test]
+fn test_glyphs() {
+    let config = Config::parse("glyph.completed: ✓\nglyph.collapsed: ▸").unwrap();
+    assert_eq!(config.glyphs.completed, "✓");
+    assert_eq!(config.glyphs.collapsed, "▸");
+    assert_eq!(config.glyphs.in_progress, Glyphs::default().in_progress);
+    let parsed = Config::parse(&config.to_keyfile()).unwrap();
+    assert_eq!(parsed.glyphs, config.glyphs);
+
+    // one grapheme can be several chars
+    let mut glyphs = Glyphs::default();
+    glyphs.apply_glyph("free_text", "e\u{301}").unwrap();
+    assert_eq!(glyphs.free_text, "e\u{301}");
+
+    assert!(Config::parse("glyph.completed: ok").is_err());
+    assert!(Config::parse("glyph.completed:").is_err());
+    assert!(Config::parse("glyph.checkbox: ✓").is_err());
+    assert!(glyphs.apply_glyph("completed", "[x]").is_err());
+    assert_eq!(glyphs.completed, Glyphs::default().completed);
+}
@@ -1,4 +1,7 @@
+use std::convert::TryFrom;
+
 use regex::Regex;
+use time::Date;
 
 pub fn dateparse(input: String) -> Option<u64> {
     lazy_static! {
@@ -61,3 +64,19 @@ pub fn dateparse(input: String) -> Option<u64> {
 
     None
 }
+
+/// Parses a `YYYY-MM-DD` due date into the seconds `Meta::due` holds.
+pub fn parse_due_date(input: &str) -> Result<u64, String> {
+    let invalid = || format!("not a date: {}, expected YYYY-MM-DD", input);
+    let date = Date::parse(input.trim(), "%Y-%m-%d").map_err(|_| invalid())?;
+    u64::try_from(date.midnight().assume_utc().timestamp()).map_err(|_| invalid())
+}
+
+#[test]
+fn test_parse_due_date() {
+    assert_eq!(parse_due_date("1970-01-02"), Ok(86400));
+    assert_eq!(parse_due_date("2024-06-01"), Ok(1_717_200_000));
+    assert!(parse_due_date("2024-13-01").is_err());
+    assert!(parse_due_date("tomorrow").is_err());
+    assert!(parse_due_date("1969-12-31").is_err());
+}
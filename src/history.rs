@@ -0,0 +1,118 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::{Node, NodeID};
+
+pub const DEFAULT_UNDO_DEPTH: usize = 100;
+
+/// A reversible change to the tree. Applying an op hands back the op that
+/// reverses it, so the same values serve both undo and redo.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Op {
+    /// Put a node back as it was, removing it if it did not exist.
+    Restore(NodeID, Option<Box<Node>>),
+    /// Put the arrow list back as it was.
+    Arrows(Vec<(NodeID, NodeID)>),
+}
+
+/// Undo and redo stacks of steps, where a step is every op recorded while
+/// handling a single event.
+#[derive(Debug, Default)]
+pub struct History {
+    undo: VecDeque<Vec<Op>>,
+    redo: Vec<Vec<Op>>,
+    // ops for the event being handled, None when not recording
+    pending: Option<Vec<Op>>,
+    touched: HashSet<NodeID>,
+    arrows_touched: bool,
+    // node whose text the last step edited, so typing undoes as one step
+    last_edit: Option<NodeID>,
+}
+
+impl History {
+    pub fn begin(&mut self) {
+        self.pending = Some(vec![]);
+        self.touched.clear();
+        self.arrows_touched = false;
+    }
+
+    /// Whether the prior state of `node_id` still needs recording for the
+    /// current step.
+    pub fn wants_node(&self, node_id: NodeID) -> bool {
+        self.pending.is_some() && !self.touched.contains(&node_id)
+    }
+
+    pub fn wants_arrows(&self) -> bool {
+        self.pending.is_some() && !self.arrows_touched
+    }
+
+    pub fn record(&mut self, op: Op) {
+        if let Some(ref mut pending) = self.pending {
+            match op {
+                Op::Restore(node_id, _) => {
+                    self.touched.insert(node_id);
+                }
+                Op::Arrows(_) => self.arrows_touched = true,
+            }
+            pending.push(op);
+        }
+    }
+
+    pub fn end(&mut self) -> Vec<Op> {
+        self.pending.take().unwrap_or_default()
+    }
+
+    /// Pushes a finished step, dropping the oldest beyond `depth`. A step
+    /// that only edits the text of the node edited by the previous step is
+    /// folded into it.
+    pub fn push(&mut self, step: Vec<Op>, edited: Option<NodeID>, depth: usize) {
+        if step.is_empty() {
+            return;
+        }
+        self.redo.clear();
+        if edited.is_some() && edited == self.last_edit && !self.undo.is_empty() {
+            return;
+        }
+        self.last_edit = edited;
+        self.undo.push_back(step);
+        while self.undo.len() > depth {
+            self.undo.pop_front();
+        }
+    }
+
+    pub fn pop_undo(&mut self) -> Option<Vec<Op>> {
+        self.last_edit = None;
+        self.undo.pop_back()
+    }
+
+    pub fn pop_redo(&mut self) -> Option<Vec<Op>> {
+        self.last_edit = None;
+        self.redo.pop()
+    }
+
+    pub fn push_redo(&mut self, step: Vec<Op>) {
+        self.redo.push(step);
+    }
+
+    pub fn push_undo(&mut self, step: Vec<Op>) {
+        self.undo.push_back(step);
+    }
+}
+
+#[test]
+fn test_history_depth() {
+    let mut history = History::default();
+    for id in 1..=5 {
+        history.push(vec![Op::Restore(id, None)], None, 3);
+    }
+    assert_eq!(history.undo.len(), 3);
+    assert_eq!(history.pop_undo(), Some(vec![Op::Restore(5, None)]));
+}
+
+#[test]
+fn test_history_folds_edits() {
+    let mut history = History::default();
+    history.push(vec![Op::Restore(1, None)], Some(1), 10);
+    history.push(vec![Op::Restore(1, None)], Some(1), 10);
+    history.push(vec![Op::Restore(2, None)], Some(2), 10);
+    assert_eq!(history.undo.len(), 2);
+}
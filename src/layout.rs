@@ -0,0 +1,140 @@
+use std::{collections::HashMap, f64::consts::TAU};
+
+use crate::{Coords, Node, NodeID};
+
+// rows between the rings of each depth in the radial layout
+const RING_ROWS: f64 = 4.0;
+// terminal cells are about twice as tall as they are wide
+const CELL_ASPECT: f64 = 2.0;
+
+/// How the top level trees of the current view are placed on screen.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum LayoutKind {
+    /// Each tree is drawn wherever it was placed.
+    #[default]
+    Free,
+    /// Trees are stacked top-down in sibling order, ignoring where they
+    /// were placed.
+    Tree,
+    /// The view root sits in the middle with its descendants fanned out
+    /// around it, one ring per depth.
+    Radial,
+}
+
+impl LayoutKind {
+    pub fn next(self) -> LayoutKind {
+        match self {
+            LayoutKind::Free => LayoutKind::Tree,
+            LayoutKind::Tree => LayoutKind::Radial,
+            LayoutKind::Radial => LayoutKind::Free,
+        }
+    }
+}
+
+/// Stacks top level trees, given in sibling order as the id and drawn
+/// (width, height) of each, down the left edge below the header with a
+/// blank line between them. Within a tree, rows already follow depth and
+/// sibling order.
+pub fn tree_positions(trees: &[(NodeID, Coords)]) -> HashMap<NodeID, Coords> {
+    let mut y: u16 = 2;
+    let mut placed = HashMap::new();
+    for &(node_id, (_, height)) in trees {
+        placed.insert(node_id, (1, y));
+        y = y.saturating_add(height).saturating_add(1);
+    }
+    placed
+}
+
+/// Places `root` in the middle of `bounds` and every node below it on a
+/// ring whose radius grows with depth. Each node's children split its
+/// share of the circle evenly, so subtrees keep to their own wedge.
+/// Children of collapsed nodes are left out, and coordinates are clamped
+/// to the screen below the header.
+pub fn radial_positions(
+    root: NodeID,
+    nodes: &HashMap<NodeID, Node>,
+    bounds: Coords,
+) -> HashMap<NodeID, Coords> {
+    let center = (f64::from(bounds.0) / 2.0, f64::from(bounds.1) / 2.0 + 1.0);
+    let clamp = |x: f64, y: f64| -> Coords {
+        (
+            x.round().max(1.0).min(f64::from(bounds.0.max(1))) as u16,
+            y.round().max(2.0).min(f64::from(bounds.1.max(2))) as u16,
+        )
+    };
+
+    let mut placed = HashMap::new();
+    placed.insert(root, clamp(center.0, center.1));
+    let mut to_place = vec![(root, 1, 0.0, TAU)];
+    while let Some((node_id, depth, start, end)) = to_place.pop() {
+        let node = match nodes.get(&node_id) {
+            Some(node) => node,
+            None => continue,
+        };
+        if node.children.is_empty() || (node.collapsed && node_id != root) {
+            continue;
+        }
+        let step = (end - start) / node.children.len() as f64;
+        let radius = f64::from(depth) * RING_ROWS;
+        for (i, &child) in node.children.iter().enumerate() {
+            let from = start + step * i as f64;
+            let angle = from + step / 2.0;
+            let x = center.0 + radius * CELL_ASPECT * angle.cos();
+            let y = center.1 + radius * angle.sin();
+            placed.insert(child, clamp(x, y));
+            to_place.push((child, depth + 1, from, from + step));
+        }
+    }
+    placed
+}
+
+#[test]
+fn test_tree_positions() {
+    let trees = [(7, (20, 3)), (2, (10, 1)), (5, (8, 2))];
+    let placed = tree_positions(&trees);
+    assert_eq!(placed[&7], (1, 2));
+    assert_eq!(placed[&2], (1, 6));
+    assert_eq!(placed[&5], (1, 8));
+}
+
+#[test]
+fn test_radial_positions() {
+    let mut nodes = HashMap::new();
+    nodes.insert(
+        0,
+        Node {
+            children: vec![1, 2, 3, 4],
+            ..Node::default()
+        },
+    );
+    for id in 1..=4 {
+        nodes.insert(
+            id,
+            Node {
+                id,
+                ..Node::default()
+            },
+        );
+    }
+    let placed = radial_positions(0, &nodes, (80, 24));
+    let (cx, cy) = placed[&0];
+    assert_eq!((cx, cy), (40, 13));
+
+    let angles: Vec<f64> = (1..=4)
+        .map(|id| {
+            let (x, y) = placed[&id];
+            let dx = (f64::from(x) - f64::from(cx)) / CELL_ASPECT;
+            let dy = f64::from(y) - f64::from(cy);
+            dy.atan2(dx).to_degrees().rem_euclid(360.0)
+        })
+        .collect();
+    for pair in angles.windows(2) {
+        assert!((pair[1] - pair[0] - 90.0).abs() < 10.0, "{:?}", angles);
+    }
+
+    // everything stays on screen, however deep
+    let placed = radial_positions(0, &nodes, (6, 4));
+    for &(x, y) in placed.values() {
+        assert!((1..=6).contains(&x) && (2..=4).contains(&y));
+    }
+}
@@ -6,9 +6,12 @@ extern crate lazy_static;
 #[macro_use]
 extern crate log;
 
+mod clipboard;
 mod colors;
 mod config;
 mod dateparse;
+mod history;
+mod layout;
 mod logging;
 mod meta;
 mod node;
@@ -23,21 +26,25 @@ mod task;
 use std::{
     cmp,
     collections::HashMap,
+    env,
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use regex::Regex;
+use time::{Date, OffsetDateTime};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 pub use crate::{
-    colors::random_fg_color,
-    config::{Action, Config},
+    colors::{color_enabled, parse_color, random_fg_color, Color, Theme},
+    config::{Action, ChordMatch, Config, ConfigError, Glyphs, Mode},
     dateparse::dateparse,
+    layout::LayoutKind,
     logging::init_screen_log,
     meta::Meta,
     node::Node,
     pack::Pack,
-    screen::Screen,
-    serialization::{deserialize_screen, serialize_screen},
+    screen::{search_filter, Screen, SearchFilter, SearchMode},
+    serialization::{deserialize_screen, open_screen, serialize_screen},
     tagdb::TagDB,
 };
 
@@ -68,6 +75,11 @@ fn now() -> Duration {
         .expect("should be able to get the time")
 }
 
+// due dates are stored as UTC midnight, so today is a UTC day too
+fn today() -> Date {
+    OffsetDateTime::from_unix_timestamp(now().as_secs() as i64).date()
+}
+
 pub fn re_matches<A: std::str::FromStr>(re: &Regex, on: &str) -> Vec<A> {
     let mut ret = vec![];
     if re.is_match(on) {
@@ -82,6 +94,177 @@ pub fn re_matches<A: std::str::FromStr>(re: &Regex, on: &str) -> Vec<A> {
     ret
 }
 
+// Scores text as a fuzzy match for query: the query's characters have to
+// appear in text in order, ignoring case. Runs of consecutive characters and
+// characters at the start of a word score higher, and every character
+// skipped over before the match is complete costs a point.
+fn fuzzy_score(query: &str, text: &str) -> Option<i64> {
+    let mut query = query.chars().flat_map(char::to_lowercase).peekable();
+    let mut score = 0;
+    let mut prev = None;
+    let mut consecutive = false;
+    for c in text.chars().flat_map(char::to_lowercase) {
+        match query.peek() {
+            None => break,
+            Some(&q) if q == c => {
+                query.next();
+                score += 1;
+                if consecutive {
+                    score += 5;
+                }
+                if !matches!(prev, Some(p) if char::is_alphanumeric(p)) {
+                    score += 3;
+                }
+                consecutive = true;
+            }
+            Some(_) => {
+                score -= 1;
+                consecutive = false;
+            }
+        }
+        prev = Some(c);
+    }
+    if query.peek().is_none() {
+        Some(score)
+    } else {
+        None
+    }
+}
+
+// Substitutes $VAR and ${VAR} with the value of the environment variable,
+// or nothing if it is unset. $$ is a literal dollar, as is a $ that isn't
+// followed by a variable name.
+fn expand_env(text: &str) -> String {
+    let is_name = |c: char| c == '_' || c.is_ascii_alphanumeric();
+    let mut ret = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(idx) = rest.find('$') {
+        ret.push_str(&rest[..idx]);
+        rest = &rest[idx + 1..];
+        let (name, after) = if let Some(braced) = rest.strip_prefix('{') {
+            match braced.find('}') {
+                Some(end) => (&braced[..end], &braced[end + 1..]),
+                None => ("", rest),
+            }
+        } else if rest.starts_with('$') {
+            ret.push('$');
+            rest = &rest[1..];
+            continue;
+        } else if rest.starts_with(|c: char| c == '_' || c.is_ascii_alphabetic()) {
+            let end = rest.find(|c| !is_name(c)).unwrap_or(rest.len());
+            (&rest[..end], &rest[end..])
+        } else {
+            ("", rest)
+        };
+        if name.is_empty() {
+            ret.push('$');
+        } else {
+            ret.push_str(&env::var(name).unwrap_or_default());
+        }
+        rest = after;
+    }
+    ret.push_str(rest);
+    ret
+}
+
+// The first http or https URL in text, ending at whitespace. Punctuation
+// closing a sentence around the URL is left off, as is a closing paren
+// the URL doesn't open.
+fn find_url(text: &str) -> Option<&str> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"https?://[^\s<>]+").unwrap();
+    }
+    let url = RE.find(text)?.as_str();
+    let url = url.trim_end_matches(|c| ".,;:!?'\"".contains(c));
+    if url.ends_with(')') && !url.contains('(') {
+        Some(&url[..url.len() - 1])
+    } else {
+        Some(url)
+    }
+}
+
+// Labels `count` things with the shortest labels typable from `alphabet`,
+// none a prefix of another, so a label is chosen as soon as it is typed
+// out. Labels are lengthened starting from the back of the alphabet, so
+// the first keys stay single characters the longest.
+fn assign_jump_labels(count: usize, alphabet: &[char]) -> Vec<String> {
+    let mut labels: Vec<String> = alphabet.iter().map(|c| c.to_string()).collect();
+    // kept sorted by length, so the last of the shortest labels is the
+    // next one to turn into a prefix
+    while labels.len() < count && alphabet.len() > 1 {
+        let shortest = labels[0].len();
+        let last_shortest = labels.iter().rposition(|l| l.len() == shortest).unwrap();
+        let prefix = labels.remove(last_shortest);
+        labels.extend(alphabet.iter().map(|c| format!("{}{}", prefix, c)));
+    }
+    labels.truncate(count);
+    labels
+}
+
+/// How many terminal columns `s` takes up, with wide characters like CJK
+/// and most emoji taking two.
+fn display_width(s: &str) -> usize {
+    UnicodeWidthStr::width(s)
+}
+
+// The longest start of `text` that fits in `width` columns.
+fn take_width(text: &str, width: usize) -> &str {
+    let mut used = 0;
+    for (idx, c) in text.char_indices() {
+        used += c.width().unwrap_or(0);
+        if used > width {
+            return &text[..idx];
+        }
+    }
+    text
+}
+
+// Breaks text into its lines, then breaks those into lines of at most
+// `width` columns at whitespace, splitting words that are longer than a
+// whole line. A width of 0 only breaks at newlines.
+fn wrap_text(text: &str, width: usize) -> Vec<String> {
+    text.split('\n')
+        .flat_map(|line| wrap_line(line, width))
+        .collect()
+}
+
+// Lines that already fit are left as they are.
+fn wrap_line(text: &str, width: usize) -> Vec<String> {
+    if width == 0 || display_width(text) <= width {
+        return vec![text.to_owned()];
+    }
+    let mut lines = vec![];
+    let mut line = String::new();
+    let mut line_width = 0;
+    for mut word in text.split_whitespace() {
+        let word_width = display_width(word);
+        if line_width > 0 && line_width + 1 + word_width <= width {
+            line.push(' ');
+            line.push_str(word);
+            line_width += 1 + word_width;
+            continue;
+        }
+        if line_width > 0 {
+            lines.push(std::mem::take(&mut line));
+        }
+        while display_width(word) > width {
+            // a wide character goes on a line of its own if it must
+            let mut head = take_width(word, width);
+            if head.is_empty() {
+                head = &word[..word.chars().next().unwrap().len_utf8()];
+            }
+            lines.push(head.to_owned());
+            word = &word[head.len()..];
+        }
+        line_width = display_width(word);
+        line = word.to_owned();
+    }
+    if line_width > 0 || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
 #[test]
 fn test_regex_parsing() {
     let re = Regex::new(r"(\S+)").unwrap();
@@ -90,3 +273,133 @@ fn test_regex_parsing() {
         vec!["yo".to_owned(), "ho".to_owned(), "ho".to_owned()]
     );
 }
+
+#[test]
+fn test_fuzzy_score() {
+    assert!(fuzzy_score("cfg", "config file").is_some());
+    assert!(fuzzy_score("CFG", "config file").is_some());
+    assert_eq!(fuzzy_score("gfc", "config file"), None);
+    assert_eq!(fuzzy_score("xyz", "config file"), None);
+    assert_eq!(fuzzy_score("configs", "config"), None);
+
+    let score = |q| fuzzy_score(q, "config file").unwrap();
+    // contiguous beats scattered, word starts beat the middle of words
+    assert!(score("con") > score("cnf"));
+    assert!(fuzzy_score("b", "foo bar") > fuzzy_score("b", "foobar"));
+    assert!(fuzzy_score("cfg", "cfg").unwrap() > score("cfg"));
+    assert!(fuzzy_score("fb", "foo bar") > fuzzy_score("fb", "fxb"));
+}
+
+#[test]
+fn test_expand_env() {
+    let _env = config::ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let home = env::var("HOME").unwrap_or_default();
+    assert_eq!(expand_env("ls $HOME"), format!("ls {}", home));
+    assert_eq!(expand_env("ls ${HOME}/src"), format!("ls {}/src", home));
+    assert_eq!(expand_env("echo [${VOID_SURELY_UNSET}]"), "echo []");
+    assert_eq!(expand_env("echo [$VOID_SURELY_UNSET]"), "echo []");
+    assert_eq!(expand_env("costs $$5"), "costs $5");
+    assert_eq!(expand_env("$$HOME"), "$HOME");
+    assert_eq!(expand_env("50$ and ${unclosed"), "50$ and ${unclosed");
+}
+
+#[test]
+fn test_find_url() {
+    assert_eq!(
+        find_url("read https://example.com/a?b=1 later"),
+        Some("https://example.com/a?b=1")
+    );
+    assert_eq!(find_url("http://x.org"), Some("http://x.org"));
+    assert_eq!(
+        find_url("docs (see https://example.com/guide)."),
+        Some("https://example.com/guide")
+    );
+    assert_eq!(
+        find_url("https://en.wikipedia.org/wiki/Void_(astronomy), first"),
+        Some("https://en.wikipedia.org/wiki/Void_(astronomy)")
+    );
+    assert_eq!(find_url("no link here, not even ftp://x.org"), None);
+    assert_eq!(find_url("https:// alone"), None);
+}
+
+#[test]
+fn test_assign_jump_labels() {
+    let alphabet: Vec<char> = "asdfghjkl".chars().collect();
+    let prefix_free = |labels: &[String]| {
+        labels.iter().enumerate().all(|(i, a)| {
+            labels
+                .iter()
+                .enumerate()
+                .all(|(j, b)| i == j || !b.starts_with(a.as_str()))
+        })
+    };
+
+    let labels = assign_jump_labels(5, &alphabet);
+    assert_eq!(labels, vec!["a", "s", "d", "f", "g"]);
+
+    let labels = assign_jump_labels(60, &alphabet);
+    assert_eq!(labels.len(), 60);
+    assert!(prefix_free(&labels));
+    assert!(labels.iter().all(|l| l.len() <= 2));
+    assert!(labels.iter().any(|l| l.len() == 2));
+    // the first keys stay as short as they can
+    assert_eq!(labels[0], "a");
+
+    let labels = assign_jump_labels(100, &alphabet);
+    assert_eq!(labels.len(), 100);
+    assert!(prefix_free(&labels));
+}
+
+#[test]
+fn test_wrap_text() {
+    assert_eq!(wrap_text("short", 10), vec!["short"]);
+    assert_eq!(wrap_text("any length at all", 0), vec!["any length at all"]);
+    assert_eq!(
+        wrap_text("buy milk and eggs for the week", 10),
+        vec!["buy milk", "and eggs", "for the", "week"]
+    );
+    // runs of whitespace don't leave blank lines behind
+    assert_eq!(wrap_text("a    b  c", 3), vec!["a b", "c"]);
+}
+
+#[test]
+fn test_wrap_text_keeps_newlines() {
+    assert_eq!(
+        wrap_text("notes\nmore notes", 0),
+        vec!["notes", "more notes"]
+    );
+    assert_eq!(
+        wrap_text("notes\nmore notes", 5),
+        vec!["notes", "more", "notes"]
+    );
+    // a trailing newline starts an empty line to type on
+    assert_eq!(wrap_text("notes\n", 0), vec!["notes", ""]);
+}
+
+#[test]
+fn test_wrap_text_breaks_long_words() {
+    assert_eq!(
+        wrap_text("see abcdefghijkl now", 5),
+        vec!["see", "abcde", "fghij", "kl", "now"]
+    );
+    // the tail of a broken word shares its line when there's room
+    assert_eq!(wrap_text("abcdefg hi", 5), vec!["abcde", "fg hi"]);
+    assert_eq!(wrap_text("ünïcödé", 3), vec!["ünï", "cöd", "é"]);
+}
+
+#[test]
+fn test_display_width() {
+    assert_eq!(display_width("日本"), 4);
+    assert_eq!(display_width("abc"), 3);
+    assert_eq!(display_width("ünï"), 3);
+    assert_eq!(take_width("日本語", 5), "日本");
+    assert_eq!(take_width("日本語", 1), "");
+}
+
+#[test]
+fn test_wrap_wide_text() {
+    assert_eq!(wrap_text("日本 語", 4), vec!["日本", "語"]);
+    assert_eq!(wrap_text("日本語です", 4), vec!["日本", "語で", "す"]);
+    // too narrow for even one wide character, so each gets its own line
+    assert_eq!(wrap_text("日本", 1), vec!["日", "本"]);
+}
@@ -1,29 +1,49 @@
-use std::{env, fs::OpenOptions, io::Write, sync::RwLock};
+use std::{
+    cmp,
+    collections::VecDeque,
+    env,
+    fs::OpenOptions,
+    io::Write,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
 
 use log::{self, Level, LevelFilter, Metadata, Record, SetLoggerError};
 
-struct ScreenLogger;
+// how many recent lines the log pane can scroll back through
+const LOG_CAPACITY: usize = 200;
 
-static SCREEN_LOGGER: ScreenLogger = ScreenLogger;
+/// Keeps the records at or above its level in a bounded buffer for the log
+/// pane, and appends every record to `$LOGFILE` when that is set.
+pub struct RingLogger {
+    level: LevelFilter,
+    buffer: Arc<Mutex<LogBuffer>>,
+}
 
-impl log::Log for ScreenLogger {
+impl RingLogger {
+    pub fn new(level: LevelFilter, buffer: Arc<Mutex<LogBuffer>>) -> RingLogger {
+        RingLogger { level, buffer }
+    }
+}
+
+impl log::Log for RingLogger {
     fn enabled(&self, metadata: &Metadata) -> bool {
-        metadata.level() <= Level::Info
+        metadata.level() <= self.level
     }
 
     fn log(&self, record: &Record) {
         if self.enabled(record.metadata()) {
-            let line = format!("{} - {}", record.level(), record.args());
-            let mut logs = LOGS.write().unwrap();
-            logs.insert(0, line);
-            logs.truncate(5);
+            self.buffer.lock().unwrap().push(LogLine {
+                level: record.level(),
+                text: format!("{} - {}", record.level(), record.args()),
+            });
         }
         let line = format!(
             "{} {} {}:{}] {}\n",
             crate::now().as_secs(),
             record.level(),
-            record.file().unwrap().split('/').last().unwrap(),
-            record.line().unwrap(),
+            record.file().unwrap_or("?").split('/').last().unwrap(),
+            record.line().unwrap_or(0),
             record.args()
         );
 
@@ -40,15 +60,141 @@ impl log::Log for ScreenLogger {
     fn flush(&self) {}
 }
 
+// Reads a `RUST_LOG` style filter: comma separated directives that are
+// either a bare level or `target=level`, where only targets naming void
+// apply. Later directives win, anything unreadable is ignored and the
+// default is info.
+fn level_from_filter(filter: &str) -> LevelFilter {
+    let mut level = LevelFilter::Info;
+    for directive in filter.split(',').map(str::trim) {
+        let raw_level = match directive.split_once('=') {
+            Some((target, raw_level)) if target == "voidmap" || target == "void" => raw_level,
+            Some(_) => continue,
+            None => directive,
+        };
+        if let Ok(parsed) = LevelFilter::from_str(raw_level) {
+            level = parsed;
+        }
+    }
+    level
+}
+
+#[derive(Debug, Clone)]
+pub struct LogLine {
+    pub level: Level,
+    pub text: String,
+}
+
+/// The most recent log lines, oldest first, dropping the oldest once full.
+#[derive(Debug, Clone)]
+pub struct LogBuffer {
+    lines: VecDeque<LogLine>,
+    capacity: usize,
+}
+
+impl LogBuffer {
+    pub fn new(capacity: usize) -> LogBuffer {
+        LogBuffer {
+            lines: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, line: LogLine) {
+        if self.lines.len() == self.capacity {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+    }
+
+    /// Lines at least as severe as `min_level`, oldest first.
+    pub fn visible_logs(&self, min_level: Level) -> impl Iterator<Item = &LogLine> {
+        self.lines
+            .iter()
+            .filter(move |line| line.level <= min_level)
+    }
+}
+
+/// Installs a `RingLogger` feeding the log pane, at the level `RUST_LOG`
+/// asks for. The log file always gets debug and above.
 pub fn init_screen_log() -> Result<(), SetLoggerError> {
-    log::set_max_level(LevelFilter::Debug);
-    log::set_logger(&SCREEN_LOGGER)
+    let level = env::var("RUST_LOG")
+        .map(|filter| level_from_filter(&filter))
+        .unwrap_or(LevelFilter::Info);
+    log::set_max_level(cmp::max(level, LevelFilter::Debug));
+    log::set_logger(Box::leak(Box::new(RingLogger::new(level, LOGS.clone()))))
 }
 
-pub fn read_logs() -> Vec<String> {
-    LOGS.read().unwrap().clone()
+pub fn read_logs() -> LogBuffer {
+    LOGS.lock().unwrap().clone()
 }
 
 lazy_static! {
-    static ref LOGS: RwLock<Vec<String>> = RwLock::new(vec![]);
+    static ref LOGS: Arc<Mutex<LogBuffer>> = Arc::new(Mutex::new(LogBuffer::new(LOG_CAPACITY)));
+}
+
+#[test]
+fn test_visible_logs() {
+    let mut logs = LogBuffer::new(3);
+    for (level, text) in [
+        (Level::Info, "saved"),
+        (Level::Warn, "no clipboard"),
+        (Level::Error, "command failed"),
+        (Level::Info, "switched layout"),
+    ] {
+        logs.push(LogLine {
+            level,
+            text: text.to_owned(),
+        });
+    }
+
+    let all: Vec<_> = logs.visible_logs(Level::Info).map(|l| &*l.text).collect();
+    assert_eq!(
+        all,
+        vec!["no clipboard", "command failed", "switched layout"]
+    );
+    let severe: Vec<_> = logs.visible_logs(Level::Warn).map(|l| &*l.text).collect();
+    assert_eq!(severe, vec!["no clipboard", "command failed"]);
+}
+
+#[test]
+fn test_ring_logger() {
+    use log::Log;
+
+    let buffer = Arc::new(Mutex::new(LogBuffer::new(3)));
+    let logger = RingLogger::new(LevelFilter::Info, buffer.clone());
+    for i in 1..=4 {
+        logger.log(
+            &Record::builder()
+                .level(Level::Info)
+                .args(format_args!("info {}", i))
+                .build(),
+        );
+    }
+    logger.log(
+        &Record::builder()
+            .level(Level::Debug)
+            .args(format_args!("filtered"))
+            .build(),
+    );
+
+    let buffer = buffer.lock().unwrap();
+    let texts: Vec<_> = buffer
+        .visible_logs(Level::Trace)
+        .map(|l| &*l.text)
+        .collect();
+    assert_eq!(
+        texts,
+        vec!["INFO - info 2", "INFO - info 3", "INFO - info 4"]
+    );
+}
+
+#[test]
+fn test_level_from_filter() {
+    assert_eq!(level_from_filter("warn"), LevelFilter::Warn);
+    assert_eq!(level_from_filter("DEBUG"), LevelFilter::Debug);
+    assert_eq!(level_from_filter("voidmap=trace"), LevelFilter::Trace);
+    assert_eq!(level_from_filter("hyper=trace,error"), LevelFilter::Error);
+    assert_eq!(level_from_filter("nonsense"), LevelFilter::Info);
+    assert_eq!(level_from_filter("off"), LevelFilter::Off);
 }
@@ -1,18 +1,46 @@
-use crate::{random_fg_color, Coords, Meta, NodeID};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone)]
+use time::{Date, OffsetDateTime};
+
+use crate::{random_fg_color, tagdb, Coords, Meta, NodeID};
+
+pub const MAX_PRIORITY: u8 = 3;
+
+// unfinished nodes due within this many days are highlighted
+const DUE_SOON_DAYS: i64 = 3;
+
+/// How pressing a node's due date is, for highlighting it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Urgency {
+    NotDue,
+    DueSoon,
+    Overdue,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Node {
     pub rooted_coords: Coords,
     pub parent_id: NodeID,
     pub id: NodeID,
     pub content: String,
+    /// The tags in `content`, as parsed by `tagdb::parse_tags`.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub tags: Vec<String>,
     pub children: Vec<NodeID>,
+    #[cfg_attr(feature = "serde", serde(skip))]
     pub selected: bool,
     pub collapsed: bool,
     pub stricken: bool,
+    pub in_progress: bool,
+    /// 0 for none, otherwise 1 up to `MAX_PRIORITY`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub priority: u8,
     pub hide_stricken: bool,
     pub meta: Meta,
     pub free_text: Option<String>,
+    #[cfg_attr(feature = "serde", serde(skip, default = "random_fg_color"))]
     pub color: String,
     pub auto_arrange: bool,
 }
@@ -24,10 +52,13 @@ impl Default for Node {
             id: 0,
             parent_id: 0,
             content: String::new(),
+            tags: vec![],
             children: vec![],
             selected: false,
             collapsed: false,
             stricken: false,
+            in_progress: false,
+            priority: 0,
             hide_stricken: false,
             meta: Meta::default(),
             free_text: None,
@@ -47,7 +78,121 @@ impl Node {
             self.meta.finish();
         }
         self.stricken = !self.stricken;
+        self.in_progress = false;
+    }
+
+    // incomplete -> in progress -> completed -> incomplete
+    pub fn toggle_in_progress(&mut self) {
+        if self.in_progress || self.stricken {
+            self.toggle_stricken();
+        } else {
+            self.in_progress = true;
+        }
+    }
+
+    // none -> 1 -> ... -> MAX_PRIORITY -> none
+    pub fn raise_priority(&mut self) {
+        self.priority = (self.priority + 1) % (MAX_PRIORITY + 1);
+    }
+
+    pub fn lower_priority(&mut self) {
+        self.priority = self.priority.checked_sub(1).unwrap_or(MAX_PRIORITY);
     }
 
     pub fn toggle_hide_stricken(&mut self) { self.hide_stricken = !self.hide_stricken; }
+
+    pub fn due_date(&self) -> Option<Date> {
+        self.meta
+            .due
+            .map(|secs| OffsetDateTime::from_unix_timestamp(secs as i64).date())
+    }
+
+    /// Whether the node is unfinished and was due before `today`.
+    pub fn is_overdue(&self, today: Date) -> bool {
+        self.urgency(today) == Urgency::Overdue
+    }
+
+    pub fn urgency(&self, today: Date) -> Urgency {
+        match self.due_date() {
+            Some(due) if !self.stricken => {
+                if due < today {
+                    Urgency::Overdue
+                } else if (due - today).whole_days() <= DUE_SOON_DAYS {
+                    Urgency::DueSoon
+                } else {
+                    Urgency::NotDue
+                }
+            }
+            _ => Urgency::NotDue,
+        }
+    }
+
+    // called wherever the text changes, so tags never go stale
+    pub fn parse_tags(&mut self) {
+        self.tags = tagdb::parse_tags(&self.content);
+    }
+}
+
+#[test]
+fn test_in_progress_cycle() {
+    let mut node = Node::default();
+    node.toggle_in_progress();
+    assert!(node.in_progress && !node.stricken);
+    node.toggle_in_progress();
+    assert!(!node.in_progress && node.stricken);
+    assert!(node.meta.finish_time.is_some());
+    node.toggle_in_progress();
+    assert!(!node.in_progress && !node.stricken);
+    assert!(node.meta.finish_time.is_none());
+
+    // completing an in progress task directly also clears the flag
+    node.toggle_in_progress();
+    node.toggle_stricken();
+    assert!(!node.in_progress && node.stricken);
+}
+
+#[test]
+fn test_parse_tags() {
+    let mut node = Node {
+        content: "ship it #work #urgent".to_owned(),
+        ..Node::default()
+    };
+    node.parse_tags();
+    assert_eq!(node.tags, vec!["work", "urgent"]);
+    node.content = "no tags, just a # sign".to_owned();
+    node.parse_tags();
+    assert!(node.tags.is_empty());
+}
+
+#[test]
+fn test_urgency() {
+    let day = |d| Date::try_from_ymd(2024, 6, d).unwrap();
+    let mut node = Node::default();
+    assert_eq!(node.urgency(day(1)), Urgency::NotDue);
+
+    // due on the 10th
+    node.meta.due = Some(1_717_977_600);
+    assert_eq!(node.due_date(), Some(day(10)));
+    assert_eq!(node.urgency(day(1)), Urgency::NotDue);
+    assert_eq!(node.urgency(day(7)), Urgency::DueSoon);
+    assert_eq!(node.urgency(day(10)), Urgency::DueSoon);
+    assert!(!node.is_overdue(day(10)));
+    assert!(node.is_overdue(day(11)));
+
+    // finished tasks aren't overdue
+    node.toggle_stricken();
+    assert!(!node.is_overdue(day(11)));
+}
+
+#[test]
+fn test_priority_cycle() {
+    let mut node = Node::default();
+    for expected in &[1, 2, 3, 0] {
+        node.raise_priority();
+        assert_eq!(node.priority, *expected);
+    }
+    node.lower_priority();
+    assert_eq!(node.priority, MAX_PRIORITY);
+    node.lower_priority();
+    assert_eq!(node.priority, 2);
 }
@@ -1,6 +1,6 @@
-use std::cmp;
+use std::{cmp, collections::HashMap};
 
-use crate::Coords;
+use crate::{Coords, NodeID};
 
 #[derive(Debug)]
 pub struct Pack {
@@ -87,3 +87,46 @@ impl Pack {
         capacity.0 >= dim.0 && capacity.1 >= dim.1
     }
 }
+
+/// Places top level trees, given as the id and drawn (width, height) of each,
+/// left to right and then downwards below the header, up to column `right`.
+/// Taller trees go first and ties go in id order, so the same trees always
+/// get the same coordinates whatever order they are passed in.
+pub fn arrange(trees: &[(NodeID, Coords)], right: u16) -> HashMap<NodeID, Coords> {
+    let mut real_estate = Pack {
+        children: None,
+        top: 2,                // leave room for header
+        left: 1,               // 1-indexed screen
+        bottom: u16::MAX, // make this "bottomless" since we can paginate
+        right,
+        elem: None,
+    };
+
+    let mut trees = trees.to_vec();
+    trees.sort_by_key(|&(id, (_, height))| (cmp::Reverse(height), id));
+
+    let mut placed = HashMap::new();
+    for (node_id, dims) in trees {
+        // add some spacing around this tree to space out
+        // placement a little bit
+        let padded_dims = (dims.0 + 2, dims.1 + 2);
+        if let Some(coords) = real_estate.insert(padded_dims) {
+            placed.insert(node_id, coords);
+        }
+    }
+    placed
+}
+
+#[test]
+fn test_arrange_is_deterministic() {
+    let trees = [(3, (10, 2)), (1, (30, 4)), (2, (10, 2)), (4, (60, 1))];
+    let placed = arrange(&trees, 79);
+    let expected: HashMap<_, _> = vec![(1, (1, 2)), (2, (33, 2)), (3, (45, 2)), (4, (1, 8))]
+        .into_iter()
+        .collect();
+    assert_eq!(placed, expected);
+
+    let mut reversed = trees.to_vec();
+    reversed.reverse();
+    assert_eq!(arrange(&reversed, 79), expected);
+}
@@ -6,7 +6,11 @@ use std::{
     fmt::Write as FmtWrite,
     fs::{remove_file, rename, File, OpenOptions},
     io::{self, stdin, stdout, Error, ErrorKind, Read, Seek, SeekFrom, Stdout, Write},
+    path::Path,
     process,
+    sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender},
+    thread,
+    time::Duration,
 };
 
 use termion::{
@@ -18,13 +22,26 @@ use termion::{
     style, terminal_size,
 };
 
+use log::Level;
 use rand::{self, Rng};
 use regex::Regex;
 use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthChar;
 
 use crate::{
-    cost, dateparse, distances, logging, now, plot, random_fg_color, re_matches, serialization,
-    Action, Config, Coords, Dir, Node, NodeID, Pack, TagDB,
+    assign_jump_labels,
+    clipboard::{system_clipboard, Clipboard},
+    color_enabled,
+    config::chord_to_string,
+    cost, dateparse,
+    dateparse::parse_due_date,
+    display_width, distances, expand_env, find_url, fuzzy_score,
+    history::{History, Op},
+    layout::{self, LayoutKind},
+    logging,
+    node::Urgency,
+    now, pack, plot, random_fg_color, re_matches, serialization, take_width, today, wrap_text,
+    Action, ChordMatch, Color, Config, Coords, Dir, Mode, Node, NodeID, TagDB,
 };
 
 pub struct Screen {
@@ -38,12 +55,37 @@ pub struct Screen {
     // screen dimensions as detected during the current draw() cycle
     pub dims: Coords,
     pub is_test: bool,
+    // set by --readonly, turning away anything that changes the tree
+    pub readonly: bool,
+    // off for NO_COLOR or output that isn't a terminal, checked by run()
+    color_enabled: bool,
+
+    // the drilled down root and scroll offsets, saved so reopening lands
+    // where we left off
+    pub drawing_root: NodeID,
+    pub view_y: u16,
+    pub view_x: u16,
 
     // non-pub members are ephemeral
-    drawing_root: NodeID,
+    layout: LayoutKind,
     show_logs: bool,
+    show_minimap: bool,
+    // how far the log pane is scrolled back, and the least severe level it
+    // shows
+    log_scroll: usize,
+    log_level: Level,
     selected: Option<NodeID>,
     cut: Option<NodeID>,
+    // subtree copied by yank_node or cut_node, pasted by paste_as_child
+    yanked: Option<Subtree>,
+    // grapheme index typing goes to in the node it names, which must be
+    // selected, otherwise typing goes to the end
+    cursor: Option<(NodeID, usize)>,
+    // node being renamed and its text from before, put back if it's left
+    // without typing a new name
+    renaming: Option<(NodeID, String)>,
+    // connected on first use
+    clipboard: Option<Box<dyn Clipboard>>,
     drawing_arrow: Option<NodeID>,
     lookup: HashMap<Coords, NodeID>,
     drawn_at: HashMap<NodeID, Coords>,
@@ -51,16 +93,40 @@ pub struct Screen {
     dragging_to: Option<Coords>,
     stdout: Option<MouseTerminal<RawTerminal<AlternateScreen<Stdout>>>>,
     lowest_drawn: u16,
-    // where we start drawing from
-    view_y: u16,
     // when we drill down then pop up, we should go to last focus, stored here
     focus_stack: Vec<(NodeID, NodeID, u16)>,
-    last_search: Option<(String, NodeID)>,
+    // query being typed or last submitted, with its highlighted matches
+    search: Option<Search>,
+    last_fuzzy_find: Option<(String, NodeID)>,
+    // command line being typed while in command mode
+    command: Option<String>,
+    help: Option<Help>,
+    // keys typed so far towards a chord, and when the first was typed
+    pending_chord: Vec<Key>,
+    chord_started_ms: u64,
+    // digits typed in normal mode, repeating the next action that often
+    count: Option<usize>,
+    // command names tab is cycling through, and the one filled in
+    completions: Option<(Vec<String>, usize)>,
+    // feedback for the status line, cleared by the next event
+    message: Option<String>,
+    // set by the quit commands, with whether unsaved changes are dropped
+    quitting: Option<bool>,
+    // commands started by exec_selected that are still running
+    execs: HashMap<NodeID, Exec>,
+    // only nodes with this tag and their ancestors are drawn
+    tag_filter: Option<String>,
+    // the nodes tag_filter leaves drawn, as of the last refresh_tag_filter
+    tag_visible: HashSet<NodeID>,
+    // nodes named by `:mark`, dropped once found to be deleted
+    marks: HashMap<char, NodeID>,
+    // collapsed flags from before focus_branch, restored when toggled off
+    unfocused: Option<Vec<(NodeID, bool)>>,
 
     // undo info
-    undo_stack: Vec<NodeID>,
-    // needs to be separate, as recursive deletion of nodes causes ordering issues
-    undo_nodes: HashMap<NodeID, Node>,
+    history: History,
+    // deleted subtrees, most recent last
+    undo_stack: Vec<Deletion>,
 
     // nodes created specifically for rendering an augmented view
     ephemeral_nodes: HashMap<NodeID, Node>,
@@ -75,8 +141,13 @@ pub struct Screen {
     // timer for double clicks
     last_click_ms: u64,
 
-    // grapheme calculation is expensive
-    grapheme_cache: HashMap<NodeID, usize>,
+    // set by any change to the tree and cleared by save
+    dirty: bool,
+    // when the tree was last saved, for autosave_secs
+    last_save_secs: u64,
+
+    // display width of each node's first line, which is expensive to find
+    width_cache: HashMap<NodeID, usize>,
 }
 
 impl Default for Screen {
@@ -89,12 +160,21 @@ impl Default for Screen {
             arrows: vec![],
             selected: None,
             cut: None,
+            yanked: None,
+            cursor: None,
+            renaming: None,
+            clipboard: None,
             drawing_arrow: None,
             nodes: HashMap::new(),
             lookup: HashMap::new(),
             drawn_at: HashMap::new(),
             show_logs: false,
+            show_minimap: false,
+            help: None,
+            log_scroll: 0,
+            log_level: Level::Trace,
             drawing_root: 0,
+            layout: LayoutKind::default(),
             stdout: None,
             dragging_from: None,
             dragging_to: None,
@@ -103,16 +183,34 @@ impl Default for Screen {
             dims: (1, 1),
             lowest_drawn: 0,
             view_y: 0,
+            view_x: 0,
             focus_stack: vec![],
             is_test: false,
-            last_search: None,
+            readonly: false,
+            color_enabled: true,
+            search: None,
+            last_fuzzy_find: None,
+            command: None,
+            completions: None,
+            pending_chord: vec![],
+            chord_started_ms: 0,
+            count: None,
+            message: None,
+            quitting: None,
+            execs: HashMap::new(),
+            tag_filter: None,
+            tag_visible: HashSet::new(),
+            marks: HashMap::new(),
+            unfocused: None,
+            history: History::default(),
             undo_stack: vec![],
-            undo_nodes: HashMap::new(),
             ephemeral_nodes: HashMap::new(),
             ephemeral_max_id: std::u64::MAX,
             tag_db: TagDB::default(),
             last_click_ms: 0,
-            grapheme_cache: HashMap::new(),
+            dirty: false,
+            last_save_secs: 0,
+            width_cache: HashMap::new(),
         };
         screen.nodes.insert(0, root);
         screen
@@ -121,12 +219,65 @@ impl Default for Screen {
 
 impl Screen {
     fn help(&mut self) {
-        self.cleanup();
-        println!("{}{}{}", cursor::Goto(1, 1), clear::All, self.config);
-        self.start_raw_mode();
-        if self.single_key_prompt("").is_err() {
-            // likely here because of testing
+        self.help = Some(Help::default());
+    }
+
+    /// The bindings as they stand, rebindings and all, grouped by mode.
+    fn help_text(&self) -> String {
+        self.config.to_string()
+    }
+
+    /// The lines of `help_text` binding an action whose name or key
+    /// contains `query`, each group under its mode's heading. An empty query
+    /// keeps every line.
+    fn filter_help(&self, query: &str) -> Vec<String> {
+        let text = self.help_text();
+        if query.is_empty() {
+            return text.lines().map(str::to_owned).collect();
+        }
+        let mut lines = vec![];
+        let mut heading = None;
+        for line in text.lines() {
+            if !line.starts_with("    ") {
+                heading = Some(line);
+            } else if line.contains(query) {
+                if let Some(heading) = heading.take() {
+                    lines.push(heading.to_owned());
+                }
+                lines.push(line.to_owned());
+            }
+        }
+        lines
+    }
+
+    // the help overlay takes every key until closed, typing filters it and
+    // Esc clears the filter before closing
+    fn help_key(&mut self, key: Key) {
+        let page = self.dims.1.saturating_sub(2) as usize;
+        let mut help = match self.help.take() {
+            Some(help) => help,
+            None => return,
+        };
+        match key {
+            Key::Up => help.scroll = help.scroll.saturating_sub(1),
+            Key::Down => help.scroll += 1,
+            Key::PageUp => help.scroll = help.scroll.saturating_sub(page),
+            Key::PageDown => help.scroll += page,
+            Key::Esc if help.query.is_empty() => return,
+            Key::Esc => help.query.clear(),
+            Key::Backspace => {
+                help.query.pop();
+            }
+            Key::Char(c) if c != '\n' => {
+                help.query.push(c);
+                help.scroll = 0;
+            }
+            _ => {}
         }
+        // scrolling stops at the last page, so Up moves straight back
+        let last_page = self.filter_help(&help.query).len().saturating_sub(page);
+        help.scroll = min(help.scroll, last_page);
+        self.help = Some(help);
     }
 
     fn new_node_id(&mut self) -> NodeID {
@@ -139,6 +290,7 @@ impl Screen {
         let mut node = Node::default();
         let id = self.new_node_id();
         node.id = id;
+        self.touch(id);
         self.nodes.insert(id, node);
         id
     }
@@ -154,6 +306,7 @@ impl Screen {
     where
         F: FnMut(&mut Node) -> B,
     {
+        self.touch(k);
         self.nodes.get_mut(&k).map(|mut node| {
             node.meta.bump_mtime();
             f(&mut node)
@@ -164,12 +317,172 @@ impl Screen {
     where
         F: FnMut(&mut Node) -> B,
     {
+        self.touch(k);
         self.nodes.get_mut(&k).map(|mut node| f(&mut node))
     }
 
+    // keys are mapped in insert mode while a node is selected for editing
+    pub fn mode(&self) -> Mode {
+        if self.command.is_some() || self.typing_search() {
+            Mode::Command
+        } else if self.selected.is_some() {
+            Mode::Insert
+        } else {
+            Mode::Normal
+        }
+    }
+
     // return of false signals to the caller that we are done in this view
     pub fn handle_event(&mut self, evt: Event) -> bool {
-        match self.config.map(evt) {
+        self.message = None;
+        self.handle_event_at(evt, now().as_millis() as u64)
+    }
+
+    // Keys starting a chord are held back until it completes, or until a
+    // key that doesn't continue it, when they are handled one at a time as
+    // if no chord was bound. A chord left dangling times out by itself in
+    // the event loop, or here when the next key arrives too late.
+    fn handle_event_at(&mut self, evt: Event, now_ms: u64) -> bool {
+        if self.help.is_some() {
+            if let Event::Key(key) = evt {
+                self.help_key(key);
+            }
+            return true;
+        }
+        let key = match evt {
+            Event::Key(key) => key,
+            other => return self.flush_chord() && self.dispatch(other),
+        };
+        if self.chord_timed_out(now_ms) && !self.flush_chord() {
+            return false;
+        }
+        self.pending_chord.push(key);
+        match self.config.match_chord(self.mode(), &self.pending_chord) {
+            ChordMatch::Complete(action) => {
+                self.pending_chord.clear();
+                self.run_action(Some(action))
+            }
+            ChordMatch::Prefix => {
+                if self.pending_chord.len() == 1 {
+                    self.chord_started_ms = now_ms;
+                }
+                true
+            }
+            ChordMatch::Unmatched if key == Key::Esc && self.pending_chord.len() > 1 => {
+                // dismisses the chord and its popup without running anything
+                self.pending_chord.clear();
+                true
+            }
+            ChordMatch::Unmatched if self.pending_chord.len() > 1 => {
+                // the key may start a chord of its own
+                self.pending_chord.pop();
+                self.flush_chord() && self.handle_event_at(evt, now_ms)
+            }
+            ChordMatch::Unmatched => {
+                self.pending_chord.clear();
+                self.dispatch(evt)
+            }
+        }
+    }
+
+    // Whether the action changes the tree rather than only looking around
+    // it. Typing only changes it while a node is selected, and clicks and
+    // drags are checked where they create or move nodes.
+    fn changes_tree(&self, action: Action) -> bool {
+        match action {
+            Action::Char(_) | Action::EraseChar | Action::EraseWord | Action::InsertNewline => {
+                self.selected.is_some() && self.command.is_none() && !self.typing_search()
+            }
+            Action::DeleteSelected
+            | Action::RenameNode
+            | Action::CreateSibling
+            | Action::CreateChild
+            | Action::CreateFreeNode
+            | Action::ExecSelected
+            | Action::ToggleCompleted
+            | Action::ToggleInProgress
+            | Action::ToggleHideCompleted
+            | Action::Arrow
+            | Action::StartArrow
+            | Action::EndArrow
+            | Action::RemoveArrow
+            | Action::AutoArrange
+            | Action::ToggleCollapsed
+            | Action::CollapseAll
+            | Action::ExpandAll
+            | Action::YankPasteNode
+            | Action::CutNode
+            | Action::PasteAsChild
+            | Action::PasteTextFromClipboard
+            | Action::RaiseSelected
+            | Action::LowerSelected
+            | Action::Demote
+            | Action::Promote
+            | Action::DuplicateNode
+            | Action::FocusBranch
+            | Action::RaisePriority
+            | Action::LowerPriority
+            | Action::UndoDelete
+            | Action::Undo
+            | Action::Redo => true,
+            _ => false,
+        }
+    }
+
+    fn chord_timed_out(&self, now_ms: u64) -> bool {
+        !self.pending_chord.is_empty()
+            && now_ms.saturating_sub(self.chord_started_ms) > CHORD_TIMEOUT_MS
+    }
+
+    fn flush_chord(&mut self) -> bool {
+        let keys = std::mem::take(&mut self.pending_chord);
+        keys.into_iter().all(|key| self.dispatch(Event::Key(key)))
+    }
+
+    fn dispatch(&mut self, evt: Event) -> bool {
+        let action = self.config.map(evt, self.mode());
+        self.run_action(action)
+    }
+
+    fn run_action(&mut self, action: Option<Action>) -> bool {
+        if self.mode() == Mode::Normal {
+            match action {
+                // a leading 0 still jumps to nodes starting with 0
+                Some(Action::Char(c @ '0'..='9')) if c != '0' || self.count.is_some() => {
+                    let digit = c.to_digit(10).unwrap() as usize;
+                    let count = self.count.unwrap_or(0) * 10 + digit;
+                    self.count = Some(min(count, MAX_COUNT));
+                    return true;
+                }
+                Some(Action::UnselectRet) if self.count.is_some() => {
+                    self.count = None;
+                    return true;
+                }
+                _ => {}
+            }
+        }
+        let count = self.count.take().unwrap_or(1);
+        let editing = self.selected.is_some()
+            && matches!(
+                action,
+                Some(Action::Char(_))
+                    | Some(Action::EraseChar)
+                    | Some(Action::EraseWord)
+                    | Some(Action::InsertNewline)
+            );
+        // repeats stop early if one asks to quit
+        self.recorded(editing, |screen| {
+            (0..count).all(|_| screen.handle_action(action))
+        })
+    }
+
+    fn handle_action(&mut self, action: Option<Action>) -> bool {
+        if let Some(action) = action.filter(|&a| self.readonly && self.changes_tree(a)) {
+            let name = action.name().unwrap_or("editing");
+            self.message = Some(format!("read only, {} is off", name));
+            return true;
+        }
+        match action {
             Some(e) => match e {
                 Action::LeftClick(x, y) => {
                     let internal_coords = self.screen_to_internal_xy((x, y));
@@ -178,19 +491,40 @@ impl Screen {
                 Action::RightClick(..) => {
                     self.pop_focus();
                 }
+                Action::MiddleClick(x, y) => {
+                    trace!("middle click at {:?}", (x, y));
+                }
                 Action::Release(x, y) => {
                     let internal_coords = self.screen_to_internal_xy((x, y));
                     self.release(internal_coords)
                 }
+                Action::Drag(x, y) => {
+                    let internal_coords = self.screen_to_internal_xy((x, y));
+                    self.drag(internal_coords)
+                }
+                // Edit the command line
+                Action::Char(c) if self.command.is_some() => self.append_cmd(c),
+                Action::EraseChar if self.command.is_some() => self.backspace_cmd(),
+                Action::UnselectRet if self.command.is_some() => self.command = None,
+                // Edit the search query
+                Action::Char(c) if self.typing_search() => self.append_search(c),
+                Action::EraseChar if self.typing_search() => self.backspace_search(),
+                Action::UnselectRet if self.typing_search() => self.search = None,
+                Action::SubmitCmd if self.typing_search() => self.submit_search(),
+                Action::ToggleSearchMode if self.typing_search() => self.toggle_search_mode(),
+                // Clear highlighted matches before quitting
+                Action::UnselectRet if self.search.is_some() && self.selected.is_none() => {
+                    self.search = None
+                }
                 // Write character to selection
                 Action::Char(c) if self.selected.is_some() => {
                     self.append(c);
                 }
                 Action::Char('/') => {
-                    self.search_forward();
+                    self.start_search(SearchDirection::Forward);
                 }
                 Action::Char('?') => {
-                    self.search_backward();
+                    self.start_search(SearchDirection::Backward);
                 }
                 Action::Char(c) => {
                     self.prefix_jump_to(c.to_string());
@@ -198,6 +532,10 @@ impl Screen {
                 Action::Help => self.help(),
                 Action::UnselectRet => return self.unselect().is_some(),
                 Action::ScrollUp => self.scroll_up(),
+                Action::ScrollLeft => self.scroll_left(),
+                Action::ScrollRight => self.scroll_right(),
+                Action::ToggleMinimap => self.toggle_minimap(),
+                Action::CenterSelection => self.center_selection(),
                 Action::ScrollDown => self.scroll_down(),
                 Action::DeleteSelected => self.delete_selected(true),
                 Action::SelectUp => self.select_up(),
@@ -205,37 +543,199 @@ impl Screen {
                 Action::SelectLeft => self.select_left(),
                 Action::SelectRight => self.select_right(),
                 Action::EraseChar => self.backspace(),
+                Action::EraseWord => self.erase_word(),
+                Action::CursorLeft => self.move_cursor(|pos, _| pos.saturating_sub(1)),
+                Action::CursorRight => self.move_cursor(|pos, _| pos + 1),
+                Action::CursorHome => self.move_cursor(|_, _| 0),
+                Action::CursorEnd => self.move_cursor(|_, len| len),
+                Action::InsertNewline => self.append('\n'),
+                Action::RenameNode => self.rename_node(),
                 Action::CreateSibling => self.create_sibling(),
                 Action::CreateChild => self.create_child(),
                 Action::CreateFreeNode => self.create_free_node(),
                 Action::ExecSelected => self.exec_selected(),
+                Action::OpenUrl => self.open_url(),
+                Action::EditInEditor => self.edit_in_editor(),
+                Action::CancelExec => self.cancel_exec(),
                 Action::DrillDown => self.drill_down(),
                 Action::PopUp => self.pop_focus(),
                 Action::PrefixJump => self.prefix_jump_prompt(),
                 Action::ToggleCompleted => self.toggle_stricken(),
+                Action::ToggleInProgress => self.toggle_in_progress(),
                 Action::ToggleHideCompleted => self.toggle_hide_stricken(),
                 Action::Arrow => self.add_or_remove_arrow(),
+                Action::StartArrow => self.start_arrow(),
+                Action::EndArrow => self.end_arrow(),
+                Action::RemoveArrow => self.remove_arrow(),
                 Action::AutoArrange => self.toggle_auto_arrange(),
+                Action::ToggleLayout => self.toggle_layout(),
                 Action::ToggleCollapsed => self.toggle_collapsed(),
+                Action::CollapseAll => self.set_all_collapsed(true),
+                Action::ExpandAll => self.set_all_collapsed(false),
+                Action::FocusBranch => self.focus_branch(),
+                Action::FilterByTag => {
+                    self.enter_cmd();
+                    self.command = Some("filter #".to_owned());
+                }
+                Action::SetMark => {
+                    self.enter_cmd();
+                    self.command = Some("mark ".to_owned());
+                }
+                Action::JumpMark => {
+                    self.enter_cmd();
+                    self.command = Some("'".to_owned());
+                }
                 Action::Quit => return false,
                 Action::Save => self.save(),
                 Action::ToggleShowLogs => self.toggle_show_logs(),
+                Action::ScrollLogsUp => self.scroll_logs(1),
+                Action::ScrollLogsDown => self.scroll_logs(-1),
+                Action::ToggleLogLevel => self.toggle_log_level(),
                 Action::EnterCmd => self.enter_cmd(),
+                Action::CompleteCmd => self.complete_cmd(),
+                Action::SubmitCmd => {
+                    self.submit_cmd();
+                    if self.quitting.is_some() {
+                        return false;
+                    }
+                }
                 Action::FindTask => self.auto_task(),
+                Action::FuzzyFind => self.fuzzy_find(),
                 Action::YankPasteNode => self.cut_paste(),
+                Action::YankNode => self.yank_node(),
+                Action::CutNode => self.cut_node(),
+                Action::PasteAsChild => self.paste_as_child(),
+                Action::CopyTextToClipboard => self.copy_clipboard(),
+                Action::PasteTextFromClipboard => self.paste_clipboard(),
                 Action::RaiseSelected => self.raise_selected(),
                 Action::LowerSelected => self.lower_selected(),
-                Action::Search => self.search_forward(),
+                Action::Demote => self.demote_selected(),
+                Action::Promote => self.promote_selected(),
+                Action::DuplicateNode => self.duplicate_selected(),
+                Action::RaisePriority => self.change_priority(Node::raise_priority),
+                Action::LowerPriority => self.change_priority(Node::lower_priority),
+                Action::Search => self.start_search(SearchDirection::Forward),
+                Action::SearchNext => self.search_next(),
+                Action::SearchPrev => self.search_prev(),
+                // there is no query being typed to match differently
+                Action::ToggleSearchMode => {}
                 Action::UndoDelete => self.undo_delete(),
+                Action::Undo => self.undo(),
+                Action::Redo => self.redo(),
                 Action::SelectParent => self.select_parent(),
                 Action::SelectNextSibling => self.select_next_sibling(),
                 Action::SelectPrevSibling => self.select_prev_sibling(),
+                Action::SelectFirstChild => self.select_first_child(),
+                Action::SelectLastChild => self.select_last_child(),
+                Action::SelectRoot => self.select_root(),
             },
             None => warn!("received unknown input"),
         }
         true
     }
 
+    // Runs f as a single undo step, recording the prior state of every node
+    // it changes. Consecutive text edits of one node fold into one step.
+    fn recorded<B, F>(&mut self, editing: bool, f: F) -> B
+    where
+        F: FnOnce(&mut Screen) -> B,
+    {
+        self.history.begin();
+        let ret = f(self);
+        let mut step = self.history.end();
+        step.retain(|op| match *op {
+            Op::Restore(node_id, ref before) => {
+                !same_ignoring_selection(before.as_deref(), self.nodes.get(&node_id))
+            }
+            Op::Arrows(ref before) => before != &self.arrows,
+        });
+        let edited = match step.as_slice() {
+            [Op::Restore(node_id, Some(_))] if editing => Some(*node_id),
+            _ => None,
+        };
+        if !step.is_empty() {
+            self.dirty = true;
+        }
+        let depth = self.config.settings.undo_depth;
+        self.history.push(step, edited, depth);
+        ret
+    }
+
+    fn touch(&mut self, node_id: NodeID) {
+        if self.history.wants_node(node_id) {
+            let before = self.nodes.get(&node_id).cloned().map(Box::new);
+            self.history.record(Op::Restore(node_id, before));
+        }
+    }
+
+    fn touch_arrows(&mut self) {
+        if self.history.wants_arrows() {
+            self.history.record(Op::Arrows(self.arrows.clone()));
+        }
+    }
+
+    fn undo(&mut self) {
+        trace!("undo()");
+        if let Some(step) = self.history.pop_undo() {
+            let inverse = self.apply_step(step);
+            self.history.push_redo(inverse);
+        }
+    }
+
+    fn redo(&mut self) {
+        trace!("redo()");
+        if let Some(step) = self.history.pop_redo() {
+            let inverse = self.apply_step(step);
+            self.history.push_undo(inverse);
+        }
+    }
+
+    // Applies a recorded step, returning the step that reverses it. The
+    // selection is dropped and moved to the first restored node.
+    fn apply_step(&mut self, step: Vec<Op>) -> Vec<Op> {
+        if let Some(selected_id) = self.selected.take() {
+            if let Some(node) = self.nodes.get_mut(&selected_id) {
+                node.selected = false;
+            }
+        }
+        let mut reselect = None;
+        let mut inverse = vec![];
+        for op in step.into_iter().rev() {
+            match op {
+                Op::Restore(node_id, before) => {
+                    let after = match before {
+                        Some(mut node) => {
+                            node.selected = false;
+                            self.tag_db.set_tags(node_id, &node.tags);
+                            if reselect.is_none() && node_id != self.drawing_root {
+                                reselect = Some(node_id);
+                            }
+                            self.nodes.insert(node_id, *node)
+                        }
+                        None => {
+                            self.tag_db.remove(node_id);
+                            self.nodes.remove(&node_id)
+                        }
+                    };
+                    self.width_cache.remove(&node_id);
+                    inverse.push(Op::Restore(node_id, after.map(Box::new)));
+                }
+                Op::Arrows(before) => {
+                    let after = std::mem::replace(&mut self.arrows, before);
+                    inverse.push(Op::Arrows(after));
+                }
+            }
+        }
+        if !self.nodes.contains_key(&self.drawing_root) {
+            self.drawing_root = 0;
+        }
+        if let Some(node_id) = reselect {
+            self.select_node(node_id);
+        }
+        self.dirty = true;
+        inverse
+    }
+
     fn exists(&self, node_id: NodeID) -> bool {
         self.nodes.get(&node_id).is_some()
     }
@@ -253,6 +753,122 @@ impl Screen {
         }
     }
 
+    fn yank_node(&mut self) {
+        if let Some(selected_id) = self.selected {
+            let mut f = |n: &Node| Some((n.id, n.clone()));
+            let nodes = self.recursive_child_filter_map(selected_id, &mut f);
+            self.yanked = Some(Subtree {
+                node_id: selected_id,
+                nodes: nodes.into_iter().collect(),
+            });
+        }
+    }
+
+    // Deletes the selected subtree into the yank buffer instead of the
+    // undo_delete stack.
+    fn cut_node(&mut self) {
+        if let Some(selected_id) = self.selected {
+            if self.parent(selected_id).is_none() {
+                return;
+            }
+            self.delete_selected(true);
+            if let Some(deletion) = self.undo_stack.pop() {
+                self.yanked = Some(Subtree {
+                    node_id: deletion.node_id,
+                    nodes: deletion.nodes,
+                });
+            }
+        }
+    }
+
+    // Copies the yanked subtree in as the last child of the selection. The
+    // yank stays put, so it can be pasted in several places.
+    fn paste_as_child(&mut self) {
+        if let Some(selected_id) = self.selected {
+            if let Some(yanked) = self.yanked.take() {
+                if let Some(copy_id) = self.paste_recursive(&yanked, yanked.node_id, selected_id) {
+                    self.with_node_mut_no_meta(selected_id, |s| s.children.push(copy_id));
+                }
+                self.yanked = Some(yanked);
+            }
+        }
+    }
+
+    fn copy_clipboard(&mut self) {
+        if let Some(selected_id) = self.selected {
+            if let Some(content) = self.with_node(selected_id, |n| n.content.clone()) {
+                self.with_clipboard(|clipboard| clipboard.set_text(content));
+            }
+        }
+    }
+
+    // The clipboard text replaces the selected node's text, or goes into a
+    // new node if nothing is selected. Lines are joined with spaces.
+    fn paste_clipboard(&mut self) {
+        let text = match self.with_clipboard(|clipboard| clipboard.get_text()) {
+            Some(text) => text.split_whitespace().collect::<Vec<_>>().join(" "),
+            None => return,
+        };
+        if text.is_empty() {
+            return;
+        }
+        if self.selected.is_none() {
+            self.create_free_node();
+        }
+        if let Some(selected_id) = self.selected {
+            self.with_node_mut(selected_id, |node| node.content = text.clone());
+            self.width_cache.remove(&selected_id);
+            self.reindex_tags(selected_id);
+        }
+    }
+
+    // Logs a warning instead when there is no clipboard to use, such as on
+    // headless systems. Tests never touch the system clipboard.
+    fn with_clipboard<B, F>(&mut self, f: F) -> Option<B>
+    where
+        F: FnOnce(&mut dyn Clipboard) -> Result<B, String>,
+    {
+        if self.clipboard.is_none() && !self.is_test {
+            match system_clipboard() {
+                Ok(clipboard) => self.clipboard = Some(clipboard),
+                Err(e) => warn!("no clipboard available: {}", e),
+            }
+        }
+        let clipboard = self.clipboard.as_mut()?;
+        match f(clipboard.as_mut()) {
+            Ok(b) => Some(b),
+            Err(e) => {
+                warn!("clipboard failed: {}", e);
+                None
+            }
+        }
+    }
+
+    // Like duplicate_recursive, but copies out of a yanked subtree.
+    fn paste_recursive(
+        &mut self,
+        yanked: &Subtree,
+        node_id: NodeID,
+        parent_id: NodeID,
+    ) -> Option<NodeID> {
+        let mut copy = yanked.nodes.get(&node_id)?.clone();
+        let copy_id = self.new_node_id();
+        copy.id = copy_id;
+        copy.parent_id = parent_id;
+        copy.selected = false;
+        copy.children = vec![];
+        self.tag_db.set_tags(copy_id, &copy.tags);
+        self.touch(copy_id);
+        self.nodes.insert(copy_id, copy);
+
+        for &child_id in &yanked.nodes[&node_id].children {
+            if let Some(child_copy) = self.paste_recursive(yanked, child_id, copy_id) {
+                self.with_node_mut_no_meta(copy_id, |copy| copy.children.push(child_copy));
+            }
+        }
+        Some(copy_id)
+    }
+
     fn reparent(&mut self, node_id: NodeID, parent_id: NodeID) {
         if !self.exists(node_id) || !self.exists(parent_id) {
             warn!("tried to reparent to deleted node");
@@ -271,7 +887,7 @@ impl Screen {
                 .unwrap();
             self.with_node_mut_no_meta(node_id, |s| s.parent_id = parent_id)
                 .unwrap();
-            self.grapheme_cache.remove(&node_id);
+            self.width_cache.remove(&node_id);
         }
     }
 
@@ -411,138 +1027,539 @@ impl Screen {
 
     fn enter_cmd(&mut self) {
         trace!("enter_cmd()");
-        if let Ok(Some(cmd)) = self.prompt("cmd: ") {
-            debug!("received command {:?}", cmd);
-        }
+        self.command = Some(String::new());
+        self.completions = None;
     }
 
-    fn search_forward(&mut self) {
-        self.search(SearchDirection::Forward)
+    fn append_cmd(&mut self, c: char) {
+        if let Some(ref mut cmd) = self.command {
+            cmd.push(c);
+        }
     }
 
-    fn search_backward(&mut self) {
-        self.search(SearchDirection::Backward)
+    fn backspace_cmd(&mut self) {
+        if let Some(ref mut cmd) = self.command {
+            cmd.pop();
+        }
     }
 
-    fn search(&mut self, direction: SearchDirection) {
-        trace!("search()");
-        let last_search_str = if let Some((ref last, _)) = self.last_search {
-            format!(" [{}]: ", last)
-        } else {
-            "".to_owned()
+    // The first tab fills in the only matching command name, or the first of
+    // several while listing them all. Further tabs cycle through the list
+    // until the command is edited.
+    fn complete_cmd(&mut self) {
+        let typed = match self.command {
+            Some(ref cmd) if !cmd.contains(' ') => cmd.clone(),
+            _ => return,
         };
-        let prompt = match direction {
-            SearchDirection::Forward => format!("search{}:", last_search_str),
-            SearchDirection::Backward => format!("search backwards{}:", last_search_str),
+        let colon = if typed.starts_with(':') { ":" } else { "" };
+        let name = &typed[colon.len()..];
+        let (names, idx) = match self.completions.take() {
+            Some((names, idx)) if name == names[idx] => {
+                let next = (idx + 1) % names.len();
+                (names, next)
+            }
+            _ => (complete_command(name), 0),
         };
-        if let Ok(Some(mut query)) = self.prompt(&*prompt) {
-            if query == "" {
-                if let Some((ref last, _)) = self.last_search {
-                    query = last.clone();
-                } else {
-                    self.last_search.take();
-                    return;
-                }
-            } else {
-                self.last_search.take();
+        if names.is_empty() {
+            self.message = Some(format!("no command starts with {}", name));
+            return;
+        }
+        if names.len() > 1 {
+            self.message = Some(names.join(" "));
+        }
+        self.command = Some(format!("{}{}", colon, names[idx]));
+        self.completions = Some((names, idx));
+    }
+
+    fn submit_cmd(&mut self) {
+        if let Some(cmd) = self.command.take() {
+            debug!("received command {:?}", cmd);
+            if let Err(e) = self.run_command(&cmd) {
+                warn!("{}", e);
+                self.message = Some(e);
             }
+        }
+    }
 
-            let mut f = |n: &Node| n.content.find(&*query).map(|idx| (idx, n.id));
-            let mut candidates = self.recursive_child_filter_map(self.drawing_root, &mut f);
-            if candidates.is_empty() {
-                return;
+    /// Runs a command line command, with or without the leading `:`.
+    ///
+    /// * `goto <id>` selects the node with that id, showing it.
+    /// * `due <YYYY-MM-DD>` sets when the selected node is due, and `due`
+    ///   alone clears it.
+    /// * `filter #<tag>` hides nodes without the tag that have no tagged
+    ///   descendants, and `filter` alone shows everything again.
+    /// * `sort priority` orders the children of the selected node, or of
+    ///   the drawing root, by descending priority.
+    /// * `set <setting> <value>` changes a keyfile setting such as
+    ///   `node_width` until void exits.
+    /// * `export md|dot|opml <path>` writes the tree below the drawing root
+    ///   to a file as a markdown outline, a Graphviz digraph or OPML.
+    /// * `import <path>` adds the headings and bullets of a markdown file
+    ///   below the drawing root, undone as one step.
+    /// * `w` saves, `q` quits, `wq` does both and `q!` quits without
+    ///   saving.
+    pub fn run_command(&mut self, cmd: &str) -> Result<(), String> {
+        let cmd = cmd.trim();
+        let mut words = cmd.strip_prefix(':').unwrap_or(cmd).split_whitespace();
+        let name = words.clone().next();
+        if self.readonly && matches!(name, Some("due") | Some("sort") | Some("import")) {
+            return Err(format!("read only, {} is off", name.unwrap()));
+        }
+        match words.next() {
+            Some("goto") => {
+                let raw_id = words.next().ok_or("usage: goto <id>")?;
+                let node_id: NodeID = raw_id
+                    .parse()
+                    .map_err(|_| format!("not a node id: {}", raw_id))?;
+                if node_id == 0 || !self.exists(node_id) {
+                    return Err(format!("no node with id {}", node_id));
+                }
+                self.zoom_select(node_id);
+                Ok(())
             }
-            candidates.sort();
-            let choice = if let Some((_, last_choice)) = self.last_search.take() {
-                let idx = candidates
-                    .iter()
-                    .position(|&e| e.1 == last_choice)
-                    .map(|i| match direction {
-                        SearchDirection::Forward => i + 1,
-                        SearchDirection::Backward => i + candidates.len() - 1,
-                    })
-                    .unwrap_or(0);
-                candidates[idx % candidates.len()]
-            } else {
-                candidates[0]
-            };
+            Some("due") => {
+                let selected_id = self.selected.ok_or("no node is selected")?;
+                let due = match words.next() {
+                    Some(raw_date) => Some(parse_due_date(raw_date)?),
+                    None => None,
+                };
+                self.with_node_mut_no_meta(selected_id, |n| n.meta.due = due);
+                Ok(())
+            }
+            Some("filter") => match words.next() {
+                Some(raw_tag) => {
+                    let tag = raw_tag.strip_prefix('#').unwrap_or(raw_tag);
+                    if self.tag_db.tag_to_nodes(tag).is_empty() {
+                        return Err(format!("no node is tagged #{}", tag));
+                    }
+                    self.tag_filter = Some(tag.to_owned());
+                    self.refresh_tag_filter();
+                    Ok(())
+                }
+                None => {
+                    self.tag_filter = None;
+                    self.refresh_tag_filter();
+                    Ok(())
+                }
+            }
+            Some("sort") => match words.next() {
+                Some("priority") => {
+                    self.sort_by_priority(self.selected.unwrap_or(self.drawing_root));
+                    Ok(())
+                }
+                Some(other) => Err(format!("can't sort by {}", other)),
+                None => Err("usage: sort priority".to_owned()),
+            }
+            Some("set") => {
+                let key = words.next().ok_or("usage: set <setting> <value>")?;
+                let value = words.collect::<Vec<_>>().join(" ");
+                self.config.settings.apply_setting(key, &value)
+            }
+            Some("export") => {
+                let usage = "usage: export md|dot|opml <path>";
+                let format = words.next().ok_or(usage)?;
+                let path = node_file_path(&words.collect::<Vec<_>>().join(" "));
+                if path.is_empty() {
+                    return Err(usage.to_owned());
+                }
+                let text = match format {
+                    "md" => self.export_outline(),
+                    "dot" => self.export_dot(),
+                    "opml" => self.export_opml(),
+                    other => return Err(format!("can't export as {}", other)),
+                };
+                std::fs::write(&path, text).map_err(|e| format!("cannot write {}: {}", path, e))
+            }
+            Some("import") => {
+                let path = node_file_path(&words.collect::<Vec<_>>().join(" "));
+                if path.is_empty() {
+                    return Err("usage: import <path>".to_owned());
+                }
+                let text = std::fs::read_to_string(&path)
+                    .map_err(|e| format!("cannot read {}: {}", path, e))?;
+                if self.import_markdown(&text).is_empty() {
+                    return Err(format!("no headings or bullets in {}", path));
+                }
+                Ok(())
+            }
+            Some("w") => {
+                self.save();
+                Ok(())
+            }
+            Some("q") => self.quit(false),
+            Some("q!") => self.quit(true),
+            Some("wq") => {
+                self.save();
+                self.quit(false)
+            }
+            Some("mark") => {
+                let name = match words.next().map(|w| w.chars().collect::<Vec<_>>()) {
+                    Some(ref chars) if chars.len() == 1 => chars[0],
+                    _ => return Err("usage: mark <letter>".to_owned()),
+                };
+                let selected_id = self.selected.ok_or("no node is selected")?;
+                self.marks.insert(name, selected_id);
+                Ok(())
+            }
+            Some(raw_mark) if raw_mark.starts_with('\'') => {
+                let mut chars = raw_mark.chars().skip(1);
+                match (chars.next(), chars.next()) {
+                    (Some(name), None) => self.jump_to_mark(name),
+                    _ => Err("usage: '<letter>".to_owned()),
+                }
+            }
+            Some(raw_line) if raw_line.chars().all(|c| c.is_ascii_digit()) => {
+                let line: usize = raw_line
+                    .parse()
+                    .map_err(|_| format!("not a line number: {}", raw_line))?;
+                self.select_line(line)
+            }
+            Some(other) => Err(format!("unknown command: {}", other)),
+            None => Ok(()),
+        }
+    }
 
-            self.last_search = Some((query.clone(), choice.1));
-            self.zoom_select(choice.1);
+    fn jump_to_mark(&mut self, name: char) -> Result<(), String> {
+        let node_id = *self
+            .marks
+            .get(&name)
+            .ok_or_else(|| format!("mark {} is not set", name))?;
+        if !self.exists(node_id) {
+            self.marks.remove(&name);
+            return Err(format!("mark {} was on a deleted node", name));
         }
+        self.zoom_select(node_id);
+        Ok(())
     }
 
-    fn prefix_jump_prompt(&mut self) {
-        trace!("prefix_jump_prompt()");
+    // counts from 1 like line numbers, with a number past the end taking
+    // the last node
+    fn select_line(&mut self, line: usize) -> Result<(), String> {
+        let nodes = self.visible_nodes();
+        if nodes.is_empty() {
+            return Err("no nodes are visible".to_owned());
+        }
+        let index = min(line.saturating_sub(1), nodes.len() - 1);
+        self.select_node(nodes[index]);
+        Ok(())
+    }
 
-        let prefix = match self.single_key_prompt("prefix: ") {
-            Ok(Key::Char(c)) => c.to_string(),
-            _ => return,
-        };
-        self.prefix_jump_to(prefix)
+    // refuses to lose unsaved changes unless forced
+    fn quit(&mut self, force: bool) -> Result<(), String> {
+        // nothing read only is saved, so nothing is lost
+        if self.dirty && !force && !self.readonly {
+            return Err("unsaved changes, use :q! to quit anyway".to_owned());
+        }
+        self.quitting = Some(force);
+        Ok(())
     }
 
-    fn prefix_jump_to(&mut self, prefix: String) {
-        let chars = "arstqwfpgdbvcxzoienyuljhkm1234567890ARSTQWFPGDVCXZOIENYULJHBKM";
-        // get visible nodes that contain prefix
-        let nodes = self.find_visible_nodes(|node_id| {
-            self.with_node(node_id, |n| n.content.starts_with(&*prefix))
-                .unwrap()
-        });
+    fn hidden_by_tag_filter(&self, node_id: NodeID) -> bool {
+        self.tag_filter.is_some() && !self.tag_visible.contains(&node_id)
+    }
 
-        if nodes.is_empty() {
-            return;
-        } else if nodes.len() == 1 {
-            let node_id = nodes[0];
-            self.select_node(node_id);
-            return;
+    // Tagged nodes are found in the tag index, then each ancestor of one is
+    // kept too, to show where it is. Runs before each draw, as tags and
+    // parents change.
+    fn refresh_tag_filter(&mut self) {
+        self.tag_visible.clear();
+        if let Some(ref tag) = self.tag_filter {
+            for node_id in self.tag_db.tag_to_nodes(tag) {
+                let mut next = Some(node_id);
+                while let Some(id) = next {
+                    if !self.tag_visible.insert(id) {
+                        break;
+                    }
+                    let parent_id = self.with_node(id, |n| n.parent_id);
+                    next = parent_id.filter(|&p| p != id);
+                }
+            }
         }
+    }
 
-        // map an alphanumeric char to each candidate NodeID
-        let mapping: HashMap<&str, NodeID> =
-            chars.split("").skip(1).zip(nodes.into_iter()).collect();
+    // Call after changing a node's text, so its tags and the tag index stay
+    // in step with it.
+    fn reindex_tags(&mut self, node_id: NodeID) {
+        if let Some(node) = self.nodes.get_mut(&node_id) {
+            node.parse_tags();
+            self.tag_db.set_tags(node_id, &node.tags);
+        }
+    }
 
-        // clear the prompt
-        print!("{}{}", cursor::Goto(1, self.dims.1), clear::AfterCursor);
+    fn typing_search(&self) -> bool {
+        self.search.as_ref().map(|s| s.typing).unwrap_or(false)
+    }
 
-        // print the hilighted char at each choice
-        for (&c, &node_id) in &mapping {
-            let &coords = self.drawn_at(node_id).unwrap();
-            let (x, y) = self.internal_to_screen_xy(coords).unwrap();
-            print!(
-                "{}{}{}{}",
-                cursor::Goto(x, y),
-                style::Invert,
-                c,
-                style::Reset
-            );
+    fn start_search(&mut self, direction: SearchDirection) {
+        trace!("start_search()");
+        // the mode sticks around from the previous search
+        let mode = self.search.as_ref().map(|s| s.mode).unwrap_or_default();
+        self.search = Some(Search {
+            query: String::new(),
+            matches: vec![],
+            current: 0,
+            typing: true,
+            direction,
+            mode,
+            invalid_regex: false,
+        });
+    }
+
+    fn append_search(&mut self, c: char) {
+        if let Some(ref mut search) = self.search {
+            search.query.push(c);
         }
+        self.refresh_search();
+    }
 
-        // read the choice
-        let choice = match self.single_key_prompt("choice: ") {
-            Ok(Key::Char(c)) => c.to_string(),
-            _ => return,
-        };
+    fn backspace_search(&mut self) {
+        if let Some(ref mut search) = self.search {
+            search.query.pop();
+        }
+        self.refresh_search();
+    }
 
-        // jump or exit
-        if let Some(&node_id) = mapping.get(&*choice) {
-            debug!("jumping to node {}", node_id);
-            self.select_node(node_id);
+    fn toggle_search_mode(&mut self) {
+        if let Some(ref mut search) = self.search {
+            search.mode = search.mode.next();
         }
+        self.refresh_search();
     }
 
-    fn find_visible_nodes<F>(&self, mut filter: F) -> Vec<NodeID>
-    where
-        F: FnMut(NodeID) -> bool,
-    {
-        self.drawn_at
-            .keys()
+    fn refresh_search(&mut self) {
+        if let Some(mut search) = self.search.take() {
+            let matches = self.mode_matches(&search.query, search.mode);
+            search.invalid_regex = matches.is_none();
+            search.matches = matches.unwrap_or_default();
+            search.current = 0;
+            self.search = Some(search);
+        }
+    }
+
+    fn submit_search(&mut self) {
+        if let Some(mut search) = self.search.take() {
+            if search.matches.is_empty() {
+                return;
+            }
+            search.typing = false;
+            search.current = match search.direction {
+                SearchDirection::Forward => 0,
+                SearchDirection::Backward => search.matches.len() - 1,
+            };
+            let choice = search.matches[search.current];
+            self.search = Some(search);
+            self.zoom_select(choice);
+        }
+    }
+
+    fn search_next(&mut self) {
+        self.step_search(1)
+    }
+
+    fn search_prev(&mut self) {
+        self.step_search(-1)
+    }
+
+    // moves through the matches of the last submitted search, wrapping
+    // around at either end
+    fn step_search(&mut self, step: isize) {
+        if let Some(mut search) = self.search.take() {
+            if search.typing {
+                self.search = Some(search);
+                return;
+            }
+            let current = search.matches.get(search.current).cloned();
+            search.matches.retain(|id| self.nodes.contains_key(id));
+            if search.matches.is_empty() {
+                return;
+            }
+            let len = search.matches.len() as isize;
+            let idx = search
+                .matches
+                .iter()
+                .position(|&id| Some(id) == current)
+                .map(|i| i as isize + step)
+                .unwrap_or(0);
+            search.current = ((idx % len + len) % len) as usize;
+            let choice = search.matches[search.current];
+            self.search = Some(search);
+            self.zoom_select(choice);
+        }
+    }
+
+    fn is_search_match(&self, node_id: NodeID) -> bool {
+        self.search
+            .as_ref()
+            .map(|s| s.matches.contains(&node_id))
+            .unwrap_or(false)
+    }
+
+    /// Returns the nodes below the current drawing root whose text contains
+    /// `query`, in tree order.
+    pub fn matches(&self, query: &str) -> Vec<NodeID> {
+        self.mode_matches(query, SearchMode::Exact)
+            .unwrap_or_default()
+    }
+
+    // None when the query is not a valid regex in SearchMode::Regex.
+    fn mode_matches(&self, query: &str, mode: SearchMode) -> Option<Vec<NodeID>> {
+        if query.is_empty() {
+            return Some(vec![]);
+        }
+        let root = self.drawing_root;
+        let filter = try_search_filter(query, mode)?;
+        let mut f = |n: &Node| {
+            if n.id != root && filter(&n.content) {
+                Some(n.id)
+            } else {
+                None
+            }
+        };
+        Some(self.recursive_child_filter_map(root, &mut f))
+    }
+
+    // Jumps to the best fuzzy match for a prompted query. Submitting the
+    // same or an empty query again moves on to the next best match.
+    fn fuzzy_find(&mut self) {
+        trace!("fuzzy_find()");
+        let prompt = if let Some((ref last, _)) = self.last_fuzzy_find {
+            format!("fuzzy find [{}]: ", last)
+        } else {
+            "fuzzy find: ".to_owned()
+        };
+        let query = match self.prompt(&prompt) {
+            Ok(Some(query)) => query,
+            _ => return,
+        };
+        let last = self.last_fuzzy_find.take();
+        let (query, last_choice) = match last {
+            Some((last, choice)) if query.is_empty() || query == last => (last, Some(choice)),
+            _ if query.is_empty() => return,
+            _ => (query, None),
+        };
+
+        let candidates = self.fuzzy_candidates(&query);
+        if candidates.is_empty() {
+            return;
+        }
+        let idx = last_choice
+            .and_then(|last| candidates.iter().position(|&id| id == last))
+            .map(|i| (i + 1) % candidates.len())
+            .unwrap_or(0);
+        let choice = candidates[idx];
+        self.last_fuzzy_find = Some((query, choice));
+        self.zoom_select(choice);
+    }
+
+    // nodes below the drawing root that fuzzy match query, best first, with
+    // ties going to the shallower node
+    fn fuzzy_candidates(&self, query: &str) -> Vec<NodeID> {
+        let root = self.drawing_root;
+        let mut f = |n: &Node| {
+            if n.id == root {
+                None
+            } else {
+                fuzzy_score(query, &n.content).map(|score| (score, n.id))
+            }
+        };
+        let mut candidates: Vec<_> = self
+            .recursive_child_filter_map(root, &mut f)
+            .into_iter()
+            .map(|(score, id)| (-score, self.lineage(id).len(), id))
+            .collect();
+        candidates.sort();
+        candidates.into_iter().map(|(_, _, id)| id).collect()
+    }
+
+    fn prefix_jump_prompt(&mut self) {
+        trace!("prefix_jump_prompt()");
+
+        let prefix = match self.single_key_prompt("prefix: ") {
+            Ok(Key::Char(c)) => c.to_string(),
+            _ => return,
+        };
+        self.prefix_jump_to(prefix)
+    }
+
+    fn prefix_jump_to(&mut self, prefix: String) {
+        // get visible nodes that contain prefix
+        let nodes = self.find_visible_nodes(|node_id| {
+            self.with_node(node_id, |n| n.content.starts_with(&*prefix))
+                .unwrap()
+        });
+
+        if nodes.is_empty() {
+            return;
+        } else if nodes.len() == 1 {
+            let node_id = nodes[0];
+            self.select_node(node_id);
+            return;
+        }
+
+        // label each candidate NodeID with keys to type
+        let labels = assign_jump_labels(nodes.len(), &self.config.settings.jump_keys);
+        let mapping: HashMap<String, NodeID> = labels.into_iter().zip(nodes).collect();
+
+        // clear the prompt
+        print!("{}{}", cursor::Goto(1, self.dims.1), clear::AfterCursor);
+
+        // print the hilighted label at each choice
+        for (label, &node_id) in &mapping {
+            let &coords = self.drawn_at(node_id).unwrap();
+            let (x, y) = self.internal_to_screen_xy(coords).unwrap();
+            print!(
+                "{}{}{}{}",
+                cursor::Goto(x, y),
+                style::Invert,
+                label,
+                self.reset_style()
+            );
+        }
+
+        // read the choice a key at a time, labels are prefix-free so the
+        // first complete label is the one meant
+        let mut choice = String::new();
+        loop {
+            match self.single_key_prompt(&format!("choice: {}", choice)) {
+                Ok(Key::Char(c)) => choice.push(c),
+                _ => return,
+            }
+
+            // jump or exit
+            if let Some(&node_id) = mapping.get(&choice) {
+                debug!("jumping to node {}", node_id);
+                self.select_node(node_id);
+                return;
+            }
+            if !mapping.keys().any(|label| label.starts_with(&*choice)) {
+                return;
+            }
+        }
+    }
+
+    fn find_visible_nodes<F>(&self, mut filter: F) -> Vec<NodeID>
+    where
+        F: FnMut(NodeID) -> bool,
+    {
+        self.drawn_at
+            .keys()
             .filter(|&node_id| self.node_is_visible(*node_id).unwrap())
             .filter(|&node_id| filter(*node_id))
             .cloned()
             .collect()
     }
 
+    /// The nodes drawn in view as of the last draw, in reading order: top
+    /// to bottom, then left to right.
+    fn visible_nodes(&self) -> Vec<NodeID> {
+        let mut nodes = self.find_visible_nodes(|node_id| node_id != 0);
+        nodes.sort_by_key(|node_id| {
+            let (x, y) = self.drawn_at[node_id];
+            (y, x)
+        });
+        nodes
+    }
+
     fn exec_selected(&mut self) {
         if self.is_test || self.selected.is_none() {
             // tests generate many randomly named nodes, so we don't
@@ -564,27 +1581,199 @@ impl Screen {
         } else if content.starts_with("txt:") {
             self.exec_text_editor(selected_id);
         } else if content.starts_with("http") {
-            #[cfg(any(target_os = "macos",))]
-            let default_open_cmd = "open";
-            #[cfg(target_os = "linux")]
-            let default_open_cmd = "xdg-open";
-            #[cfg(target_os = "windows")]
-            let default_open_cmd = "start";
-
-            let browser = env::var("BROWSER").unwrap_or_else(|_| default_open_cmd.to_owned());
-            let cmd = process::Command::new(browser).arg(&content).spawn();
-            if cmd.is_err() {
+            if open_in_browser(&content).is_err() {
                 error!("command failed to start: {}", &content);
             }
+        } else if self.execs.contains_key(&selected_id) {
+            warn!("command is already running: {}", &content);
         } else {
-            let shell = env::var("SHELL").unwrap_or_else(|_| "bash".to_owned());
-            let cmd = process::Command::new(shell).arg("-c").arg(&content).spawn();
-            if cmd.is_err() {
-                error!("command failed to start: {}", &content);
+            self.spawn_exec(selected_id, &expand_env(&content));
+        }
+    }
+
+    // Opens the first URL in the selected node's text, wherever it is in it.
+    fn open_url(&mut self) {
+        let selected = self.selected;
+        let content = match selected.and_then(|id| self.with_node(id, |n| n.content.clone())) {
+            Some(content) => content,
+            None => return,
+        };
+        match find_url(&content) {
+            None => self.message = Some("no URL in the selected node".to_owned()),
+            // opening browsers from tests helps nobody
+            Some(_) if self.is_test => {}
+            Some(url) => {
+                info!("opening {}", url);
+                if let Err(e) = open_in_browser(url) {
+                    self.message = Some(format!("failed to open {}: {}", url, e));
+                }
+            }
+        }
+    }
+
+    // Opens the file the selected node's text names in $EDITOR, handing it
+    // the terminal until it exits.
+    fn edit_in_editor(&mut self) {
+        let selected = self.selected;
+        let content = match selected.and_then(|id| self.with_node(id, |n| n.content.clone())) {
+            Some(content) => content,
+            None => return,
+        };
+        let path = node_file_path(&content);
+        if !Path::new(&path).exists() {
+            self.message = Some(format!("no file at {}", path));
+            return;
+        }
+        let cmd = editor_command(env::var("EDITOR").ok().as_deref(), &path);
+        if self.is_test {
+            return;
+        }
+
+        // have raw mode destructor run
+        self.cleanup();
+        let status = process::Command::new(&cmd[0]).args(&cmd[1..]).status();
+        self.start_raw_mode();
+        match status {
+            Ok(status) if !status.success() => {
+                self.message = Some(format!("{} exited with {}", cmd[0], status));
+            }
+            Err(e) => self.message = Some(format!("failed to start {}: {}", cmd[0], e)),
+            Ok(_) => {}
+        }
+    }
+
+    // Starts content in the background, tracked under node_id until
+    // poll_execs sees it finish. Captured output is read on helper threads
+    // so a chatty command can't fill its pipes and stall.
+    fn spawn_exec(&mut self, node_id: NodeID, content: &str) {
+        let shell = env::var("SHELL").unwrap_or_else(|_| "bash".to_owned());
+        let mut cmd = process::Command::new(shell);
+        cmd.arg("-c").arg(content);
+        if self.config.settings.exec_capture_output {
+            cmd.stdin(process::Stdio::null())
+                .stdout(process::Stdio::piped())
+                .stderr(process::Stdio::piped());
+        }
+        match cmd.spawn() {
+            Ok(mut child) => {
+                let stdout = child.stdout.take().map(read_to_end_in_thread);
+                let stderr = child.stderr.take().map(read_to_end_in_thread);
+                self.execs.insert(
+                    node_id,
+                    Exec {
+                        child,
+                        stdout,
+                        stderr,
+                    },
+                );
+            }
+            Err(e) => error!("command failed to start: {}: {}", content, e),
+        }
+    }
+
+    // Runs between events and every TICK_MS without them, returning whether
+    // the screen needs a redraw. Commands that finished add their output,
+    // and running ones keep their spinners turning.
+    fn tick(&mut self) -> bool {
+        let finished = self.recorded(false, |screen| screen.poll_execs());
+        finished || !self.execs.is_empty()
+    }
+
+    // Collects every finished command, adding captured output under its
+    // node. Returns whether any finished.
+    fn poll_execs(&mut self) -> bool {
+        let finished: Vec<NodeID> = self
+            .execs
+            .iter_mut()
+            .filter_map(|(&node_id, exec)| match exec.child.try_wait() {
+                Ok(None) => None,
+                _ => Some(node_id),
+            })
+            .collect();
+        for &node_id in &finished {
+            let mut exec = self.execs.remove(&node_id).unwrap();
+            let status = match exec.child.wait() {
+                Ok(status) => status,
+                Err(e) => {
+                    error!("failed to wait on command: {}", e);
+                    continue;
+                }
+            };
+            if exec.stdout.is_none() {
+                if !status.success() {
+                    warn!("command for node {} exited with {}", node_id, status);
+                }
+                continue;
+            }
+            let join = |handle: Option<thread::JoinHandle<Vec<u8>>>| {
+                handle.and_then(|h| h.join().ok()).unwrap_or_default()
+            };
+            let output = process::Output {
+                status,
+                stdout: join(exec.stdout.take()),
+                stderr: join(exec.stderr.take()),
+            };
+            if self.exists(node_id) {
+                self.add_output_children(node_id, &output);
+            }
+        }
+        !finished.is_empty()
+    }
+
+    fn cancel_exec(&mut self) {
+        if let Some(selected_id) = self.selected {
+            if let Some(mut exec) = self.execs.remove(&selected_id) {
+                info!("killing command for node {}", selected_id);
+                if let Err(e) = exec.child.kill() {
+                    error!("failed to kill command: {}", e);
+                }
+                let _ = exec.child.wait();
             }
         }
     }
 
+    // Adds each non-blank line of a finished command's stdout then stderr as
+    // a child of node_id, up to exec_max_lines of them. A failed command gets
+    // a last child holding its exit status.
+    fn add_output_children(&mut self, node_id: NodeID, output: &process::Output) {
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let lines: Vec<_> = stdout
+            .lines()
+            .chain(stderr.lines())
+            .filter(|line| !line.trim().is_empty())
+            .collect();
+        let max_lines = self.config.settings.exec_max_lines;
+        let mut contents: Vec<_> = lines
+            .iter()
+            .take(max_lines)
+            .map(|&l| l.to_owned())
+            .collect();
+        if lines.len() > max_lines {
+            contents.push(format!("… {} more lines", lines.len() - max_lines));
+        }
+        if !output.status.success() {
+            contents.push(match output.status.code() {
+                Some(code) => format!("exit status {}", code),
+                None => "killed by signal".to_owned(),
+            });
+        }
+        for content in contents {
+            self.add_child(node_id, content);
+        }
+    }
+
+    fn add_child(&mut self, parent_id: NodeID, content: String) -> NodeID {
+        let node_id = self.new_node();
+        self.with_node_mut_no_meta(node_id, |node| {
+            node.parent_id = parent_id;
+            node.content = content.clone();
+        });
+        self.reindex_tags(node_id);
+        self.with_node_mut_no_meta(parent_id, |parent| parent.children.push(node_id));
+        node_id
+    }
+
     fn exec_text_editor(&mut self, node_id: NodeID) {
         let text = self
             .with_node(node_id, |n| n.free_text.clone())
@@ -642,31 +1831,18 @@ impl Screen {
 
     pub fn arrange(&mut self) {
         trace!("arrange");
-        let mut real_estate = Pack {
-            children: None,
-            top: 2,                // leave room for header
-            left: 1,               // 1-indexed screen
-            bottom: std::u16::MAX, // make this "bottomless" since we can paginate
-            right: max(self.dims.0, 1) - 1,
-            elem: None,
-        };
-
         let nodes = self
             .with_node(self.drawing_root, |n| n.children.clone())
             .unwrap();
-        let mut node_dims: Vec<(NodeID, Coords)> = nodes
+        let node_dims: Vec<(NodeID, Coords)> = nodes
             .into_iter()
             .map(|n| (n, self.drawable_subtree_dims(n).unwrap()))
             .collect();
-        node_dims.sort_by_key(|&(_, (_, y))| y);
-        node_dims.reverse();
-
-        for (node_id, dims) in node_dims {
-            // add some spacing around this tree to space out
-            // placement a little bit
-            let padded_dims = (dims.0 + 2, dims.1 + 2);
-            if let Some((x, y)) = real_estate.insert(padded_dims) {
-                self.with_node_mut_no_meta(node_id, |n| n.rooted_coords = (x, y))
+
+        let placed = pack::arrange(&node_dims, max(self.dims.0, 1) - 1);
+        for (node_id, _) in node_dims {
+            if let Some(&coords) = placed.get(&node_id) {
+                self.with_node_mut_no_meta(node_id, |n| n.rooted_coords = coords)
                     .unwrap();
             }
         }
@@ -693,6 +1869,159 @@ impl Screen {
         ret
     }
 
+    /// Renders the tree below the current drawing root as a markdown
+    /// outline, one `* [ ] text` line per node, indented two spaces per
+    /// level. Top level nodes are in the order they appear on screen.
+    pub fn export_outline(&self) -> String {
+        let mut buf = String::new();
+        let anchors = self.sorted_anchors();
+        let mut to_visit: Vec<_> = anchors.into_iter().rev().map(|id| (id, 0)).collect();
+        while let Some((node_id, depth)) = to_visit.pop() {
+            if let Some(node) = self.nodes.get(&node_id) {
+                let checkbox = if node.stricken { "[x]" } else { "[ ]" };
+                writeln!(buf, "{}* {} {}", "  ".repeat(depth), checkbox, node.content).unwrap();
+                to_visit.extend(node.children.iter().rev().map(|&id| (id, depth + 1)));
+            }
+        }
+        buf
+    }
+
+    /// Renders the tree below the current drawing root as a Graphviz
+    /// digraph. Parent to child edges are solid, and arrows between nodes
+    /// that are both exported are dashed.
+    pub fn export_dot(&self) -> String {
+        let mut buf = String::new();
+        let mut exported = HashSet::new();
+        writeln!(buf, "digraph void {{").unwrap();
+        let mut to_visit: Vec<_> = self.sorted_anchors().into_iter().rev().collect();
+        while let Some(node_id) = to_visit.pop() {
+            if let Some(node) = self.nodes.get(&node_id) {
+                exported.insert(node_id);
+                let label = dot_escape(&node.content);
+                writeln!(buf, "  \"{}\" [label=\"{}\"];", node_id, label).unwrap();
+                for &child in &node.children {
+                    writeln!(buf, "  \"{}\" -> \"{}\";", node_id, child).unwrap();
+                }
+                to_visit.extend(node.children.iter().rev());
+            }
+        }
+        for &(from, to) in &self.arrows {
+            if exported.contains(&from) && exported.contains(&to) {
+                writeln!(buf, "  \"{}\" -> \"{}\" [style=dashed];", from, to).unwrap();
+            }
+        }
+        writeln!(buf, "}}").unwrap();
+        buf
+    }
+
+    /// Renders the tree below the current drawing root as an OPML document
+    /// of nested outlines, with `_complete="true"` on completed nodes.
+    pub fn export_opml(&self) -> String {
+        let title = match self.drawing_root {
+            0 => "void".to_owned(),
+            root => self
+                .with_node(root, |n| n.content.clone())
+                .unwrap_or_default(),
+        };
+        let mut buf = String::new();
+        writeln!(buf, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>").unwrap();
+        writeln!(buf, "<opml version=\"2.0\">").unwrap();
+        writeln!(buf, "  <head>").unwrap();
+        writeln!(buf, "    <title>{}</title>", xml_escape(&title)).unwrap();
+        writeln!(buf, "  </head>").unwrap();
+        writeln!(buf, "  <body>").unwrap();
+        for anchor in self.sorted_anchors() {
+            self.write_opml_outline(&mut buf, anchor, 2);
+        }
+        writeln!(buf, "  </body>").unwrap();
+        writeln!(buf, "</opml>").unwrap();
+        buf
+    }
+
+    fn write_opml_outline(&self, buf: &mut String, node_id: NodeID, depth: usize) {
+        let node = match self.nodes.get(&node_id) {
+            Some(node) => node,
+            None => return,
+        };
+        let indent = "  ".repeat(depth);
+        let complete = if node.stricken {
+            " _complete=\"true\""
+        } else {
+            ""
+        };
+        let text = xml_escape(&node.content);
+        if node.children.is_empty() {
+            writeln!(buf, "{}<outline text=\"{}\"{}/>", indent, text, complete).unwrap();
+            return;
+        }
+        writeln!(buf, "{}<outline text=\"{}\"{}>", indent, text, complete).unwrap();
+        for &child in &node.children {
+            self.write_opml_outline(buf, child, depth + 1);
+        }
+        writeln!(buf, "{}</outline>", indent).unwrap();
+    }
+
+    // top level nodes in the order they appear on screen
+    fn sorted_anchors(&self) -> Vec<NodeID> {
+        let mut anchors = self
+            .with_node(self.drawing_root, |n| n.children.clone())
+            .unwrap_or_default();
+        anchors.sort_by_key(|&id| self.with_node(id, |n| (n.rooted_coords.1, n.rooted_coords.0)));
+        anchors
+    }
+
+    /// Adds a markdown outline below the drawing root, reading what
+    /// `export_outline` writes. `#` headings nest by level, `-` and `*`
+    /// bullets by indentation under the heading above them, and a `[x]`
+    /// checkbox marks a node complete. Other lines are skipped. The new top
+    /// level nodes are returned, placed one under another below the trees
+    /// already there.
+    pub fn import_markdown(&mut self, text: &str) -> Vec<NodeID> {
+        let root = self.drawing_root;
+        let existing = self.with_node(root, |n| n.children.clone()).unwrap();
+        let mut anchors = vec![];
+        // the nodes the next line can go under, with the rank of their line
+        let mut open: Vec<(usize, NodeID)> = vec![];
+        for line in text.lines() {
+            let (rank, checked, content) = match outline_item(line) {
+                Some(item) => item,
+                None => continue,
+            };
+            while open.last().is_some_and(|&(r, _)| r >= rank) {
+                open.pop();
+            }
+            let parent = open.last().map_or(root, |&(_, id)| id);
+            let node_id = self.new_node();
+            self.with_node_mut_no_meta(node_id, |node| {
+                node.parent_id = parent;
+                node.content = content.to_owned();
+                node.stricken = checked;
+            });
+            self.reindex_tags(node_id);
+            self.with_node_mut_no_meta(parent, |p| p.children.push(node_id));
+            if parent == root {
+                anchors.push(node_id);
+            }
+            open.push((rank, node_id));
+        }
+
+        let mut y = existing
+            .into_iter()
+            .filter_map(|id| {
+                let top = self.with_node(id, |n| n.rooted_coords.1)?;
+                let (_, height) = self.drawable_subtree_dims(id)?;
+                Some(top + height + 1)
+            })
+            .max()
+            .unwrap_or(2);
+        for &anchor in &anchors {
+            self.with_node_mut_no_meta(anchor, |n| n.rooted_coords = (1, y));
+            let (_, height) = self.drawable_subtree_dims(anchor).unwrap_or((0, 1));
+            y += height + 1;
+        }
+        anchors
+    }
+
     fn drawable_subtree_dims(&mut self, node_id: NodeID) -> Option<(u16, u16)> {
         if let Some(widths) = self.drawable_subtree_widths(node_id, 0) {
             let height = widths.len() as u16;
@@ -707,13 +2036,17 @@ impl Screen {
         let raw_node_opt = self.with_node(node_id, |n| n.clone());
         if let Some(raw_node) = raw_node_opt {
             let node = self.format_node(&raw_node);
-            let width = 1 + (3 * depth as u16) + node.content.len() as u16;
-            let mut ret = vec![width];
+            // one row per wrapped line
+            let mut ret: Vec<u16> = wrap_text(&node.content, self.config.settings.node_width)
+                .iter()
+                .map(|line| 1 + (3 * depth as u16) + display_width(line) as u16)
+                .collect();
             let hide_stricken = self.with_node(node_id, |n| n.hide_stricken).unwrap();
             if !node.collapsed {
                 for &child in &node.children {
                     let stricken = self.with_node(child, |c| c.stricken).unwrap();
-                    if !(hide_stricken && stricken) {
+                    let hidden = hide_stricken && stricken || self.hidden_by_tag_filter(child);
+                    if !hidden {
                         // ASSUMES node.children are all valid
                         let mut child_widths =
                             self.drawable_subtree_widths(child, depth + 1).unwrap();
@@ -737,6 +2070,16 @@ impl Screen {
 
     fn unselect(&mut self) -> Option<NodeID> {
         trace!("unselect()");
+        // editing starts over at the end
+        self.cursor = None;
+        if let Some((node_id, old_content)) = self.renaming.take() {
+            let unnamed = self.with_node(node_id, |n| n.content.is_empty());
+            if self.selected == Some(node_id) && unnamed == Some(true) {
+                self.with_node_mut_no_meta(node_id, |n| n.content = old_content.clone());
+                self.width_cache.remove(&node_id);
+                self.reindex_tags(node_id);
+            }
+        }
         lazy_static! {
             static ref RE_DATE: Regex = Regex::new(r"\[(\S+)\]").unwrap();
         }
@@ -768,6 +2111,7 @@ impl Screen {
                     }
                 }
             });
+            self.reindex_tags(selected_id);
         }
         self.selected.take()
     }
@@ -777,15 +2121,18 @@ impl Screen {
         if coords.1 < self.view_y + 2 || coords.1 > self.view_y + self.dims.1 {
             // coords are above or below screen
             None
+        } else if coords.0 <= self.view_x || coords.0 > self.view_x + self.dims.0 {
+            // coords are left or right of screen
+            None
         } else {
-            Some((coords.0, coords.1 - self.view_y))
+            Some((coords.0 - self.view_x, coords.1 - self.view_y))
         }
     }
 
     fn screen_to_internal_xy(&self, coords: Coords) -> Coords {
         (
-            coords.0,
-            min(coords.1, std::u16::MAX - self.view_y) + self.view_y,
+            min(coords.0, u16::MAX - self.view_x) + self.view_x,
+            min(coords.1, u16::MAX - self.view_y) + self.view_y,
         )
     }
 
@@ -830,13 +2177,81 @@ impl Screen {
         None
     }
 
+    // With cascade_complete, descendants take the new state of the selected
+    // node rather than each toggling, so the subtree always ends up uniform.
     fn toggle_stricken(&mut self) {
         trace!("toggle_stricken()");
         if let Some(selected_id) = self.selected {
-            self.with_node_mut(selected_id, |node| node.toggle_stricken());
+            let stricken = self
+                .with_node_mut(selected_id, |node| {
+                    node.toggle_stricken();
+                    node.stricken
+                })
+                .unwrap();
+            if self.config.settings.cascade_complete {
+                let differing = self.recursive_child_filter_map(selected_id, &mut |n: &Node| {
+                    if n.stricken != stricken {
+                        Some(n.id)
+                    } else {
+                        None
+                    }
+                });
+                for node_id in differing {
+                    self.with_node_mut(node_id, |node| node.toggle_stricken());
+                }
+            }
+            if self.config.settings.auto_complete_parents {
+                self.complete_ancestors(selected_id);
+            }
+        }
+    }
+
+    // Walks up from node_id, completing each parent whose children are all
+    // complete and reopening completed parents with an open child. Stops
+    // at the first parent left as it was, and at the root, which free
+    // nodes hang off directly.
+    fn complete_ancestors(&mut self, node_id: NodeID) {
+        let mut parent_id = self.with_node(node_id, |n| n.parent_id);
+        // the parent chain has no cycles, but don't trust that blindly
+        let mut seen = HashSet::new();
+        while let Some(id) = parent_id.filter(|&id| id != 0 && seen.insert(id)) {
+            let (stricken, children) = match self.nodes.get(&id) {
+                Some(parent) => (parent.stricken, parent.children.clone()),
+                None => return,
+            };
+            let all_done = children
+                .iter()
+                .all(|c| self.with_node(*c, |n| n.stricken).unwrap_or(true));
+            if stricken == all_done {
+                return;
+            }
+            self.with_node_mut(id, |node| node.toggle_stricken());
+            parent_id = self.with_node(id, |n| n.parent_id);
+        }
+    }
+
+    fn toggle_in_progress(&mut self) {
+        trace!("toggle_in_progress()");
+        if let Some(selected_id) = self.selected {
+            self.with_node_mut(selected_id, |node| node.toggle_in_progress());
+        }
+    }
+
+    fn change_priority(&mut self, f: fn(&mut Node)) {
+        if let Some(selected_id) = self.selected {
+            self.with_node_mut(selected_id, f);
         }
     }
 
+    // Stable, so children of equal priority keep their order.
+    fn sort_by_priority(&mut self, parent_id: NodeID) {
+        let mut children = self
+            .with_node(parent_id, |n| n.children.clone())
+            .unwrap_or_default();
+        children.sort_by_key(|id| std::cmp::Reverse(self.nodes.get(id).map_or(0, |n| n.priority)));
+        self.with_node_mut_no_meta(parent_id, |n| n.children = children.clone());
+    }
+
     fn toggle_hide_stricken(&mut self) {
         trace!("toggle_hide_stricken()");
         if let Some(selected_id) = self.selected {
@@ -844,8 +2259,12 @@ impl Screen {
         }
     }
 
-    fn delete_recursive(&mut self, node_id: NodeID) {
+    // Removes node_id and its descendants, returning the removed nodes.
+    fn delete_recursive(&mut self, node_id: NodeID) -> HashMap<NodeID, Node> {
         trace!("delete_recursive({})", node_id);
+        let mut removed = HashMap::new();
+        self.touch(node_id);
+        self.touch_arrows();
         if let Some(node) = self.nodes.remove(&node_id) {
             // clean up any arrow state
             self.arrows
@@ -855,44 +2274,154 @@ impl Screen {
             self.tag_db.remove(node_id);
 
             for child_id in &node.children {
-                self.delete_recursive(*child_id);
+                removed.extend(self.delete_recursive(*child_id));
             }
 
-            self.undo_nodes.insert(node_id, node);
+            removed.insert(node_id, node);
         }
+        removed
     }
 
-    fn delete_selected(&mut self, reselect: bool) {
-        trace!("delete_selected()");
-        if let Some(selected_id) = self.selected.take() {
-            let (_, height) = self.drawable_subtree_dims(selected_id).unwrap();
-            let coords = self.drawn_at.remove(&selected_id);
-            // remove ref from parent
-            if let Some(parent_id) = self.parent(selected_id) {
+    // Copies the selected subtree in as the next sibling and selects the copy.
+    // A duplicated anchor is placed below the original.
+    fn duplicate_selected(&mut self) {
+        if let Some(selected_id) = self.selected {
+            let parent_id = if let Some(parent_id) = self.parent(selected_id) {
+                parent_id
+            } else {
+                return;
+            };
+            let copy_id = if let Some(copy_id) = self.duplicate_recursive(selected_id, parent_id) {
+                copy_id
+            } else {
+                return;
+            };
+            if parent_id == self.drawing_root {
+                let height = self
+                    .drawable_subtree_dims(selected_id)
+                    .map(|(_, h)| h)
+                    .unwrap_or(1);
+                self.with_node_mut_no_meta(copy_id, |copy| {
+                    copy.rooted_coords.1 += height + 1;
+                });
+            }
+            self.with_node_mut_no_meta(parent_id, |parent| {
+                let idx = parent
+                    .children
+                    .iter()
+                    .position(|&e| e == selected_id)
+                    .unwrap_or(0);
+                parent.children.insert(idx + 1, copy_id);
+            });
+            self.select_node(copy_id);
+        }
+    }
+
+    // Copies node_id and its descendants under fresh ids, returning the id
+    // of the copy. The caller adds the copy to parent_id's children.
+    fn duplicate_recursive(&mut self, node_id: NodeID, parent_id: NodeID) -> Option<NodeID> {
+        let mut copy = self.nodes.get(&node_id)?.clone();
+        let copy_id = self.new_node_id();
+        let children = copy.children.clone();
+        copy.id = copy_id;
+        copy.parent_id = parent_id;
+        copy.selected = false;
+        copy.children = vec![];
+        self.tag_db.set_tags(copy_id, &copy.tags);
+        self.touch(copy_id);
+        self.nodes.insert(copy_id, copy);
+
+        for child_id in children {
+            if let Some(child_copy) = self.duplicate_recursive(child_id, copy_id) {
+                self.with_node_mut_no_meta(copy_id, |copy| copy.children.push(child_copy));
+            }
+        }
+        Some(copy_id)
+    }
+
+    fn delete_selected(&mut self, reselect: bool) {
+        trace!("delete_selected()");
+        if let Some(selected_id) = self.selected.take() {
+            let (_, height) = self.drawable_subtree_dims(selected_id).unwrap();
+            let coords = self.drawn_at.remove(&selected_id);
+            let mut position = (self.drawing_root, 0);
+            // remove ref from parent
+            if let Some(parent_id) = self.parent(selected_id) {
                 trace!("deleting node {} from parent {}", selected_id, parent_id);
-                self.with_node_mut_no_meta(parent_id, |p| p.children.retain(|c| c != &selected_id))
+                let idx = self
+                    .with_node_mut_no_meta(parent_id, |p| {
+                        let idx = p.children.iter().position(|c| c == &selected_id);
+                        p.children.retain(|c| c != &selected_id);
+                        idx
+                    })
                     .unwrap();
+                position = (parent_id, idx.unwrap_or(0));
             }
             // remove children
-            self.delete_recursive(selected_id);
+            let nodes = self.delete_recursive(selected_id);
             if let Some((x, y)) = coords {
                 if reselect {
                     self.click_select((x, y + height));
                 }
             }
-            self.undo_stack.push(selected_id);
+            self.undo_stack.push(Deletion {
+                node_id: selected_id,
+                parent_id: position.0,
+                idx: position.1,
+                nodes,
+            });
         }
     }
 
+    // Puts the last deleted subtree back at its old index, or at the end of
+    // the nearest ancestor that is still around if its parent was deleted too.
     fn undo_delete(&mut self) {
-        if let Some(node_id) = self.undo_stack.pop() {
-            self.recursive_restore(node_id).unwrap();
+        if let Some(deletion) = self.undo_stack.pop() {
+            let Deletion {
+                node_id,
+                parent_id,
+                idx,
+                mut nodes,
+            } = deletion;
+            let ancestor = self.surviving_ancestor(parent_id);
+            if let Some(node) = nodes.get_mut(&node_id) {
+                node.parent_id = ancestor;
+            }
+            self.with_node_mut_no_meta(ancestor, |p| {
+                let idx = if ancestor == parent_id {
+                    min(idx, p.children.len())
+                } else {
+                    p.children.len()
+                };
+                p.children.insert(idx, node_id);
+            });
+            self.recursive_restore(node_id, &mut nodes).unwrap();
             self.select_node(node_id);
         }
     }
 
-    fn recursive_restore(&mut self, node_id: NodeID) -> Result<(), ()> {
-        if let Some(node) = self.undo_nodes.remove(&node_id) {
+    fn surviving_ancestor(&self, mut node_id: NodeID) -> NodeID {
+        while !self.exists(node_id) {
+            let parent_id = self
+                .undo_stack
+                .iter()
+                .filter_map(|deletion| deletion.nodes.get(&node_id))
+                .map(|node| node.parent_id)
+                .next();
+            node_id = match parent_id {
+                Some(parent_id) => parent_id,
+                None => return 0,
+            };
+        }
+        node_id
+    }
+
+    fn recursive_restore(
+        &mut self,
+        node_id: NodeID,
+        nodes: &mut HashMap<NodeID, Node>,
+    ) -> Result<(), ()> {
+        if let Some(node) = nodes.remove(&node_id) {
             self.with_node_mut_no_meta(node.parent_id, |p| {
                 if !p.children.contains(&node.id) {
                     p.children.push(node.id);
@@ -900,9 +2429,10 @@ impl Screen {
             })
             .unwrap();
             let children = node.children.clone();
+            self.touch(node_id);
             self.nodes.insert(node_id, node);
             for &child in &children {
-                self.recursive_restore(child)?;
+                self.recursive_restore(child, nodes)?;
             }
             Ok(())
         } else {
@@ -922,16 +2452,44 @@ impl Screen {
     }
 
     pub fn run(&mut self) {
+        self.color_enabled = color_enabled();
         self.start_raw_mode();
         self.dims = terminal_size().unwrap();
+        if self.config.startup_mode == Mode::Insert && self.selected.is_none() {
+            // an empty free node is cleaned up again if nothing is typed
+            self.create_free_node();
+        }
         self.draw();
-        let stdin = stdin();
-        for (num_events, c) in stdin.events().enumerate() {
-            let evt = c.unwrap();
+        self.last_save_secs = now().as_secs();
+        let (events, handled) = spawn_event_reader();
+        let mut num_events = 0;
+        loop {
+            let evt = match events.recv_timeout(Duration::from_millis(TICK_MS)) {
+                Ok(evt) => Some(evt.unwrap()),
+                // a dangling chord prefix is handled as if it were an event
+                Err(RecvTimeoutError::Timeout)
+                    if self.chord_timed_out(now().as_millis() as u64) =>
+                {
+                    None
+                },
+                Err(RecvTimeoutError::Timeout) => {
+                    if self.tick() {
+                        self.draw();
+                    }
+                    continue;
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
 
             self.dims = terminal_size().unwrap();
 
-            let should_break = !self.handle_event(evt);
+            let read_event = evt.is_some();
+            let should_break = !match evt {
+                Some(evt) => self.handle_event(evt),
+                None => self.flush_chord(),
+            };
+
+            self.tick();
 
             self.draw();
 
@@ -940,21 +2498,30 @@ impl Screen {
                 self.draw();
             }
 
-            // if selected not visible, try to make it visible
+            // keep the selection in view
             self.scroll_to_selected();
 
-            // auto-save every 25 events to avoid larger data loss
-            if num_events > 0 && num_events % self.autosave_every == 0 {
+            // auto-save changes every 25 events, and once autosave_secs
+            // have passed, to avoid larger data loss
+            let every_events = num_events > 0 && num_events % self.autosave_every == 0;
+            let discarding = self.quitting == Some(true);
+            if self.dirty && !discarding && (every_events || self.autosave_due(now().as_secs())) {
                 self.save();
             }
+            num_events += 1;
 
             if should_break {
                 self.cleanup();
-                self.save();
+                if !discarding {
+                    self.save();
+                }
                 break;
             }
+            if read_event {
+                let _ = handled.send(());
+            }
         }
-        trace!("leaving stdin.events() loop");
+        trace!("leaving event loop");
         print!("{}{}", cursor::Goto(1, 1), clear::All);
     }
 
@@ -965,10 +2532,117 @@ impl Screen {
         }
     }
 
+    // Only nodes below the drawing root are touched. Collapsing only marks
+    // nodes with children, and a selection that ends up hidden moves to the
+    // collapsed node hiding it.
+    fn set_all_collapsed(&mut self, collapsed: bool) {
+        trace!("set_all_collapsed({})", collapsed);
+        let mut to_visit = self
+            .with_node(self.drawing_root, |n| n.children.clone())
+            .unwrap_or_default();
+        while let Some(node_id) = to_visit.pop() {
+            if let Some(mut children) = self.with_node_mut_no_meta(node_id, |n| {
+                n.collapsed = collapsed && !n.children.is_empty();
+                n.children.clone()
+            }) {
+                to_visit.append(&mut children);
+            }
+        }
+
+        if let Some(selected_id) = self.selected {
+            let hidden_by = self.lineage_in_view(selected_id).into_iter().find(|&id| {
+                id != selected_id && self.with_node(id, |n| n.collapsed).unwrap_or(false)
+            });
+            if let Some(ancestor) = hidden_by {
+                self.select_node(ancestor);
+            }
+        }
+    }
+
+    // Collapses the siblings of the selected node and their subtrees, and
+    // expands the path down to it. Calling it again puts back the collapsed
+    // flags it changed.
+    fn focus_branch(&mut self) {
+        trace!("focus_branch()");
+        if let Some(unfocused) = self.unfocused.take() {
+            for (node_id, collapsed) in unfocused {
+                self.with_node_mut_no_meta(node_id, |n| n.collapsed = collapsed);
+            }
+            return;
+        }
+        let selected_id = if let Some(selected_id) = self.selected {
+            selected_id
+        } else {
+            return;
+        };
+
+        let mut changes: Vec<_> = self
+            .lineage_in_view(selected_id)
+            .into_iter()
+            .map(|id| (id, false))
+            .collect();
+        let siblings = self
+            .parent(selected_id)
+            .and_then(|p| self.with_node(p, |n| n.children.clone()))
+            .unwrap_or_default();
+        let mut to_visit: Vec<_> = siblings
+            .into_iter()
+            .filter(|&id| id != selected_id)
+            .collect();
+        while let Some(node_id) = to_visit.pop() {
+            if let Some(mut children) = self.with_node(node_id, |n| n.children.clone()) {
+                changes.push((node_id, !children.is_empty()));
+                to_visit.append(&mut children);
+            }
+        }
+
+        let mut unfocused = vec![];
+        for (node_id, collapsed) in changes {
+            if let Some(was) = self.with_node_mut_no_meta(node_id, |n| {
+                let was = n.collapsed;
+                n.collapsed = collapsed;
+                was
+            }) {
+                unfocused.push((node_id, was));
+            }
+        }
+        self.unfocused = Some(unfocused);
+    }
+
     fn toggle_show_logs(&mut self) {
         self.show_logs = !self.show_logs;
     }
 
+    fn toggle_minimap(&mut self) {
+        self.show_minimap = !self.show_minimap;
+    }
+
+    // positive scrolls back to older lines, only while the pane is shown
+    fn scroll_logs(&mut self, by: isize) {
+        if !self.show_logs {
+            return;
+        }
+        let count = logging::read_logs().visible_logs(self.log_level).count();
+        let max_scroll = count.saturating_sub(LOG_PANE_LINES);
+        self.log_scroll = if by < 0 {
+            self.log_scroll.saturating_sub(by.unsigned_abs())
+        } else {
+            min(self.log_scroll + by as usize, max_scroll)
+        };
+    }
+
+    // switches the log pane between everything and only warn+
+    fn toggle_log_level(&mut self) {
+        if !self.show_logs {
+            return;
+        }
+        self.log_level = match self.log_level {
+            Level::Warn => Level::Trace,
+            _ => Level::Warn,
+        };
+        self.log_scroll = 0;
+    }
+
     fn create_child(&mut self) {
         if let Some(mut selected_id) = self.selected {
             if self
@@ -1106,30 +2780,112 @@ impl Screen {
 
     fn backspace(&mut self) {
         trace!("backspace");
+        if let Some((selected_id, pos)) = self.cursor_pos() {
+            if pos == 0 {
+                return;
+            }
+            if self
+                .with_node_mut(selected_id, |node| {
+                    // the whole grapheme goes, so an emoji doesn't leave parts
+                    let from = byte_offset(&node.content, pos - 1);
+                    let to = byte_offset(&node.content, pos);
+                    node.content.replace_range(from..to, "");
+                })
+                .is_some()
+            {
+                self.cursor = Some((selected_id, pos - 1));
+                self.width_cache.remove(&selected_id);
+                self.reindex_tags(selected_id);
+            }
+        }
+    }
+
+    // Clears the selected node's text so typing replaces it.
+    fn rename_node(&mut self) {
+        trace!("rename_node()");
         if let Some(selected_id) = self.selected {
-            if let Some(content) = self.with_node_mut(selected_id, |node| {
-                let content = node.content.clone();
-                let chars = content.chars();
-                let oldlen = chars.clone().count();
-                let truncated: String = chars.take(max(oldlen, 1) - 1).collect();
-                node.content = truncated;
-                node.content.clone()
+            let old_content = self.with_node(selected_id, |n| n.content.clone()).unwrap();
+            if old_content.is_empty() {
+                return;
+            }
+            self.with_node_mut(selected_id, |n| n.content.clear());
+            self.width_cache.remove(&selected_id);
+            self.reindex_tags(selected_id);
+            self.cursor = None;
+            self.renaming = Some((selected_id, old_content));
+        }
+    }
+
+    // Erases back from the cursor over any whitespace and then the word
+    // before it.
+    fn erase_word(&mut self) {
+        trace!("erase_word()");
+        if let Some((selected_id, pos)) = self.cursor_pos() {
+            if pos == 0 {
+                return;
+            }
+            if let Some(start) = self.with_node_mut(selected_id, |node| {
+                let blank = |g: &str| g.chars().all(char::is_whitespace);
+                let before: Vec<&str> = node.content.graphemes(true).take(pos).collect();
+                let mut start = pos;
+                while start > 0 && blank(before[start - 1]) {
+                    start -= 1;
+                }
+                while start > 0 && !blank(before[start - 1]) {
+                    start -= 1;
+                }
+                let from = byte_offset(&node.content, start);
+                let to = byte_offset(&node.content, pos);
+                node.content.replace_range(from..to, "");
+                start
             }) {
-                self.grapheme_cache.remove(&selected_id);
-                self.tag_db.reindex(selected_id, content);
+                self.cursor = Some((selected_id, start));
+                self.width_cache.remove(&selected_id);
+                self.reindex_tags(selected_id);
             }
         }
     }
 
+    // The selected node and the grapheme index in its text that typing goes
+    // to.
+    fn cursor_pos(&self) -> Option<(NodeID, usize)> {
+        let selected_id = self.selected?;
+        let len = self.with_node(selected_id, |n| grapheme_count(&n.content))?;
+        let pos = match self.cursor {
+            Some((node_id, pos)) if node_id == selected_id => min(pos, len),
+            _ => len,
+        };
+        Some((selected_id, pos))
+    }
+
+    // `to` gets the current position and the text length, and the result
+    // is clamped to the text
+    fn move_cursor<F>(&mut self, to: F)
+    where
+        F: FnOnce(usize, usize) -> usize,
+    {
+        if let Some((selected_id, pos)) = self.cursor_pos() {
+            let len = self
+                .with_node(selected_id, |n| grapheme_count(&n.content))
+                .unwrap();
+            self.cursor = Some((selected_id, min(to(pos, len), len)));
+            self.width_cache.remove(&selected_id);
+        }
+    }
+
     fn append(&mut self, c: char) {
         trace!("append({})", c);
-        if let Some(selected_id) = self.selected {
-            if let Some(content) = self.with_node_mut(selected_id, |node| {
-                node.content.push(c);
-                node.content.clone()
+        if let Some((selected_id, pos)) = self.cursor_pos() {
+            if let Some(new_pos) = self.with_node_mut(selected_id, |node| {
+                let at = byte_offset(&node.content, pos);
+                node.content.insert(at, c);
+                // a joiner or modifier extends the grapheme before it rather
+                // than starting one
+                grapheme_count(&node.content[..at + c.len_utf8()])
             }) {
-                self.grapheme_cache.remove(&selected_id);
-                self.tag_db.reindex(selected_id, content);
+                self.cursor = Some((selected_id, new_pos));
+                self.width_cache.remove(&selected_id);
+                self.reindex_tags(selected_id);
             }
         }
     }
@@ -1156,6 +2912,17 @@ impl Screen {
         lineage
     }
 
+    // the part of node_id's lineage below the drawing root, or nothing if
+    // node_id isn't under it
+    fn lineage_in_view(&self, node_id: NodeID) -> Vec<NodeID> {
+        let mut lineage = self.lineage(node_id);
+        if let Some(idx) = lineage.iter().position(|&id| id == self.drawing_root) {
+            lineage.split_off(idx + 1)
+        } else {
+            vec![]
+        }
+    }
+
     // returns true if a is a parent of b
     fn is_parent(&self, a: NodeID, b: NodeID) -> bool {
         trace!("is_parent({}, {})", a, b);
@@ -1264,11 +3031,12 @@ impl Screen {
 
     fn pop_focus(&mut self) {
         // bust grapheme cache on new view
-        self.grapheme_cache.clear();
+        self.width_cache.clear();
         self.unselect();
         let (root, selected, view_y) = self.focus_stack.pop().unwrap_or((0, 0, 0));
         self.drawing_root = root;
         self.view_y = view_y;
+        self.view_x = 0;
         self.select_node(selected);
     }
 
@@ -1293,6 +3061,51 @@ impl Screen {
         self.select_neighbor(SearchDirection::Backward);
     }
 
+    fn select_first_child(&mut self) {
+        self.select_child(|children| children.first().cloned());
+    }
+
+    fn select_last_child(&mut self) {
+        self.select_child(|children| children.last().cloned());
+    }
+
+    // Children of a collapsed node aren't drawn, so they can't be selected.
+    fn select_child<F>(&mut self, pick: F)
+    where
+        F: Fn(&[NodeID]) -> Option<NodeID>,
+    {
+        if let Some(selected_id) = self.selected {
+            let child = self
+                .with_node(selected_id, |n| {
+                    if n.collapsed {
+                        None
+                    } else {
+                        pick(&n.children)
+                    }
+                })
+                .and_then(|c| c);
+            if let Some(child_id) = child {
+                self.select_node(child_id);
+            }
+        }
+    }
+
+    // The drawing root itself is never drawn, so the root of what's on
+    // screen is the anchor below it. After a drill-down that's the top of
+    // the drilled-into subtree rather than of the whole tree. With nothing
+    // selected the first anchor in view is picked.
+    fn select_root(&mut self) {
+        let root = if let Some(selected_id) = self.selected {
+            self.lineage_in_view(selected_id).first().cloned()
+        } else {
+            self.with_node(self.drawing_root, |n| n.children.first().cloned())
+                .and_then(|c| c)
+        };
+        if let Some(root_id) = root {
+            self.select_node(root_id);
+        }
+    }
+
     fn select_neighbor(&mut self, dir: SearchDirection) -> Option<NodeID> {
         use SearchDirection::*;
         let selected_id = self.selected?;
@@ -1326,13 +3139,14 @@ impl Screen {
     fn drill_down(&mut self) {
         trace!("drill_down()");
         // bust grapheme cache on new view
-        self.grapheme_cache.clear();
+        self.width_cache.clear();
         if let Some(selected_id) = self.unselect() {
             if selected_id != self.drawing_root {
                 let breadcrumb = (self.drawing_root, selected_id, self.view_y);
                 self.focus_stack.push(breadcrumb);
                 self.drawing_root = selected_id;
                 self.view_y = 0;
+                self.view_x = 0;
             }
         }
     }
@@ -1357,25 +3171,86 @@ impl Screen {
         }
     }
 
-    fn scroll_to_selected(&mut self) -> bool {
-        if let Some(selected_id) = self.selected {
-            self.scroll_to_node(selected_id)
-        } else {
-            false
+    fn scroll_left(&mut self) {
+        self.view_x = max(self.view_x, self.dims.0 / 2) - self.dims.0 / 2;
+        self.unselect();
+    }
+
+    // stops once the rightmost drawn column reaches the right edge
+    fn scroll_right(&mut self) {
+        let furthest = self.rightmost_drawn().saturating_sub(self.dims.0);
+        if furthest > self.view_x {
+            self.view_x = min(self.view_x + self.dims.0 / 2, furthest);
+            self.unselect();
         }
     }
 
-    fn scroll_to_node(&mut self, node_id: NodeID) -> bool {
-        if let Some(visible) = self.node_is_visible(node_id) {
-            let &(_, y) = self.drawn_at(node_id).unwrap();
-            if !visible {
-                // move only if necessary
-                self.view_y = max(y - 1, self.dims.1 / 2) - self.dims.1 / 2;
+    // the last column of the widest tree, drawn in view or not
+    fn rightmost_drawn(&mut self) -> u16 {
+        let anchors = self
+            .with_node(self.drawing_root, |n| n.children.clone())
+            .unwrap();
+        anchors
+            .into_iter()
+            .filter_map(|id| {
+                let &(x, _) = self.drawn_at(id)?;
+                let (width, _) = self.drawable_subtree_dims(id)?;
+                Some(x + width - 1)
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    // scrolls the selected node to the middle of the view, as far as the
+    // edges of what is drawn allow
+    fn center_selection(&mut self) {
+        let (id, (x, y)) = match self.selected {
+            Some(id) => match self.drawn_at(id) {
+                Some(&coords) => (id, coords),
+                None => return,
+            }
+            None => return,
+        };
+        let width = self.width_cache.get(&id).cloned().unwrap_or(0) as u16;
+        let furthest_x = self.rightmost_drawn().saturating_sub(self.dims.0);
+        let furthest_y = self.lowest_drawn.saturating_sub(self.dims.1);
+        self.view_x = min((x + width / 2).saturating_sub(self.dims.0 / 2), furthest_x);
+        self.view_y = min(y.saturating_sub(self.dims.1 / 2), furthest_y);
+    }
+
+    fn scroll_to_selected(&mut self) {
+        if let Some(selected_id) = self.selected {
+            // a node drawn in view for the first time has only then measured
+            // its width, which can call for scrolling a little further
+            for _ in 0..2 {
+                if !self.ensure_visible(selected_id) {
+                    break;
+                }
                 self.draw();
-                return true;
             }
         }
-        false
+    }
+
+    /// Scrolls as little as possible for all of a drawn node to be in view,
+    /// with the `scrolloff` setting's rows of context above and below it.
+    /// Returns whether the view moved.
+    fn ensure_visible(&mut self, node_id: NodeID) -> bool {
+        let (x, y) = match self.drawn_at(node_id) {
+            Some(&coords) => coords,
+            None => return false,
+        };
+        let node_width = self.config.settings.node_width;
+        let height = self
+            .with_node(node_id, |n| wrap_text(&n.content, node_width).len())
+            .unwrap_or(1) as u16;
+        let width = self.width_cache.get(&node_id).cloned().unwrap_or(1) as u16;
+        // the bottom row holds the status line
+        let rows = self.dims.1.saturating_sub(1);
+        let (view_x, view_y) = (self.view_x, self.view_y);
+        self.view_x = scroll_into_view(view_x, self.dims.0, x, width, 0);
+        let scrolloff = self.config.settings.scrolloff;
+        self.view_y = scroll_into_view(view_y, rows, y, height, scrolloff);
+        (view_x, view_y) != (self.view_x, self.view_y)
     }
 
     fn zoom_select(&mut self, node_id: NodeID) {
@@ -1452,6 +3327,73 @@ impl Screen {
         }
     }
 
+    // Makes the selected node the last child of its previous sibling. The
+    // first child has no previous sibling, and anchors are left alone like
+    // they are when raising and lowering, so both are no-ops.
+    fn demote_selected(&mut self) {
+        if let Some(selected_id) = self.selected {
+            if !self.exists(selected_id) {
+                warn!("tried to demote deleted node");
+                return;
+            }
+            let parent_id = self.parent(selected_id).unwrap();
+            if parent_id == self.drawing_root {
+                // principle: don't modify things that are above the visible scope
+                return;
+            }
+            let prev = self
+                .with_node(parent_id, |parent| {
+                    let idx = parent
+                        .children
+                        .iter()
+                        .position(|&e| e == selected_id)
+                        .unwrap();
+                    if idx > 0 {
+                        Some(parent.children[idx - 1])
+                    } else {
+                        None
+                    }
+                })
+                .unwrap();
+            if let Some(prev_id) = prev {
+                self.reparent(selected_id, prev_id);
+            }
+        }
+    }
+
+    // Makes the selected node the sibling after its parent. Anchors have no
+    // parent on screen to be promoted past, so promoting one is a no-op. A
+    // node promoted to an anchor stays where it was drawn.
+    fn promote_selected(&mut self) {
+        if let Some(selected_id) = self.selected {
+            if !self.exists(selected_id) {
+                warn!("tried to promote deleted node");
+                return;
+            }
+            let parent_id = self.parent(selected_id).unwrap();
+            if parent_id == self.drawing_root {
+                return;
+            }
+            let grandparent_id = self.parent(parent_id).unwrap();
+            if grandparent_id == self.drawing_root {
+                let coords = self
+                    .drawn_at(selected_id)
+                    .cloned()
+                    .or_else(|| self.with_node(parent_id, |p| p.rooted_coords))
+                    .unwrap();
+                self.with_node_mut_no_meta(selected_id, |s| s.rooted_coords = coords);
+            }
+
+            self.with_node_mut_no_meta(parent_id, |p| p.children.retain(|c| c != &selected_id));
+            self.with_node_mut_no_meta(grandparent_id, |gp| {
+                let idx = gp.children.iter().position(|&e| e == parent_id).unwrap();
+                gp.children.insert(idx + 1, selected_id);
+            });
+            self.with_node_mut_no_meta(selected_id, |s| s.parent_id = grandparent_id);
+            self.width_cache.remove(&selected_id);
+        }
+    }
+
     fn select_up(&mut self) {
         let view_y = self.view_y;
         let height = self.dims.1;
@@ -1559,13 +3501,13 @@ impl Screen {
 
     fn click_screen(&mut self, coords: Coords) {
         trace!("click_screen({:?})", coords);
-        if coords.0 > self.dims.0 || coords.1 > self.view_y + self.dims.1 {
+        if coords.0 > self.view_x + self.dims.0 || coords.1 > self.view_y + self.dims.1 {
             warn!("click way off-screen");
             return;
         }
         let old = self.selected;
         let new = self.try_select(coords);
-        if old.is_none() && self.dragging_from.is_none() {
+        if old.is_none() && self.dragging_from.is_none() && !self.readonly {
             self.create_anchor(coords);
         }
 
@@ -1586,15 +3528,31 @@ impl Screen {
         }
     }
 
+    // holding the button after a press updates where the drag would land
+    fn drag(&mut self, to: Coords) {
+        trace!("drag({:?})", to);
+        if to.0 > self.view_x + self.dims.0 || to.1 > self.view_y + self.dims.1 {
+            warn!("drag way off-screen");
+            return;
+        }
+        if self.dragging_from.is_some() {
+            self.dragging_to = Some(to);
+        }
+    }
+
     fn release(&mut self, to: Coords) {
         trace!("release({:?})", to);
-        if to.0 > self.dims.0 || to.1 > self.view_y + self.dims.1 {
+        if to.0 > self.view_x + self.dims.0 || to.1 > self.view_y + self.dims.1 {
             warn!("release way off-screen");
             return;
         }
         if let Some(from) = self.dragging_from.take() {
             self.dragging_to.take();
-            self.move_selected(from, to);
+            if self.readonly {
+                self.message = Some("read only, moving is off".to_owned());
+            } else {
+                self.move_selected(from, to);
+            }
         }
         trace!("leaving release");
     }
@@ -1631,24 +3589,33 @@ impl Screen {
         }
     }
 
-    pub fn save(&self) {
+    pub fn save(&mut self) {
         trace!("save()");
+        if self.readonly {
+            self.message = Some("read only, save is off".to_owned());
+            return;
+        }
         self.assert_node_consistency();
         let data = serialization::serialize_screen(self);
-        if let Some(ref path) = self.work_path {
-            let mut tmp_path = path.clone();
-            tmp_path.push_str(".tmp");
-            if remove_file(&tmp_path).is_ok() {
-                warn!("removed stale tmp file");
+        if let Some(path) = self.work_path.clone() {
+            match write_with_backup(&path, &data) {
+                Ok(()) => {
+                    info!("saved work to {}", path);
+                    self.dirty = false;
+                    self.last_save_secs = now().as_secs();
+                }
+                Err(e) => error!("failed to save work to {}: {}", path, e),
             }
-            let mut f = File::create(&tmp_path).unwrap();
-            f.write_all(&*data).unwrap();
-            f.sync_all().unwrap();
-            rename(tmp_path, path).unwrap();
-            info!("saved work to {}", path);
         }
     }
 
+    // The input loop blocks, so this is checked after each event rather
+    // than on a timer of its own.
+    fn autosave_due(&self, now_secs: u64) -> bool {
+        let interval = self.config.settings.autosave_secs;
+        interval != 0 && now_secs.saturating_sub(self.last_save_secs) >= interval
+    }
+
     pub fn cleanup(&mut self) {
         trace!("cleanup()");
         print!("{}", cursor::Show);
@@ -1667,6 +3634,59 @@ impl Screen {
         self.lookup.contains_key(&coords)
     }
 
+    fn start_arrow(&mut self) {
+        self.drawing_arrow = self.selected;
+    }
+
+    // Links the node picked by start_arrow to the selection. Links from a
+    // node to itself and links that already exist are refused.
+    fn end_arrow(&mut self) {
+        let from = if let Some(from) = self.drawing_arrow.take() {
+            from
+        } else {
+            return;
+        };
+        if let Some(to) = self.selected {
+            if from == to {
+                warn!("refusing to link node {} to itself", from);
+            } else if !self.exists(from) || !self.exists(to) {
+                warn!("tried to link a deleted node");
+            } else if !self.arrows.contains(&(from, to)) {
+                self.touch_arrows();
+                self.arrows.push((from, to));
+            }
+        }
+    }
+
+    // Removes the arrow from the node picked by start_arrow to the selection.
+    // Without a start, the selection's outgoing arrow is removed, picking the
+    // one whose target is drawn nearest if there are several.
+    fn remove_arrow(&mut self) {
+        let selected_id = if let Some(selected_id) = self.selected {
+            selected_id
+        } else {
+            return;
+        };
+        let to_remove = match self.drawing_arrow.take() {
+            Some(from) if from != selected_id => Some((from, selected_id)),
+            _ => {
+                let origin = self.drawn_at.get(&selected_id).cloned();
+                self.arrows
+                    .iter()
+                    .filter(|&&(from, _)| from == selected_id)
+                    .min_by_key(|&&(_, to)| match (origin, self.drawn_at.get(&to)) {
+                        (Some(origin), Some(&coords)) => cost(origin, coords),
+                        _ => u16::MAX,
+                    })
+                    .cloned()
+            }
+        };
+        if let Some(arrow) = to_remove {
+            self.touch_arrows();
+            self.arrows.retain(|e| e != &arrow);
+        }
+    }
+
     pub fn add_or_remove_arrow(&mut self) {
         if self.drawing_arrow.is_none() {
             self.drawing_arrow = self.selected;
@@ -1683,6 +3703,7 @@ impl Screen {
                         acc
                     }
                 });
+                self.touch_arrows();
                 if contains {
                     self.arrows.retain(|e| e != &arrow);
                 } else {
@@ -1731,7 +3752,7 @@ impl Screen {
         self.lookup.clear();
         self.drawn_at.clear();
         self.lowest_drawn = 0;
-        print!("{}", clear::All);
+        print!("{}{}", self.reset_style(), clear::All);
 
         // print visible nodes
         self.draw_children_of_root();
@@ -1744,21 +3765,32 @@ impl Screen {
 
         // print logs
         if self.show_logs && self.dims.0 > 4 && self.dims.1 > 7 {
+            let mut title = "logs".to_owned();
+            if self.log_level == Level::Warn {
+                title.push_str(" warn+");
+            }
+            if self.log_scroll > 0 {
+                write!(title, " -{}", self.log_scroll).unwrap();
+            }
             let mut sep = format!(
-                "{}{}logs{}",
+                "{}{}{}{}",
                 cursor::Goto(0, self.dims.1 - 6),
                 style::Invert,
-                style::Reset
+                title,
+                self.reset_style()
             );
-            for _ in 0..self.dims.0 - 4 {
+            for _ in title.len()..self.dims.0 as usize {
                 sep.push('█');
             }
             println!("{}", sep);
             {
                 let logs = logging::read_logs();
-                for msg in logs.iter().rev() {
-                    let line_width = min(msg.len(), self.dims.0 as usize);
-                    println!("\r{}", msg[..line_width as usize].to_owned());
+                let lines: Vec<_> = logs.visible_logs(self.log_level).collect();
+                let end = lines.len().saturating_sub(self.log_scroll);
+                let start = end.saturating_sub(LOG_PANE_LINES);
+                for line in &lines[start..end] {
+                    let msg: String = line.text.chars().take(self.dims.0 as usize).collect();
+                    println!("\r{}", msg);
                 }
             }
         }
@@ -1795,14 +3827,179 @@ impl Screen {
             self.draw_scrollbar();
         }
 
-        print!("{}", cursor::Hide);
-        self.flush();
+        if self.show_minimap {
+            self.draw_minimap();
+        }
+
+        self.draw_chord_popup();
+
+        if let Some(ref help) = self.help {
+            self.draw_help(help);
+        }
+
+        if let Some(ref cmd) = self.command {
+            print!(
+                "{}{}{}:{}{}",
+                cursor::Goto(1, self.dims.1),
+                style::Invert,
+                clear::CurrentLine,
+                cmd,
+                self.reset_style()
+            );
+        } else if let Some(ref search) = self.search {
+            if search.typing {
+                let sigil = match search.direction {
+                    SearchDirection::Forward => '/',
+                    SearchDirection::Backward => '?',
+                };
+                print!(
+                    "{}{}{}{}{}{}{}",
+                    cursor::Goto(1, self.dims.1),
+                    style::Invert,
+                    clear::CurrentLine,
+                    sigil,
+                    search.query,
+                    search.status(),
+                    self.reset_style()
+                );
+            }
+        }
+        if self.command.is_none() && !self.typing_search() {
+            print!(
+                "{}{}{}{}",
+                cursor::Goto(1, self.dims.1),
+                style::Invert,
+                self.status_line(),
+                self.reset_style()
+            );
+        }
+
+        print!("{}", cursor::Hide);
+        self.flush();
 
         // let after = time::get_time();
 
         // debug!("draw time: {}", after - before);
     }
 
+    /// The bottom line shown when not typing a command or search: the
+    /// mode, how many nodes there are and a `*` when there are unsaved
+    /// changes.
+    pub fn status_line(&self) -> String {
+        // the root isn't a node anyone made
+        let count = self.nodes.len().saturating_sub(1);
+        let mut status = format!(
+            "{} {} node{}",
+            self.mode().name().to_uppercase(),
+            count,
+            if count == 1 { "" } else { "s" }
+        );
+        if self.dirty {
+            status.push_str(" *");
+        }
+        if self.readonly {
+            status.push_str("  read only");
+        }
+        if let Some(count) = self.count {
+            write!(status, "  {}", count).unwrap();
+        }
+        if !self.pending_chord.is_empty() {
+            write!(status, "  {}", chord_to_string(&self.pending_chord)).unwrap();
+        }
+        if let Some(ref tag) = self.tag_filter {
+            write!(status, "  #{}", tag).unwrap();
+        }
+        if let Some(ref message) = self.message {
+            write!(status, "  {}", message).unwrap();
+        }
+        status
+    }
+
+    /// Lines listing the keys that finish the pending chord and what each
+    /// does, like which-key.
+    fn chord_popup_lines(&self) -> Vec<String> {
+        if self.pending_chord.is_empty() {
+            return vec![];
+        }
+        let candidates = self
+            .config
+            .chord_candidates(self.mode(), &self.pending_chord);
+        let keys: Vec<_> = candidates
+            .iter()
+            .map(|&(key, _)| chord_to_string(&[key]))
+            .collect();
+        let width = keys.iter().map(|k| k.len()).max().unwrap_or(0);
+        keys.iter()
+            .zip(&candidates)
+            .map(|(key, (_, action))| {
+                format!(
+                    " {:width$}  {} ",
+                    key,
+                    action.name().unwrap_or("?"),
+                    width = width
+                )
+            })
+            .collect()
+    }
+
+    fn draw_chord_popup(&self) {
+        let lines = self.chord_popup_lines();
+        // keep the top row and the status line clear
+        let room = self.dims.1.saturating_sub(2) as usize;
+        if lines.is_empty() || room == 0 {
+            return;
+        }
+        let shown = &lines[..min(lines.len(), room)];
+        let top = self.dims.1 - shown.len() as u16;
+        for (row, line) in shown.iter().enumerate() {
+            let line: String = line.chars().take(self.dims.0 as usize).collect();
+            print!(
+                "{}{}{}{}",
+                cursor::Goto(1, top + row as u16),
+                style::Invert,
+                line,
+                self.reset_style()
+            );
+        }
+    }
+
+    // covers the map, between a title bar and the status line, scrolled
+    // no further than the last screenful
+    fn draw_help(&self, help: &Help) {
+        let lines = self.filter_help(&help.query);
+        let rows = self.dims.1.saturating_sub(2) as usize;
+        let start = min(help.scroll, lines.len().saturating_sub(rows));
+        let title = if help.query.is_empty() {
+            "help: type to filter, Up / Down / PgUp / PgDn scroll, Esc closes".to_owned()
+        } else {
+            format!("help: {}  (Esc clears)", help.query)
+        };
+        let title: String = title.chars().take(self.dims.0 as usize).collect();
+        print!(
+            "{}{}{}{}{}",
+            clear::All,
+            cursor::Goto(1, 1),
+            style::Invert,
+            title,
+            self.reset_style()
+        );
+        for (row, line) in lines[start..].iter().take(rows).enumerate() {
+            let line = take_width(line, self.dims.0 as usize);
+            print!("{}{}", cursor::Goto(1, 2 + row as u16), line);
+        }
+    }
+
+    // style::Reset also drops the theme's background, so it is put back
+    fn reset_style(&self) -> String {
+        let background = self
+            .config
+            .theme
+            .background
+            .filter(|_| self.color_enabled)
+            .map(Color::bg);
+        format!("{}{}", style::Reset, background.unwrap_or_default())
+    }
+
     fn draw_scrollbar(&self) {
         let bar_height = max(self.dims.1, 1) - 1;
         let normalized_lowest = f64::from(max(self.lowest_drawn, 1));
@@ -1822,8 +4019,108 @@ impl Screen {
         }
     }
 
+    // the furthest internal coordinates reached by a drawn node or the view
+    fn canvas_extent(&self) -> Coords {
+        let right = self
+            .drawn_at
+            .iter()
+            .map(|(id, &(x, _))| x + self.width_cache.get(id).cloned().unwrap_or(0) as u16)
+            .max()
+            .unwrap_or(0);
+        (
+            max(right, self.view_x + self.dims.0),
+            max(self.lowest_drawn, self.view_y + self.dims.1),
+        )
+    }
+
+    /// The cells of a `w` by `h` minimap, counted from its top left corner,
+    /// that hold a drawn node.
+    fn minimap_cells(&self, w: u16, h: u16) -> Vec<(u16, u16)> {
+        let (right, bottom) = self.canvas_extent();
+        let mut cells: Vec<_> = self
+            .drawn_at
+            .values()
+            .map(|&(x, y)| (minimap_scale(x, right, w), minimap_scale(y, bottom, h)))
+            .collect();
+        cells.sort();
+        cells.dedup();
+        cells
+    }
+
+    // the first and last cells of a `w` by `h` minimap covered by the view
+    fn minimap_view(&self, w: u16, h: u16) -> (Coords, Coords) {
+        let (right, bottom) = self.canvas_extent();
+        let first = (
+            minimap_scale(self.view_x + 1, right, w),
+            minimap_scale(self.view_y + 1, bottom, h),
+        );
+        let last = (
+            minimap_scale(self.view_x + self.dims.0, right, w),
+            minimap_scale(self.view_y + self.dims.1, bottom, h),
+        );
+        (first, last)
+    }
+
+    fn draw_minimap(&self) {
+        let (right, bottom) = self.canvas_extent();
+        let w = min(MINIMAP_WIDTH, self.dims.0 / 3);
+        let h = min(MINIMAP_HEIGHT, self.dims.1 / 3);
+        if right <= self.dims.0 && bottom <= self.dims.1 || w < 2 || h < 2 {
+            return;
+        }
+        let cells: HashSet<_> = self.minimap_cells(w, h).into_iter().collect();
+        let ((x0, y0), (x1, y1)) = self.minimap_view(w, h);
+        // below the header, and left of the scrollbar
+        let left = self.dims.0 - w - 2;
+        let rule = "─".repeat(w as usize);
+        print!("{}┌{}┐", cursor::Goto(left, 2), rule);
+        for y in 0..h {
+            let row: String = (0..w)
+                .map(|x| {
+                    if cells.contains(&(x, y)) {
+                        '▪'
+                    } else if (x0..=x1).contains(&x) && (y0..=y1).contains(&y) {
+                        '░'
+                    } else {
+                        ' '
+                    }
+                })
+                .collect();
+            print!("{}│{}│", cursor::Goto(left, 3 + y), row);
+        }
+        print!("{}└{}┘", cursor::Goto(left, 3 + h), rule);
+    }
+
+    // Where the layout puts the given top level trees, for those it moves
+    // away from their stored coordinates.
+    fn anchor_positions(&mut self, anchors: &[NodeID]) -> HashMap<NodeID, Coords> {
+        match self.layout {
+            LayoutKind::Free => HashMap::new(),
+            LayoutKind::Tree => {
+                let trees: Vec<_> = anchors
+                    .iter()
+                    .filter_map(|&id| self.drawable_subtree_dims(id).map(|dims| (id, dims)))
+                    .collect();
+                layout::tree_positions(&trees)
+            }
+            LayoutKind::Radial => {
+                let view_y = self.view_y;
+                layout::radial_positions(self.drawing_root, &self.nodes, self.dims)
+                    .into_iter()
+                    .map(|(id, (x, y))| (id, (x, y + view_y)))
+                    .collect()
+            }
+        }
+    }
+
+    fn toggle_layout(&mut self) {
+        self.layout = self.layout.next();
+        info!("switched to {:?} layout", self.layout);
+    }
+
     fn draw_children_of_root(&mut self) {
         trace!("draw_children_of_root()");
+        self.refresh_tag_filter();
         let anchors = self
             .with_node(self.drawing_root, |n| n.children.clone())
             .unwrap();
@@ -1832,8 +4129,16 @@ impl Screen {
             self.drawing_root,
             anchors
         );
+        let positions = self.anchor_positions(&anchors);
+        if self.layout == LayoutKind::Radial {
+            self.draw_radial(positions);
+            return;
+        }
         for child_id in anchors {
-            let child_coords = self.with_node(child_id, |n| n.rooted_coords).unwrap();
+            let child_coords = match positions.get(&child_id) {
+                Some(&coords) => coords,
+                None => self.with_node(child_id, |n| n.rooted_coords).unwrap(),
+            };
             let child_color = self.with_node(child_id, |n| n.color.clone()).unwrap();
             let hide_stricken = self
                 .with_node(self.drawing_root, |n| n.hide_stricken)
@@ -1849,6 +4154,23 @@ impl Screen {
         }
     }
 
+    // every node gets its own spot in the radial layout, so draw them one
+    // at a time rather than as trees
+    fn draw_radial(&mut self, positions: HashMap<NodeID, Coords>) {
+        let mut positions: Vec<_> = positions
+            .into_iter()
+            .filter(|&(id, _)| id != self.drawing_root)
+            .collect();
+        positions.sort();
+        let hide_stricken = self
+            .with_node(self.drawing_root, |n| n.hide_stricken)
+            .unwrap();
+        for (node_id, coords) in positions {
+            let color = self.with_node(node_id, |n| n.color.clone()).unwrap();
+            self.draw_node(node_id, "".to_owned(), coords, false, hide_stricken, color);
+        }
+    }
+
     // recursively draw node and children, returning how many have been drawn
     fn draw_node(
         &mut self,
@@ -1877,11 +4199,30 @@ impl Screen {
         } else {
             self.format_node(&raw_node)
         };
-        if node.stricken && hide_stricken {
+        if node.stricken && hide_stricken || self.hidden_by_tag_filter(node_id) {
             return 0;
         }
+        let content = match self.cursor_pos() {
+            Some((cursor_id, pos))
+                if cursor_id == node_id && pos < grapheme_count(&node.content) =>
+            {
+                let mut content = node.content.clone();
+                content.insert(byte_offset(&content, pos), CURSOR);
+                content
+            },
+            _ => node.content.clone(),
+        };
+        let lines = wrap_text(&content, self.config.settings.node_width);
+        let anchor = prefix.is_empty();
 
-        let reset = &*format!("{}", color::Fg(color::Reset));
+        let (color, reset) = if self.color_enabled {
+            (color, format!("{}", color::Fg(color::Reset)))
+        } else {
+            (String::new(), String::new())
+        };
+        let reset = &*reset;
+        let highlight = &*format!("{}", style::Underline);
+        let tint = &*self.node_tint(&node);
         let mut pre_meta = String::new();
         let mut buf = String::new();
 
@@ -1901,14 +4242,19 @@ impl Screen {
                     write!(&mut buf, "├─").unwrap();
                 }
             }
-            if node.stricken {
-                write!(&mut buf, "☠").unwrap();
+            if self.execs.contains_key(&node_id) {
+                let frame = (now().as_millis() / 100) as usize % SPINNER.len();
+                write!(&mut buf, "{}", SPINNER[frame]).unwrap();
+            } else if node.stricken {
+                write!(&mut buf, "{}", self.config.glyphs.completed).unwrap();
+            } else if node.in_progress {
+                write!(&mut buf, "{}", self.config.glyphs.in_progress).unwrap();
             } else if node.collapsed {
-                write!(&mut buf, "⊞").unwrap();
+                write!(&mut buf, "{}", self.config.glyphs.collapsed).unwrap();
             } else if node.hide_stricken {
-                write!(&mut buf, "⚔").unwrap();
+                write!(&mut buf, "{}", self.config.glyphs.hide_completed).unwrap();
             } else if node.free_text.is_some() {
-                write!(&mut buf, "✏").unwrap();
+                write!(&mut buf, "{}", self.config.glyphs.free_text).unwrap();
             } else {
                 write!(&mut buf, " ").unwrap();
             }
@@ -1917,44 +4263,43 @@ impl Screen {
                 write!(&mut buf, "{}", reset).unwrap();
             }
 
-            write!(&mut buf, "{}", node.content).unwrap();
+            if self.is_search_match(node_id) {
+                write!(&mut buf, "{}", highlight).unwrap();
+            }
+            write!(&mut buf, "{}{}", tint, lines[0]).unwrap();
 
             let max_width = (max(self.dims.0, 1 + x) - 1 - x) as usize;
-            let visible_graphemes =
-                self.grapheme_cache
-                    .get(&node.id)
-                    .cloned()
-                    .unwrap_or_else(|| {
-                        let visible = buf.replace(reset, "").replace(&*pre_meta, "");
-                        let vg = UnicodeSegmentation::graphemes(&*visible, true).count();
-                        self.grapheme_cache.insert(node.id, vg);
-                        vg
-                    });
-            if visible_graphemes > max_width {
-                let buf_clone = buf.clone();
-                let chars = buf_clone.chars();
-                let width = chars.clone().count();
-                let new_size = width - (visible_graphemes - max_width);
-                buf = chars.take(new_size).collect();
+            let visible_width = self.width_cache.get(&node.id).cloned().unwrap_or_else(|| {
+                let visible = buf
+                    .replace(reset, "")
+                    .replace(highlight, "")
+                    .replace(tint, "")
+                    .replace(&*pre_meta, "");
+                let width = display_width(&visible);
+                self.width_cache.insert(node.id, width);
+                width
+            });
+            if visible_width > max_width {
+                // the text comes last, so trim columns off the end
+                let mut excess = visible_width - max_width;
+                while excess > 0 {
+                    match buf.pop() {
+                        Some(c) => excess = excess.saturating_sub(c.width().unwrap_or(0)),
+                        None => break,
+                    }
+                }
                 buf.push('…');
             }
 
-            print!("{}{}", buf, style::Reset);
+            print!("{}{}", buf, self.reset_style());
         }
 
-        let visible_graphemes = self
-            .grapheme_cache
-            .get(&node.id)
-            .cloned()
-            .unwrap_or_else(|| {
-                let visible = buf.replace(reset, "").replace(&*pre_meta, "");
-                let vg = UnicodeSegmentation::graphemes(&*visible, true).count();
-                self.grapheme_cache.insert(node.id, vg);
-                vg
-            });
+        // a node out of view takes up no columns, and isn't cached as such
+        // so it measures up once it comes into view
+        let visible_width = self.width_cache.get(&node.id).cloned().unwrap_or(0);
 
         self.drawn_at.insert(node_id, internal_coords);
-        for x in (internal_coords.0..(internal_coords.0 + visible_graphemes as u16)).rev() {
+        for x in (internal_coords.0..(internal_coords.0 + visible_width as u16)).rev() {
             trace!("inserting {:?} at {:?}", node_id, internal_coords);
             self.lookup.insert((x, internal_coords.1), node_id);
         }
@@ -1972,8 +4317,35 @@ impl Screen {
         }
         let prefix = prefix;
 
-        let mut drawn = 1;
-        if !node.collapsed {
+        // wrapped lines sit under the first, keeping the tree lines going
+        for (i, line) in lines.iter().enumerate().skip(1) {
+            let coords = (internal_coords.0, internal_coords.1 + i as u16);
+            if let Some((x, y)) = self.internal_to_screen_xy(coords) {
+                let max_width = (max(self.dims.0, 1 + x) - 1 - x) as usize;
+                let shown_prefix: String = prefix.chars().take(max_width).collect();
+                let room = max_width - display_width(&shown_prefix);
+                let shown_line = take_width(line, room);
+                let mut buf = format!("{}{}{}", cursor::Goto(x, y), color, shown_prefix);
+                if !node.selected && !anchor {
+                    write!(&mut buf, "{}", reset).unwrap();
+                }
+                if node.selected {
+                    write!(&mut buf, "{}", style::Invert).unwrap();
+                }
+                if self.is_search_match(node_id) {
+                    write!(&mut buf, "{}", highlight).unwrap();
+                }
+                print!("{}{}{}{}", buf, tint, shown_line, self.reset_style());
+            }
+            let width = (display_width(&prefix) + display_width(line)) as u16;
+            for x in coords.0..coords.0 + width {
+                self.lookup.insert((x, coords.1), node_id);
+            }
+            self.lowest_drawn = max(self.lowest_drawn, coords.1);
+        }
+
+        let mut drawn = lines.len();
+        if !node.collapsed && self.layout != LayoutKind::Radial {
             let n_children = node.children.len();
             for (n, &child) in node.children.iter().enumerate() {
                 let last = n + 1 == n_children;
@@ -1992,13 +4364,44 @@ impl Screen {
         drawn
     }
 
+    // the color of a node's text for its due date or state
+    fn node_tint(&self, node: &Node) -> String {
+        if !self.color_enabled {
+            return String::new();
+        }
+        let theme = &self.config.theme;
+        let state_color = match theme.selected {
+            Some(selected) if node.selected => Some(selected),
+            _ if node.stricken => theme.completed,
+            _ if node.in_progress => theme.in_progress,
+            _ => None,
+        };
+        // due dates outrank the theme
+        match node.urgency(today()) {
+            Urgency::Overdue => format!("{}", color::Fg(color::LightRed)),
+            Urgency::DueSoon => format!("{}", color::Fg(color::LightYellow)),
+            Urgency::NotDue => state_color.map(Color::fg).unwrap_or_default(),
+        }
+    }
+
+    fn arrow_color(&self) -> String {
+        if !self.color_enabled {
+            return String::new();
+        }
+        self.config
+            .theme
+            .arrows
+            .map(Color::fg)
+            .unwrap_or_else(random_fg_color)
+    }
+
     fn draw_path(&self, internal_path: Vec<Coords>, start_dir: Dir, dest_dir: Dir) {
         let path: Vec<_> = internal_path
             .iter()
             .filter_map(|&c| self.internal_to_screen_xy(c))
             .collect();
         trace!("draw_path({:?}, {:?}, {:?})", path, start_dir, dest_dir);
-        print!("{}", random_fg_color());
+        print!("{}", self.arrow_color());
         if path.len() == 1 {
             print!("{} ↺", cursor::Goto(path[0].0, path[0].1))
         } else if path.len() > 1 {
@@ -2042,7 +4445,9 @@ impl Screen {
             };
             print!("{}{}", cursor::Goto(end_x, end_y), end_char);
         }
-        print!("{}", color::Fg(color::Reset));
+        if self.color_enabled {
+            print!("{}", color::Fg(color::Reset));
+        }
     }
 
     fn draw_header(&self) {
@@ -2060,15 +4465,15 @@ impl Screen {
 
         header_text.push_str(&*plot_line);
 
-        if self.dims.0 > header_text.len() as u16 && self.dims.1 > 1 {
+        if self.dims.0 > display_width(&header_text) as u16 && self.dims.1 > 1 {
             let mut sep = format!(
                 "{}{}{}{}",
                 cursor::Goto(0, 1),
                 style::Invert,
                 header_text,
-                style::Reset
+                self.reset_style()
             );
-            let text_len = header_text.chars().count();
+            let text_len = display_width(&header_text);
             for _ in 0..(max(self.dims.0 as usize, text_len) - text_len) {
                 sep.push('█');
             }
@@ -2330,9 +4735,31 @@ impl Screen {
                 _ => node.content,
             };
         }
+        if node.collapsed && !node.children.is_empty() {
+            let (done, total) = self.progress(node.id);
+            write!(node.content, " ({}/{})", done, total).unwrap();
+        }
         node
     }
 
+    // Counts completed and total leaves under node_id, so a parent's
+    // progress follows its tasks rather than how they are grouped.
+    fn progress(&self, node_id: NodeID) -> (usize, usize) {
+        let children = self.nodes.get(&node_id).map_or(&[][..], |n| &n.children);
+        children.iter().fold((0, 0), |(done, total), child_id| {
+            match self.nodes.get(child_id) {
+                Some(child) if child.children.is_empty() => {
+                    (done + child.stricken as usize, total + 1)
+                }
+                Some(_) => {
+                    let (child_done, child_total) = self.progress(*child_id);
+                    (done + child_done, total + child_total)
+                }
+                None => (done, total),
+            }
+        })
+    }
+
     fn plot(
         &self,
         queried_nodes: Vec<NodeID>,
@@ -2367,6 +4794,308 @@ impl Screen {
     }
 }
 
+fn same_ignoring_selection(a: Option<&Node>, b: Option<&Node>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) if a.selected == b.selected => a == b,
+        (Some(a), Some(b)) => {
+            let mut a = a.clone();
+            a.selected = b.selected;
+            &a == b
+        }
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+// glyphs cycled through on nodes whose command is running
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+// drawn where typing goes when that isn't the end of the text
+const CURSOR: char = '▏';
+
+// log lines shown below the pane's title bar
+const LOG_PANE_LINES: usize = 5;
+
+// the most cells the minimap shows across and down, inside its border
+const MINIMAP_WIDTH: u16 = 24;
+const MINIMAP_HEIGHT: u16 = 8;
+
+struct Exec {
+    child: process::Child,
+    // output being read from the child's pipes when capturing
+    stdout: Option<thread::JoinHandle<Vec<u8>>>,
+    stderr: Option<thread::JoinHandle<Vec<u8>>>,
+}
+
+// Escapes text for a quoted Graphviz string.
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+// Escapes text for an XML attribute, where a raw newline would read back
+// as a space.
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+        .replace('\n', "&#10;")
+}
+
+// Markdown allows six levels of heading, so bullets rank below them all.
+const HEADING_LEVELS: usize = 6;
+
+// How a markdown outline line ranks, with deeper lines ranking higher,
+// whether it has a checked box, and its text. Headings rank by their
+// number of #s and bullets by indentation, and anything else is None.
+fn outline_item(line: &str) -> Option<(usize, bool, &str)> {
+    let trimmed = line.trim_start();
+    let (rank, rest) = if trimmed.starts_with('#') {
+        let level = trimmed.chars().take_while(|&c| c == '#').count();
+        // #tag isn't a heading
+        (level, trimmed[level..].strip_prefix(' ')?)
+    } else if let Some(rest) = trimmed
+        .strip_prefix("- ")
+        .or_else(|| trimmed.strip_prefix("* "))
+    {
+        (HEADING_LEVELS + line.len() - trimmed.len(), rest)
+    } else {
+        return None;
+    };
+    let rest = rest.trim();
+    let checked = rest
+        .strip_prefix("[x]")
+        .or_else(|| rest.strip_prefix("[X]"));
+    let (checked, text) = match (checked, rest.strip_prefix("[ ]")) {
+        (Some(text), _) => (true, text),
+        (None, Some(text)) => (false, text),
+        (None, None) => (false, rest),
+    };
+    Some((rank, checked, text.trim_start()))
+}
+
+// The file a node's text names, with environment variables and a leading
+// ~ expanded.
+fn node_file_path(text: &str) -> String {
+    let path = expand_env(text.trim());
+    match path.strip_prefix("~/") {
+        Some(rest) => format!("{}/{}", env::var("HOME").unwrap_or_default(), rest),
+        None => path,
+    }
+}
+
+// The command line opening path in `editor`, the value of $EDITOR, which
+// can carry arguments of its own. Without one, vi is used.
+fn editor_command(editor: Option<&str>, path: &str) -> Vec<String> {
+    let mut cmd: Vec<String> = editor
+        .unwrap_or("")
+        .split_whitespace()
+        .map(str::to_owned)
+        .collect();
+    if cmd.is_empty() {
+        cmd.push("vi".to_owned());
+    }
+    cmd.push(path.to_owned());
+    cmd
+}
+
+// Reads terminal events on their own thread, so the event loop can wake up
+// without input. Each event waits for the one before it to be handled
+// before being read, which leaves stdin to programs like $EDITOR that an
+// event runs in the foreground.
+fn spawn_event_reader() -> (Receiver<io::Result<Event>>, SyncSender<()>) {
+    let (event_tx, event_rx) = mpsc::sync_channel(0);
+    let (handled_tx, handled_rx) = mpsc::sync_channel(1);
+    thread::spawn(move || {
+        for evt in stdin().events() {
+            if event_tx.send(evt).is_err() || handled_rx.recv().is_err() {
+                break;
+            }
+        }
+    });
+    (event_rx, handled_tx)
+}
+
+// Opens url with $BROWSER, or else the platform's opener.
+fn open_in_browser(url: &str) -> io::Result<process::Child> {
+    #[cfg(any(target_os = "macos",))]
+    let default_open_cmd = "open";
+    #[cfg(target_os = "linux")]
+    let default_open_cmd = "xdg-open";
+    #[cfg(target_os = "windows")]
+    let default_open_cmd = "start";
+
+    let browser = env::var("BROWSER").unwrap_or_else(|_| default_open_cmd.to_owned());
+    process::Command::new(browser).arg(url).spawn()
+}
+
+fn read_to_end_in_thread<R: Read + Send + 'static>(mut pipe: R) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = vec![];
+        if let Err(e) = pipe.read_to_end(&mut buf) {
+            error!("failed to read command output: {}", e);
+        }
+        buf
+    })
+}
+
+// A copy of a subtree that is no longer part of the tree, keyed by the ids
+// the nodes had when they were copied.
+struct Subtree {
+    node_id: NodeID,
+    nodes: HashMap<NodeID, Node>,
+}
+
+struct Deletion {
+    node_id: NodeID,
+    // where the subtree was deleted from
+    parent_id: NodeID,
+    idx: usize,
+    nodes: HashMap<NodeID, Node>,
+}
+
+// the open help overlay
+#[derive(Default)]
+struct Help {
+    // how far the lines are scrolled down
+    scroll: usize,
+    // what the listed bindings are filtered by
+    query: String,
+}
+
+struct Search {
+    query: String,
+    // matching nodes in tree order, recomputed as the query is typed
+    matches: Vec<NodeID>,
+    current: usize,
+    // true until the query is submitted
+    typing: bool,
+    direction: SearchDirection,
+    mode: SearchMode,
+    // set while a regex query fails to compile, which matches nothing
+    invalid_regex: bool,
+}
+
+impl Search {
+    // trailer for the search line, naming any non-default mode
+    fn status(&self) -> String {
+        let mut status = String::new();
+        if self.mode != SearchMode::Exact {
+            write!(status, "  [{}]", self.mode.name()).unwrap();
+        }
+        if self.invalid_regex {
+            status.push_str("  invalid regex");
+        }
+        status
+    }
+}
+
+/// How a search query is matched against node text.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum SearchMode {
+    /// Case-sensitive substring match.
+    #[default]
+    Exact,
+    /// Substring match ignoring case.
+    IgnoreCase,
+    /// The query is a regular expression.
+    Regex,
+}
+
+impl SearchMode {
+    fn next(self) -> SearchMode {
+        match self {
+            SearchMode::Exact => SearchMode::IgnoreCase,
+            SearchMode::IgnoreCase => SearchMode::Regex,
+            SearchMode::Regex => SearchMode::Exact,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            SearchMode::Exact => "exact",
+            SearchMode::IgnoreCase => "ignore case",
+            SearchMode::Regex => "regex",
+        }
+    }
+}
+
+// how often the event loop wakes up without input
+const TICK_MS: u64 = 100;
+
+// how long a typed chord prefix waits for the rest of the chord
+const CHORD_TIMEOUT_MS: u64 = 1000;
+
+// the most times a count typed before an action can repeat it
+const MAX_COUNT: usize = 9999;
+
+// squeezes a coordinate in 1..=extent onto a minimap row or column
+fn minimap_scale(v: u16, extent: u16, size: u16) -> u16 {
+    let scaled = u32::from(v.saturating_sub(1)) * u32::from(size) / u32::from(max(extent, 1));
+    min(scaled as u16, size.saturating_sub(1))
+}
+
+// The least scrolled view of `size` cells, starting from `view`, that shows
+// the `len` cells from 1-based `start` with `margin` cells to either side.
+// The margin shrinks to what fits, and a span too long to fit shows its
+// start.
+fn scroll_into_view(view: u16, size: u16, start: u16, len: u16, margin: u16) -> u16 {
+    let margin = min(margin, size.saturating_sub(len) / 2);
+    let end = start + len - 1 + margin;
+    let mut view = view;
+    if end > view + size {
+        view = end - size;
+    }
+    min(view, (start - 1).saturating_sub(margin))
+}
+
+/// Names of the commands `run_command` knows.
+const COMMANDS: [&str; 12] = [
+    "due", "export", "filter", "goto", "import", "mark", "set", "sort", "w", "q", "q!", "wq",
+];
+
+/// Returns the command names starting with `prefix`.
+pub fn complete_command(prefix: &str) -> Vec<String> {
+    COMMANDS
+        .iter()
+        .filter(|name| name.starts_with(prefix))
+        .map(|&name| name.to_owned())
+        .collect()
+}
+
+/// Tests node text against a search query.
+pub type SearchFilter = Box<dyn Fn(&str) -> bool>;
+
+/// Builds a predicate testing node text against `query`. A query that is
+/// not a valid regex matches nothing in `SearchMode::Regex`.
+pub fn search_filter(query: &str, mode: SearchMode) -> SearchFilter {
+    try_search_filter(query, mode).unwrap_or_else(|| Box::new(|_| false))
+}
+
+// None when query is not a valid regex in SearchMode::Regex.
+fn try_search_filter(query: &str, mode: SearchMode) -> Option<SearchFilter> {
+    match mode {
+        SearchMode::Exact => {
+            let query = query.to_owned();
+            Some(Box::new(move |text| text.contains(&*query)))
+        }
+        SearchMode::IgnoreCase => {
+            let query = query.to_lowercase();
+            Some(Box::new(move |text| text.to_lowercase().contains(&*query)))
+        }
+        SearchMode::Regex => match Regex::new(query) {
+            Ok(re) => Some(Box::new(move |text| re.is_match(text))),
+            Err(e) => {
+                debug!("invalid search regex {:?}: {}", query, e);
+                None
+            }
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 enum SearchDirection {
     Forward,
@@ -2378,6 +5107,2358 @@ enum PlotType {
     Done,
 }
 
+// Writes through a tmp file, moving the previous contents to `<path>.bak`
+// first. If the new file can't be put in place the backup is moved back,
+// so a failed save never loses both.
+fn write_with_backup(path: &str, data: &[u8]) -> io::Result<()> {
+    let tmp_path = format!("{}.tmp", path);
+    let bak_path = format!("{}.bak", path);
+    if remove_file(&tmp_path).is_ok() {
+        warn!("removed stale tmp file");
+    }
+    let mut f = File::create(&tmp_path)?;
+    f.write_all(data)?;
+    f.sync_all()?;
+
+    let backed_up = match rename(path, &bak_path) {
+        Ok(()) => true,
+        Err(ref e) if e.kind() == ErrorKind::NotFound => false,
+        Err(e) => return Err(e),
+    };
+    if let Err(e) = rename(&tmp_path, path) {
+        if backed_up {
+            rename(&bak_path, path)?;
+        }
+        return Err(e);
+    }
+    Ok(())
+}
+
+// Where the grapheme at `pos` starts in `text`, or its end past the last
+// grapheme.
+fn byte_offset(text: &str, pos: usize) -> usize {
+    text.grapheme_indices(true)
+        .nth(pos)
+        .map(|(at, _)| at)
+        .unwrap_or_else(|| text.len())
+}
+
+fn grapheme_count(text: &str) -> usize {
+    text.graphemes(true).count()
+}
+
 fn visible(view_y: u16, height: u16, y: u16) -> bool {
     y > view_y && y < view_y + height
 }
+
+#[test]
+fn test_command_mode() {
+    use termion::event::Key::{Backspace, Char, Ctrl, Esc};
+
+    let mut screen = Screen::default();
+    assert_eq!(screen.mode(), Mode::Normal);
+    screen.handle_event(Event::Key(Ctrl('e')));
+    assert_eq!(screen.mode(), Mode::Command);
+
+    for c in "gotx".chars() {
+        screen.handle_event(Event::Key(Char(c)));
+    }
+    screen.handle_event(Event::Key(Backspace));
+    assert_eq!(screen.command, Some("got".to_owned()));
+
+    // esc leaves command mode instead of quitting
+    assert!(screen.handle_event(Event::Key(Esc)));
+    assert_eq!(screen.mode(), Mode::Normal);
+    assert_eq!(screen.command, None);
+
+    screen.handle_event(Event::Key(Ctrl('e')));
+    screen.handle_event(Event::Key(Char('\n')));
+    assert_eq!(screen.mode(), Mode::Normal);
+}
+
+#[test]
+fn test_drag_updates_destination() {
+    use termion::event::MouseEvent;
+
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    screen.drag((10, 10));
+    assert_eq!(screen.dragging_to, None);
+
+    screen.dragging_from = Some((2, 3));
+    screen.dragging_to = Some((2, 3));
+    screen.handle_event(Event::Mouse(MouseEvent::Hold(10, 10)));
+    assert_eq!(screen.dragging_to, Some((10, 10)));
+
+    // off-screen holds are ignored
+    screen.handle_event(Event::Mouse(MouseEvent::Hold(200, 10)));
+    assert_eq!(screen.dragging_to, Some((10, 10)));
+}
+
+#[test]
+fn test_select_first_and_last_child() {
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    screen.create_anchor((1, 2));
+    let anchor = screen.selected.unwrap();
+    screen.append('a');
+    screen.select_first_child();
+    assert_eq!(screen.selected, Some(anchor));
+
+    screen.create_child();
+    let first = screen.selected.unwrap();
+    screen.append('b');
+    screen.create_sibling();
+    let last = screen.selected.unwrap();
+    screen.append('c');
+
+    screen.select_parent();
+    screen.select_first_child();
+    assert_eq!(screen.selected, Some(first));
+    screen.select_parent();
+    screen.select_last_child();
+    assert_eq!(screen.selected, Some(last));
+
+    screen.select_parent();
+    screen.toggle_collapsed();
+    screen.select_first_child();
+    assert_eq!(screen.selected, Some(anchor));
+}
+
+#[test]
+fn test_select_root() {
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    screen.create_anchor((1, 2));
+    let anchor = screen.selected.unwrap();
+    screen.append('a');
+    screen.create_child();
+    let child = screen.selected.unwrap();
+    screen.append('b');
+    screen.create_child();
+    screen.append('c');
+
+    screen.select_root();
+    assert_eq!(screen.selected, Some(anchor));
+    screen.unselect();
+    screen.select_root();
+    assert_eq!(screen.selected, Some(anchor));
+
+    // after drilling into the anchor, its child is the root in view
+    screen.select_node(anchor);
+    screen.drill_down();
+    screen.select_root();
+    assert_eq!(screen.selected, Some(child));
+    screen.select_first_child();
+    assert_ne!(screen.selected, Some(child));
+    screen.select_root();
+    assert_eq!(screen.selected, Some(child));
+}
+
+#[test]
+fn test_demote_and_promote() {
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    screen.create_anchor((1, 2));
+    let anchor = screen.selected.unwrap();
+    screen.append('a');
+    screen.create_child();
+    let first = screen.selected.unwrap();
+    screen.append('b');
+    screen.create_child();
+    let grandchild = screen.selected.unwrap();
+    screen.append('c');
+    screen.select_node(first);
+    screen.create_sibling();
+    let second = screen.selected.unwrap();
+    screen.append('d');
+
+    // the first child has no previous sibling to move under
+    screen.select_node(first);
+    screen.demote_selected();
+    assert_eq!(screen.parent(first), Some(anchor));
+
+    screen.select_node(second);
+    screen.demote_selected();
+    assert_eq!(screen.parent(second), Some(first));
+    let children = screen.with_node(first, |n| n.children.clone()).unwrap();
+    assert_eq!(children, vec![grandchild, second]);
+
+    screen.promote_selected();
+    assert_eq!(screen.parent(second), Some(anchor));
+    let children = screen.with_node(anchor, |n| n.children.clone()).unwrap();
+    assert_eq!(children, vec![first, second]);
+
+    // children come along, and anchors can't be promoted any further
+    screen.select_node(first);
+    screen.promote_selected();
+    assert_eq!(screen.parent(first), Some(0));
+    assert_eq!(screen.parent(grandchild), Some(first));
+    screen.promote_selected();
+    assert_eq!(screen.parent(first), Some(0));
+    screen.assert_node_consistency();
+}
+
+#[test]
+fn test_duplicate_subtree() {
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    screen.create_anchor((1, 2));
+    let anchor = screen.selected.unwrap();
+    screen.append('a');
+    screen.create_child();
+    let original = screen.selected.unwrap();
+    screen.append('b');
+    screen.create_child();
+    let grandchild = screen.selected.unwrap();
+    screen.append('c');
+    screen.toggle_stricken();
+    screen.select_node(original);
+    screen.toggle_collapsed();
+
+    screen.duplicate_selected();
+    let copy = screen.selected.unwrap();
+    assert_ne!(copy, original);
+    let siblings = screen.with_node(anchor, |n| n.children.clone()).unwrap();
+    assert_eq!(siblings, vec![original, copy]);
+
+    let (content, collapsed, children) = screen
+        .with_node(copy, |n| {
+            (n.content.clone(), n.collapsed, n.children.clone())
+        })
+        .unwrap();
+    assert_eq!(content, "b");
+    assert!(collapsed);
+    assert_eq!(children.len(), 1);
+    let grandchild_copy = children[0];
+    assert_ne!(grandchild_copy, grandchild);
+    assert_eq!(screen.parent(grandchild_copy), Some(copy));
+    assert_eq!(
+        screen.with_node(grandchild_copy, |n| n.stricken),
+        Some(true)
+    );
+
+    // the copy is independent of the original
+    screen.append('x');
+    assert_eq!(
+        screen.with_node(original, |n| n.content.clone()).unwrap(),
+        "b"
+    );
+    screen.assert_node_consistency();
+}
+
+#[test]
+fn test_collapse_and_expand_all() {
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    screen.create_anchor((1, 2));
+    let anchor = screen.selected.unwrap();
+    screen.append('a');
+    screen.create_child();
+    let child = screen.selected.unwrap();
+    screen.append('b');
+    screen.create_child();
+    let grandchild = screen.selected.unwrap();
+    screen.append('c');
+
+    let collapsed = |screen: &Screen, id| screen.with_node(id, |n| n.collapsed).unwrap();
+
+    // drilled into the anchor, only what's below it is collapsed
+    screen.select_node(anchor);
+    screen.drill_down();
+    screen.select_node(grandchild);
+    screen.set_all_collapsed(true);
+    assert!(!collapsed(&screen, anchor));
+    assert!(collapsed(&screen, child));
+    assert!(!collapsed(&screen, grandchild));
+    assert_eq!(screen.selected, Some(child));
+
+    screen.pop_focus();
+    screen.set_all_collapsed(true);
+    assert!(collapsed(&screen, anchor));
+    screen.set_all_collapsed(false);
+    assert!(!collapsed(&screen, anchor));
+    assert!(!collapsed(&screen, child));
+}
+
+#[test]
+fn test_focus_branch_toggle() {
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    screen.create_anchor((1, 2));
+    let anchor = screen.selected.unwrap();
+    screen.append('a');
+    screen.create_child();
+    let focused = screen.selected.unwrap();
+    screen.append('b');
+    screen.create_sibling();
+    let sibling = screen.selected.unwrap();
+    screen.append('c');
+    screen.create_child();
+    screen.append('d');
+    screen.create_child();
+    let nested = screen.selected.unwrap();
+    screen.append('e');
+    screen.create_child();
+    screen.append('f');
+
+    let collapsed = |screen: &Screen, id| screen.with_node(id, |n| n.collapsed).unwrap();
+    screen.select_node(anchor);
+    screen.toggle_collapsed();
+    screen.select_node(focused);
+
+    screen.focus_branch();
+    assert!(!collapsed(&screen, anchor));
+    assert!(collapsed(&screen, sibling));
+    assert!(collapsed(&screen, nested));
+
+    screen.focus_branch();
+    assert!(collapsed(&screen, anchor));
+    assert!(!collapsed(&screen, sibling));
+    assert!(!collapsed(&screen, nested));
+}
+
+#[test]
+fn test_export_outline() {
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    screen.create_anchor((1, 10));
+    screen.append('z');
+    screen.create_anchor((1, 2));
+    let anchor = screen.selected.unwrap();
+    screen.append('a');
+    screen.create_child();
+    let child = screen.selected.unwrap();
+    screen.append('b');
+    screen.create_child();
+    screen.append('c');
+    screen.toggle_stricken();
+    screen.select_node(child);
+    screen.create_sibling();
+    screen.append('d');
+    screen.unselect();
+
+    assert_eq!(
+        screen.export_outline(),
+        "* [ ] a\n  * [ ] b\n    * [x] c\n  * [ ] d\n* [ ] z\n"
+    );
+
+    screen.select_node(anchor);
+    screen.drill_down();
+    assert_eq!(screen.export_outline(), "* [ ] b\n  * [x] c\n* [ ] d\n");
+}
+
+#[test]
+fn test_export_command() {
+    let mut screen = Screen::default();
+    screen.import_markdown("* a\n  * b\n");
+    let path = env::temp_dir().join(format!("void_export.{}", std::process::id()));
+    let path = path.to_str().unwrap();
+    let exports = [
+        ("md", screen.export_outline()),
+        ("dot", screen.export_dot()),
+        ("opml", screen.export_opml()),
+    ];
+    for (format, expected) in &exports {
+        let cmd = format!("export {} {}", format, path);
+        screen.run_command(&cmd).unwrap();
+        assert_eq!(&std::fs::read_to_string(path).unwrap(), expected);
+    }
+    std::fs::remove_file(path).unwrap();
+
+    let err = screen.run_command("export md").unwrap_err();
+    assert_eq!(err, "usage: export md|dot|opml <path>");
+    assert!(screen.run_command("export pdf out.pdf").is_err());
+    assert!(screen.run_command("export md /no/such/dir/out.md").is_err());
+}
+
+#[test]
+fn test_import_markdown() {
+    let mut screen = Screen::default();
+    let text = "# Project\n\
+                intro text is skipped\n\
+                - [x] plan\n\
+                \x20 * [ ] design\n\
+                \x20   - build #later\n\
+                - ship\n\
+                ## Notes\n\
+                * one\n\
+                # Other\n";
+    let anchors = screen.import_markdown(text);
+    assert_eq!(anchors.len(), 2);
+    let content = |screen: &Screen, id| screen.nodes[&id].content.clone();
+    let children = |screen: &Screen, id| screen.nodes[&id].children.clone();
+    let (project, other) = (anchors[0], anchors[1]);
+    assert_eq!(content(&screen, project), "Project");
+    assert_eq!(content(&screen, other), "Other");
+    assert!(children(&screen, other).is_empty());
+
+    let top = children(&screen, project);
+    let top: Vec<_> = top.iter().map(|&id| content(&screen, id)).collect();
+    assert_eq!(top, vec!["plan", "ship", "Notes"]);
+    let plan = children(&screen, project)[0];
+    assert!(screen.nodes[&plan].stricken);
+    let design = children(&screen, plan)[0];
+    assert_eq!(content(&screen, design), "design");
+    assert!(!screen.nodes[&design].stricken);
+    let build = children(&screen, design)[0];
+    assert_eq!(content(&screen, build), "build #later");
+    assert_eq!(screen.nodes[&build].tags, vec!["later".to_owned()]);
+    let notes = children(&screen, project)[2];
+    assert_eq!(content(&screen, children(&screen, notes)[0]), "one");
+    assert!(screen.nodes[&project].rooted_coords.1 < screen.nodes[&other].rooted_coords.1);
+}
+
+#[test]
+fn test_import_command() {
+    let mut screen = Screen::default();
+    let path = env::temp_dir().join(format!("void_import.{}.md", std::process::id()));
+    std::fs::write(&path, "# a\n* b\n* c\n").unwrap();
+    let import = format!("import {}", path.display());
+    screen.command = Some(import.clone());
+    assert!(screen.run_action(Some(Action::SubmitCmd)));
+    let anchors = screen.nodes[&0].children.clone();
+    assert_eq!(anchors.len(), 1);
+    assert_eq!(screen.nodes[&anchors[0]].children.len(), 2);
+
+    // the whole import is one step to undo
+    screen.run_action(Some(Action::Undo));
+    assert!(screen.nodes[&0].children.is_empty());
+    assert_eq!(screen.nodes.len(), 1);
+
+    std::fs::write(&path, "no outline here\n").unwrap();
+    let err = screen.run_command(&import).unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+    assert!(err.starts_with("no headings or bullets"));
+    assert!(screen.run_command(&import).is_err());
+    let err = screen.run_command("import").unwrap_err();
+    assert_eq!(err, "usage: import <path>");
+}
+
+#[test]
+fn test_import_markdown_round_trip() {
+    let outline = "* [ ] a\n  * [ ] b\n    * [x] c\n  * [ ] d\n* [ ] z\n";
+    let mut screen = Screen::default();
+    screen.import_markdown(outline);
+    assert_eq!(screen.export_outline(), outline);
+    // importing again puts the copy after what is there
+    screen.import_markdown(outline);
+    assert_eq!(screen.export_outline(), outline.repeat(2));
+}
+
+#[test]
+fn test_export_dot() {
+    let mut screen = Screen::default();
+    let anchors = screen.import_markdown("* a\n  * b\n  * say \"hi\"\n* c\n");
+    let (a, c) = (anchors[0], anchors[1]);
+    let b = screen.nodes[&a].children[0];
+    let hi = screen.nodes[&a].children[1];
+    screen.arrows.push((b, c));
+
+    let dot = screen.export_dot();
+    assert!(dot.starts_with("digraph void {\n"), "{}", dot);
+    assert!(dot.ends_with("}\n"), "{}", dot);
+    assert!(dot.contains(&format!("\"{}\" [label=\"a\"];", a)));
+    assert!(dot.contains(&format!("\"{}\" -> \"{}\";", a, b)));
+    assert!(dot.contains(&format!("\"{}\" [label=\"say \\\"hi\\\"\"];", hi)));
+    assert!(dot.contains(&format!("\"{}\" -> \"{}\" [style=dashed];", b, c)));
+
+    // drilled down, c and the arrow to it are left out
+    screen.drawing_root = a;
+    let dot = screen.export_dot();
+    assert!(dot.contains(&format!("\"{}\" [label=\"b\"];", b)));
+    assert!(!dot.contains("label=\"c\""), "{}", dot);
+    assert!(!dot.contains("dashed"), "{}", dot);
+    assert_eq!(dot_escape("two\nlines\\"), "two\\nlines\\\\");
+}
+
+#[test]
+fn test_export_opml() {
+    let mut screen = Screen::default();
+    screen.import_markdown("* [x] a & <b>\n  * \"c\"\n    * d\n* e\n");
+    let opml = screen.export_opml();
+    assert!(opml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<opml"));
+    assert!(opml.contains("<outline text=\"a &amp; &lt;b&gt;\" _complete=\"true\">"));
+    assert!(opml.contains("<outline text=\"&quot;c&quot;\">"));
+
+    // every tag closes in order, and the outlines nest three deep
+    let mut open: Vec<&str> = vec![];
+    let mut deepest = 0;
+    for tag in opml.split('<').skip(1).map(|t| &t[..t.find('>').unwrap()]) {
+        let name = tag.split(' ').next().unwrap();
+        if tag.starts_with('?') {
+            continue;
+        } else if let Some(name) = name.strip_prefix('/') {
+            assert_eq!(open.pop(), Some(name));
+        } else if !tag.ends_with('/') {
+            open.push(name);
+        }
+        let outlines = open.iter().filter(|&&t| t == "outline").count();
+        deepest = max(deepest, outlines + tag.ends_with('/') as usize);
+    }
+    assert!(open.is_empty(), "{:?}", open);
+    assert_eq!(deepest, 3);
+}
+
+#[test]
+fn test_fuzzy_candidates() {
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    screen.create_anchor((1, 2));
+    for c in "config".chars() {
+        screen.append(c);
+    }
+    let config = screen.selected.unwrap();
+    screen.create_child();
+    for c in "config".chars() {
+        screen.append(c);
+    }
+    let nested = screen.selected.unwrap();
+    screen.create_sibling();
+    for c in "cfg".chars() {
+        screen.append(c);
+    }
+    let exact = screen.selected.unwrap();
+    screen.create_sibling();
+    screen.append('x');
+    screen.unselect();
+
+    // equal scores go to the shallower node
+    assert_eq!(screen.fuzzy_candidates("cfg"), vec![exact, config, nested]);
+    assert!(screen.fuzzy_candidates("zzz").is_empty());
+}
+
+#[test]
+fn test_search_matches() {
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    screen.create_anchor((1, 2));
+    for c in "buy milk".chars() {
+        screen.append(c);
+    }
+    let milk = screen.selected.unwrap();
+    screen.create_child();
+    for c in "oat milk".chars() {
+        screen.append(c);
+    }
+    let oat = screen.selected.unwrap();
+    screen.create_sibling();
+    for c in "eggs".chars() {
+        screen.append(c);
+    }
+    screen.create_sibling();
+    for c in "milkshake".chars() {
+        screen.append(c);
+    }
+    let shake = screen.selected.unwrap();
+    screen.unselect();
+
+    assert_eq!(screen.matches("milk"), vec![milk, oat, shake]);
+    assert!(screen.matches("tea").is_empty());
+    assert!(screen.matches("").is_empty());
+
+    // typing highlights every match, submitting jumps to the first
+    screen.start_search(SearchDirection::Forward);
+    for c in "milk".chars() {
+        screen.append_search(c);
+    }
+    assert_eq!(screen.mode(), Mode::Command);
+    assert!(screen.is_search_match(shake));
+    screen.submit_search();
+    assert_eq!(screen.selected, Some(milk));
+
+    // next and prev wrap around the ends of the match list
+    screen.search_prev();
+    assert_eq!(screen.selected, Some(shake));
+    screen.search_next();
+    assert_eq!(screen.selected, Some(milk));
+}
+
+#[test]
+fn test_search_filter_modes() {
+    let exact = search_filter("Milk", SearchMode::Exact);
+    assert!(exact("buy Milk"));
+    assert!(!exact("buy milk"));
+
+    let ignore_case = search_filter("Milk", SearchMode::IgnoreCase);
+    assert!(ignore_case("buy milk"));
+    assert!(ignore_case("MILKSHAKE"));
+    assert!(!ignore_case("eggs"));
+
+    let regex = search_filter("^b.y [mM]ilk$", SearchMode::Regex);
+    assert!(regex("buy milk"));
+    assert!(!regex("buy oat milk"));
+
+    // an invalid regex matches nothing rather than panicking
+    let invalid = search_filter("milk(", SearchMode::Regex);
+    assert!(!invalid("milk("));
+}
+
+#[test]
+fn test_search_mode_toggle() {
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    screen.create_anchor((1, 2));
+    for c in "Milk (2)".chars() {
+        screen.append(c);
+    }
+    let milk = screen.selected.unwrap();
+    screen.unselect();
+
+    screen.start_search(SearchDirection::Forward);
+    for c in "milk (".chars() {
+        screen.append_search(c);
+    }
+    assert!(!screen.is_search_match(milk));
+    screen.toggle_search_mode();
+    assert!(screen.is_search_match(milk));
+    screen.toggle_search_mode();
+    assert!(!screen.is_search_match(milk));
+    let status = screen.search.as_ref().map(Search::status).unwrap();
+    assert!(status.contains("invalid regex"));
+
+    // fixing the pattern clears the error
+    screen.backspace_search();
+    assert!(!screen.search.as_ref().unwrap().invalid_regex);
+    assert!(!screen.is_search_match(milk));
+    screen.toggle_search_mode();
+    screen.toggle_search_mode();
+    assert_eq!(screen.search.as_ref().unwrap().mode, SearchMode::IgnoreCase);
+    assert!(screen.is_search_match(milk));
+
+    // C-t cycles the mode while the query is typed, not once it is
+    // submitted and a command is being typed
+    let ctrl_t = Event::Key(Key::Ctrl('t'));
+    screen.handle_event(ctrl_t.clone());
+    assert_eq!(screen.search.as_ref().unwrap().mode, SearchMode::Regex);
+    screen.handle_event(ctrl_t.clone());
+    screen.handle_event(ctrl_t.clone());
+    assert_eq!(screen.search.as_ref().unwrap().mode, SearchMode::IgnoreCase);
+    screen.submit_search();
+    screen.unselect();
+    screen.enter_cmd();
+    screen.handle_event(ctrl_t);
+    assert_eq!(screen.search.as_ref().unwrap().mode, SearchMode::IgnoreCase);
+}
+
+#[test]
+fn test_undo_redo_create_and_move() {
+    fn tree(screen: &Screen) -> Vec<Node> {
+        let mut nodes: Vec<Node> = screen
+            .nodes
+            .values()
+            .cloned()
+            .map(|mut n| {
+                n.selected = false;
+                n
+            })
+            .collect();
+        nodes.sort_by_key(|n| n.id);
+        nodes
+    }
+
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    let empty = tree(&screen);
+    screen.recorded(false, |s| s.create_anchor((1, 2)));
+    for c in "a".chars() {
+        screen.recorded(true, |s| s.append(c));
+    }
+    screen.recorded(false, |s| s.create_child());
+    for c in "bb".chars() {
+        screen.recorded(true, |s| s.append(c));
+    }
+    screen.recorded(false, |s| s.create_sibling());
+    for c in "cc".chars() {
+        screen.recorded(true, |s| s.append(c));
+    }
+    let created = tree(&screen);
+    let c = screen.selected.unwrap();
+    screen.recorded(false, |s| s.demote_selected());
+    let moved = tree(&screen);
+    assert_ne!(created, moved);
+
+    screen.recorded(false, |s| s.undo());
+    assert_eq!(tree(&screen), created);
+    assert_eq!(screen.selected, Some(c));
+    screen.recorded(false, |s| s.redo());
+    assert_eq!(tree(&screen), moved);
+
+    // the move, three creations and three runs of typing
+    for _ in 0..7 {
+        screen.recorded(false, |s| s.undo());
+    }
+    assert_eq!(tree(&screen), empty);
+    for _ in 0..7 {
+        screen.recorded(false, |s| s.redo());
+    }
+    assert_eq!(tree(&screen), moved);
+    screen.assert_node_consistency();
+
+    // a new change drops the redo stack
+    screen.recorded(false, |s| s.undo());
+    screen.recorded(false, |s| s.toggle_stricken());
+    screen.recorded(false, |s| s.redo());
+    assert!(screen.nodes[&c].stricken);
+    assert_eq!(screen.nodes[&c].parent_id, created[1].id);
+}
+
+#[test]
+fn test_undo_delete_restores_position() {
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    screen.create_anchor((1, 2));
+    screen.append('a');
+    let parent = screen.selected.unwrap();
+    let mut children = vec![];
+    for c in "xyz".chars() {
+        if children.is_empty() {
+            screen.create_child();
+        } else {
+            screen.create_sibling();
+        }
+        screen.append(c);
+        children.push(screen.selected.unwrap());
+    }
+
+    screen.select_node(children[1]);
+    screen.delete_selected(false);
+    assert_eq!(
+        screen.nodes[&parent].children,
+        vec![children[0], children[2]]
+    );
+
+    screen.undo_delete();
+    assert_eq!(screen.nodes[&parent].children, children);
+    assert_eq!(screen.nodes[&children[1]].parent_id, parent);
+    assert_eq!(screen.selected, Some(children[1]));
+}
+
+#[test]
+fn test_undo_delete_in_reverse_order() {
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    let mut roots = vec![];
+    let mut leaves = vec![];
+    for (i, c) in "abc".chars().enumerate() {
+        screen.create_anchor((1, 2 + 3 * i as u16));
+        screen.append(c);
+        roots.push(screen.selected.unwrap());
+        screen.create_child();
+        screen.append(c);
+        leaves.push(screen.selected.unwrap());
+    }
+
+    for &root in &roots {
+        screen.select_node(root);
+        screen.delete_selected(false);
+    }
+    assert!(roots.iter().chain(&leaves).all(|id| !screen.exists(*id)));
+
+    for i in (0..3).rev() {
+        screen.undo_delete();
+        assert_eq!(screen.selected, Some(roots[i]));
+        assert_eq!(screen.nodes[&roots[i]].children, vec![leaves[i]]);
+        assert!(roots[..i].iter().all(|id| !screen.exists(*id)));
+    }
+    assert_eq!(screen.nodes[&0].children, roots);
+    screen.assert_node_consistency();
+}
+
+#[cfg(test)]
+fn wait_for_execs(screen: &mut Screen) {
+    let deadline = now() + std::time::Duration::from_secs(10);
+    while !screen.execs.is_empty() {
+        assert!(now() < deadline, "command did not finish");
+        screen.poll_execs();
+        thread::sleep(std::time::Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn test_capture_exec_output() {
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    screen.create_anchor((1, 2));
+    screen.append('x');
+    let node_id = screen.selected.unwrap();
+
+    screen.config.settings.exec_capture_output = true;
+    screen.spawn_exec(node_id, "echo hello; echo; echo world >&2");
+    wait_for_execs(&mut screen);
+    let children: Vec<_> = screen.nodes[&node_id]
+        .children
+        .iter()
+        .map(|c| screen.nodes[c].content.clone())
+        .collect();
+    assert_eq!(children, vec!["hello", "world"]);
+    screen.assert_node_consistency();
+
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    screen.config.settings.exec_capture_output = true;
+    screen.config.settings.exec_max_lines = 2;
+    screen.create_anchor((1, 2));
+    screen.append('x');
+    let node_id = screen.selected.unwrap();
+
+    screen.spawn_exec(node_id, "echo 1; echo 2; echo 3; echo 4; exit 3");
+    wait_for_execs(&mut screen);
+    let children: Vec<_> = screen.nodes[&node_id]
+        .children
+        .iter()
+        .map(|c| screen.nodes[c].content.clone())
+        .collect();
+    assert_eq!(children, vec!["1", "2", "… 2 more lines", "exit status 3"]);
+}
+
+#[test]
+fn test_exec_runs_in_background() {
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    screen.config.settings.exec_capture_output = true;
+    screen.create_anchor((1, 2));
+    screen.append('x');
+    let sleeper = screen.selected.unwrap();
+    screen.create_anchor((1, 5));
+    screen.append('y');
+    let echo = screen.selected.unwrap();
+
+    screen.spawn_exec(sleeper, "sleep 5; echo late");
+    screen.spawn_exec(echo, "echo done");
+    assert!(screen.execs.contains_key(&sleeper));
+    assert!(screen.execs.contains_key(&echo));
+
+    let deadline = now() + std::time::Duration::from_secs(10);
+    while screen.execs.contains_key(&echo) {
+        assert!(now() < deadline, "command did not finish");
+        screen.poll_execs();
+        thread::sleep(std::time::Duration::from_millis(10));
+    }
+    assert_eq!(screen.nodes[&echo].children.len(), 1);
+    assert!(screen.execs.contains_key(&sleeper));
+
+    screen.select_node(sleeper);
+    screen.cancel_exec();
+    assert!(!screen.execs.contains_key(&sleeper));
+    screen.poll_execs();
+    assert!(screen.nodes[&sleeper].children.is_empty());
+}
+
+#[test]
+fn test_tick_adds_output_without_input() {
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    screen.config.settings.exec_capture_output = true;
+    screen.create_anchor((1, 2));
+    screen.append('x');
+    let node_id = screen.selected.unwrap();
+
+    screen.spawn_exec(node_id, "sleep 0.2; echo hello");
+    // a running command asks for redraws to turn its spinner
+    assert!(screen.tick());
+    let deadline = now() + std::time::Duration::from_secs(10);
+    while screen.nodes[&node_id].children.is_empty() {
+        assert!(now() < deadline, "output never appeared");
+        thread::sleep(std::time::Duration::from_millis(TICK_MS));
+        screen.tick();
+    }
+    let child = screen.nodes[&node_id].children[0];
+    assert_eq!(screen.nodes[&child].content, "hello");
+    assert!(screen.execs.is_empty());
+    assert!(!screen.tick());
+}
+
+#[test]
+fn test_paste_as_child_twice() {
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    screen.create_anchor((1, 2));
+    screen.append('a');
+    let yanked = screen.selected.unwrap();
+    screen.create_child();
+    screen.append('b');
+    screen.create_anchor((1, 6));
+    screen.append('t');
+    let target = screen.selected.unwrap();
+
+    screen.select_node(yanked);
+    screen.yank_node();
+    screen.select_node(target);
+    screen.paste_as_child();
+    screen.paste_as_child();
+
+    let copies = screen.nodes[&target].children.clone();
+    assert_eq!(copies.len(), 2);
+    assert_ne!(copies[0], copies[1]);
+    for &copy in &copies {
+        assert_ne!(copy, yanked);
+        assert_eq!(screen.nodes[&copy].content, "a");
+        let child = screen.nodes[&copy].children[0];
+        assert_eq!(screen.nodes[&child].content, "b");
+    }
+    // the two copies don't share any nodes
+    assert_ne!(
+        screen.nodes[&copies[0]].children,
+        screen.nodes[&copies[1]].children
+    );
+    assert_eq!(screen.nodes[&yanked].parent_id, 0);
+    screen.assert_node_consistency();
+}
+
+#[test]
+fn test_yank_and_cut_node() {
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    screen.create_anchor((1, 2));
+    screen.append('a');
+    screen.create_child();
+    screen.append('b');
+    let original = screen.selected.unwrap();
+    screen.create_anchor((1, 6));
+    screen.append('t');
+    let target = screen.selected.unwrap();
+
+    // yanking leaves the original where it was
+    screen.select_node(original);
+    screen.yank_node();
+    screen.select_node(target);
+    screen.paste_as_child();
+    assert!(screen.exists(original));
+    let copy = screen.nodes[&target].children[0];
+    assert_ne!(copy, original);
+    assert_eq!(screen.nodes[&copy].content, "b");
+
+    // cutting takes it out of the tree until it is pasted
+    screen.select_node(original);
+    screen.cut_node();
+    assert!(!screen.exists(original));
+    assert!(screen.undo_stack.is_empty());
+    screen.select_node(target);
+    screen.paste_as_child();
+    let pasted = screen.nodes[&target].children[1];
+    assert_eq!(screen.nodes[&pasted].content, "b");
+    assert!(!screen.exists(original));
+    screen.assert_node_consistency();
+}
+
+#[cfg(test)]
+struct StubClipboard(std::rc::Rc<std::cell::RefCell<String>>);
+
+#[cfg(test)]
+impl Clipboard for StubClipboard {
+    fn get_text(&mut self) -> Result<String, String> {
+        Ok(self.0.borrow().clone())
+    }
+
+    fn set_text(&mut self, text: String) -> Result<(), String> {
+        *self.0.borrow_mut() = text;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_clipboard_copy_paste() {
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    // no clipboard to talk to
+    screen.create_anchor((1, 2));
+    screen.append('a');
+    screen.copy_clipboard();
+    screen.paste_clipboard();
+    assert_eq!(screen.nodes[&screen.selected.unwrap()].content, "a");
+
+    let text = std::rc::Rc::new(std::cell::RefCell::new(String::new()));
+    screen.clipboard = Some(Box::new(StubClipboard(text.clone())));
+    screen.copy_clipboard();
+    assert_eq!(*text.borrow(), "a");
+
+    *text.borrow_mut() = "from\nsomewhere else".to_owned();
+    screen.paste_clipboard();
+    let selected = screen.selected.unwrap();
+    assert_eq!(screen.nodes[&selected].content, "from somewhere else");
+
+    // with nothing selected the text goes into a new node
+    screen.unselect();
+    screen.paste_clipboard();
+    let created = screen.selected.unwrap();
+    assert_ne!(created, selected);
+    assert_eq!(screen.nodes[&created].content, "from somewhere else");
+}
+
+#[test]
+fn test_start_end_arrow() {
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    screen.create_anchor((1, 2));
+    screen.append('a');
+    let a = screen.selected.unwrap();
+    screen.create_anchor((1, 6));
+    screen.append('b');
+    let b = screen.selected.unwrap();
+
+    // a node can't point at itself
+    screen.start_arrow();
+    screen.end_arrow();
+    assert!(screen.arrows.is_empty());
+
+    for _ in 0..2 {
+        screen.select_node(a);
+        screen.start_arrow();
+        screen.select_node(b);
+        screen.end_arrow();
+    }
+    assert_eq!(screen.arrows, vec![(a, b)]);
+
+    // ending without a start does nothing
+    screen.select_node(a);
+    screen.end_arrow();
+    assert_eq!(screen.arrows, vec![(a, b)]);
+}
+
+#[test]
+fn test_remove_arrow() {
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    let mut ids = vec![];
+    for (i, c) in "abc".chars().enumerate() {
+        screen.create_anchor((1, 2 + 4 * i as u16));
+        screen.append(c);
+        ids.push(screen.selected.unwrap());
+    }
+    let (a, b, c) = (ids[0], ids[1], ids[2]);
+    screen.arrows = vec![(a, c), (a, b), (b, c)];
+
+    // a picked start and the selected target name the arrow exactly
+    screen.select_node(b);
+    screen.start_arrow();
+    screen.select_node(c);
+    screen.remove_arrow();
+    assert_eq!(screen.arrows, vec![(a, c), (a, b)]);
+
+    // otherwise the selection's outgoing arrow to the nearest drawn target
+    // goes, here b rather than c, which is listed first
+    screen.select_node(a);
+    screen.draw_children_of_root();
+    screen.remove_arrow();
+    assert_eq!(screen.arrows, vec![(a, c)]);
+
+    // deleting a node prunes every arrow touching it
+    screen.arrows.push((c, a));
+    screen.select_node(c);
+    screen.delete_selected(false);
+    assert!(screen.arrows.is_empty());
+    screen.assert_node_consistency();
+}
+
+#[test]
+fn test_tree_layout_positions() {
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    screen.create_anchor((40, 12));
+    screen.append('a');
+    let a = screen.selected.unwrap();
+    screen.create_child();
+    screen.append('b');
+    screen.create_sibling();
+    screen.append('c');
+    screen.create_anchor((3, 3));
+    screen.append('d');
+    let d = screen.selected.unwrap();
+    screen.unselect();
+
+    assert!(screen.anchor_positions(&[a, d]).is_empty());
+    screen.toggle_layout();
+    let positions = screen.anchor_positions(&[a, d]);
+    assert_eq!(positions[&a], (1, 2));
+    assert_eq!(positions[&d], (1, 6));
+    // stored coordinates are left alone for switching back
+    assert_eq!(screen.nodes[&a].rooted_coords, (40, 12));
+    screen.toggle_layout();
+    assert_eq!(screen.layout, LayoutKind::Radial);
+    let positions = screen.anchor_positions(&[a, d]);
+    assert_eq!(positions[&screen.drawing_root], (40, 13));
+    assert_eq!(positions.len(), 5);
+    screen.toggle_layout();
+    assert_eq!(screen.layout, LayoutKind::Free);
+}
+
+#[test]
+fn test_dirty_flag() {
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    let path = std::env::temp_dir().join(format!("void_dirty_{}", std::process::id()));
+    screen.work_path = Some(path.to_str().unwrap().to_owned());
+    assert!(!screen.dirty);
+
+    screen.recorded(false, |s| {
+        s.create_anchor((1, 2));
+        s.append('a');
+    });
+    let a = screen.selected.unwrap();
+    assert!(screen.dirty);
+    screen.save();
+    assert!(!screen.dirty);
+
+    // moving the selection around is not a change worth saving
+    screen.recorded(false, |s| s.unselect());
+    screen.recorded(false, |s| s.select_node(a));
+    assert!(!screen.dirty);
+    screen.recorded(true, |s| s.append('b'));
+    assert!(screen.dirty);
+    screen.undo();
+    assert!(screen.dirty);
+    screen.save();
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(format!("{}.bak", path.display())).unwrap();
+
+    let saved_at = screen.last_save_secs;
+    assert!(!screen.autosave_due(saved_at + 59));
+    assert!(screen.autosave_due(saved_at + 60));
+    screen.config.settings.autosave_secs = 0;
+    assert!(!screen.autosave_due(saved_at + 3600));
+}
+
+#[test]
+fn test_save_keeps_backup() {
+    let mut screen = Screen::default();
+    let path = std::env::temp_dir().join(format!("void_backup_{}", std::process::id()));
+    let path = path.to_str().unwrap().to_owned();
+    let bak_path = format!("{}.bak", path);
+    screen.work_path = Some(path.clone());
+
+    screen.create_anchor((1, 2));
+    screen.append('a');
+    screen.save();
+    let first = std::fs::read(&path).unwrap();
+    assert!(std::fs::metadata(&bak_path).is_err());
+
+    screen.append('b');
+    screen.save();
+    let second = std::fs::read(&path).unwrap();
+    assert_ne!(first, second);
+    assert_eq!(std::fs::read(&bak_path).unwrap(), first);
+
+    let loaded = serialization::deserialize_screen(second).unwrap();
+    assert!(loaded.nodes.values().any(|n| n.content == "ab"));
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(&bak_path).unwrap();
+}
+
+#[test]
+fn test_wrapped_nodes_are_taller() {
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    screen.config.settings.node_width = 10;
+    screen.create_anchor((1, 2));
+    for c in "buy milk and eggs".chars() {
+        screen.append(c);
+    }
+    let a = screen.selected.unwrap();
+    screen.create_child();
+    screen.append('x');
+    screen.create_anchor((1, 3));
+    screen.append('d');
+    let d = screen.selected.unwrap();
+    screen.unselect();
+
+    assert_eq!(screen.drawable_subtree_dims(a), Some((9, 3)));
+    screen.toggle_layout();
+    let positions = screen.anchor_positions(&[a, d]);
+    assert_eq!(positions[&d], (1, 6));
+
+    screen.config.settings.node_width = 0;
+    assert_eq!(screen.drawable_subtree_dims(a), Some((18, 2)));
+}
+
+#[test]
+fn test_wide_text_dims() {
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    screen.create_anchor((1, 2));
+    for c in "日本 語".chars() {
+        screen.append(c);
+    }
+    let a = screen.selected.unwrap();
+    screen.unselect();
+    assert_eq!(screen.drawable_subtree_dims(a), Some((8, 1)));
+    screen.config.settings.node_width = 4;
+    assert_eq!(screen.drawable_subtree_dims(a), Some((5, 2)));
+
+    screen.config.settings.node_width = 0;
+    screen.draw_children_of_root();
+    // the pointer finds the node across both columns of each character
+    assert_eq!(screen.lookup((1 + 7, 2)), Some(&a));
+}
+
+#[test]
+fn test_insert_newline() {
+    use termion::event::Key::Alt;
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    screen.create_anchor((1, 2));
+    screen.append('a');
+    let a = screen.selected.unwrap();
+    assert_eq!(screen.drawable_subtree_dims(a), Some((2, 1)));
+
+    screen.handle_event(Event::Key(Alt('\r')));
+    screen.handle_event(Event::Key(Key::Char('b')));
+    assert_eq!(screen.nodes[&a].content, "a\nb");
+    assert_eq!(screen.drawable_subtree_dims(a), Some((2, 2)));
+    // enter still makes a sibling
+    screen.handle_event(Event::Key(Key::Char('\n')));
+    assert_ne!(screen.selected, Some(a));
+    assert_eq!(screen.nodes[&a].content, "a\nb");
+}
+
+#[test]
+fn test_cursor_editing() {
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    screen.create_anchor((1, 2));
+    screen.append('a');
+    screen.append('c');
+    let a = screen.selected.unwrap();
+
+    screen.handle_action(Some(Action::CursorLeft));
+    screen.append('b');
+    assert_eq!(screen.nodes[&a].content, "abc");
+    screen.backspace();
+    assert_eq!(screen.nodes[&a].content, "ac");
+    assert_eq!(screen.cursor_pos(), Some((a, 1)));
+
+    // backspacing at the start does nothing
+    screen.handle_action(Some(Action::CursorHome));
+    screen.backspace();
+    assert_eq!(screen.nodes[&a].content, "ac");
+    screen.append('_');
+    assert_eq!(screen.nodes[&a].content, "_ac");
+
+    // the cursor stays within the text
+    screen.handle_action(Some(Action::CursorLeft));
+    screen.handle_action(Some(Action::CursorLeft));
+    assert_eq!(screen.cursor_pos(), Some((a, 0)));
+    for _ in 0..5 {
+        screen.handle_action(Some(Action::CursorRight));
+    }
+    assert_eq!(screen.cursor_pos(), Some((a, 3)));
+
+    // reselecting types at the end again
+    screen.handle_action(Some(Action::CursorHome));
+    screen.unselect();
+    screen.select_node(a);
+    screen.append('!');
+    assert_eq!(screen.nodes[&a].content, "_ac!");
+}
+
+#[test]
+fn test_erase_word() {
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    screen.create_anchor((1, 2));
+    for c in "buy milk  now".chars() {
+        screen.append(c);
+    }
+    let a = screen.selected.unwrap();
+
+    // from the middle, taking the whitespace before the cursor with it
+    for _ in 0.."now".len() {
+        screen.handle_action(Some(Action::CursorLeft));
+    }
+    screen.erase_word();
+    assert_eq!(screen.nodes[&a].content, "buy now");
+    assert_eq!(screen.cursor_pos(), Some((a, 4)));
+
+    // nothing before the cursor at the start
+    screen.handle_action(Some(Action::CursorHome));
+    screen.erase_word();
+    assert_eq!(screen.nodes[&a].content, "buy now");
+
+    screen.handle_action(Some(Action::CursorEnd));
+    screen.erase_word();
+    screen.erase_word();
+    assert_eq!(screen.nodes[&a].content, "");
+    screen.erase_word();
+    assert_eq!(screen.nodes[&a].content, "");
+}
+
+#[test]
+fn test_rename_node() {
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    screen.create_anchor((1, 2));
+    for c in "old name".chars() {
+        screen.append(c);
+    }
+    let a = screen.selected.unwrap();
+
+    screen.handle_action(Some(Action::RenameNode));
+    assert_eq!(screen.nodes[&a].content, "");
+    screen.handle_event(Event::Key(Key::Esc));
+    assert_eq!(screen.selected, None);
+    assert_eq!(screen.nodes[&a].content, "old name");
+
+    screen.select_node(a);
+    screen.handle_action(Some(Action::RenameNode));
+    screen.handle_event(Event::Key(Key::Char('x')));
+    assert_eq!(screen.nodes[&a].content, "x");
+    screen.handle_event(Event::Key(Key::Esc));
+    assert_eq!(screen.nodes[&a].content, "x");
+    assert!(screen.renaming.is_none());
+}
+
+#[test]
+fn test_status_line() {
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    assert_eq!(screen.status_line(), "NORMAL 0 nodes");
+
+    screen.create_anchor((1, 2));
+    screen.handle_event(Event::Key(Key::Char('a')));
+    let status = screen.status_line();
+    assert!(status.contains("INSERT"), "{}", status);
+    assert!(status.contains("1 node "), "{}", status);
+    assert!(status.ends_with('*'), "{}", status);
+}
+
+#[test]
+fn test_goto_command() {
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    screen.create_anchor((1, 2));
+    screen.append('a');
+    let a = screen.selected.unwrap();
+    screen.create_child();
+    screen.append('b');
+    let b = screen.selected.unwrap();
+    screen.select_node(a);
+    screen.toggle_collapsed();
+    screen.unselect();
+
+    // b is hidden under the collapsed a, so the view moves to show it
+    assert_eq!(screen.run_command(&format!(":goto {}", b)), Ok(()));
+    assert_eq!(screen.selected, Some(b));
+    assert_eq!(screen.drawing_root, a);
+    assert_eq!(screen.run_command(&format!(" goto {} ", a)), Ok(()));
+    assert_eq!(screen.selected, Some(a));
+}
+
+#[test]
+fn test_goto_unknown_node() {
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    let err = screen.run_command("goto 42").unwrap_err();
+    assert_eq!(err, "no node with id 42");
+    assert!(screen.run_command("goto home").is_err());
+    assert!(screen.run_command("goto").is_err());
+    assert!(screen.run_command("frobnicate").is_err());
+
+    // a failed command says why in the status line
+    screen.enter_cmd();
+    for c in "goto 42".chars() {
+        screen.append_cmd(c);
+    }
+    screen.submit_cmd();
+    assert!(screen.status_line().ends_with("no node with id 42"));
+}
+
+#[test]
+fn test_quit_commands() {
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    let path = std::env::temp_dir().join(format!("void_quit_{}", std::process::id()));
+    let path = path.to_str().unwrap().to_owned();
+    screen.work_path = Some(path.clone());
+    screen.create_anchor((1, 2));
+    screen.append('a');
+    screen.dirty = true;
+
+    // unsaved changes keep :q from quitting
+    let err = screen.run_command(":q").unwrap_err();
+    assert!(err.contains("unsaved changes"));
+    assert_eq!(screen.quitting, None);
+
+    screen.run_command(":w").unwrap();
+    assert!(!screen.dirty);
+    assert!(std::fs::metadata(&path).is_ok());
+    screen.run_command(":q").unwrap();
+    assert_eq!(screen.quitting, Some(false));
+
+    screen.quitting = None;
+    screen.append('b');
+    screen.dirty = true;
+    screen.run_command(":wq").unwrap();
+    assert!(!screen.dirty);
+    assert_eq!(screen.quitting, Some(false));
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(format!("{}.bak", path)).unwrap();
+}
+
+#[test]
+fn test_force_quit_discards() {
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    screen.create_anchor((1, 2));
+    screen.append('a');
+    screen.dirty = true;
+
+    screen.command = Some("q!".to_owned());
+    assert!(!screen.handle_action(Some(Action::SubmitCmd)));
+    assert_eq!(screen.quitting, Some(true));
+    assert!(screen.dirty);
+}
+
+#[test]
+fn test_set_command() {
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    screen.create_anchor((1, 2));
+    for c in "one two three".chars() {
+        screen.append(c);
+    }
+    let node_id = screen.selected.unwrap();
+    assert_eq!(screen.drawable_subtree_dims(node_id), Some((14, 1)));
+
+    // text rewraps as soon as the width changes
+    screen.run_command(":set node_width 5").unwrap();
+    assert_eq!(screen.config.settings.node_width, 5);
+    assert_eq!(screen.drawable_subtree_dims(node_id), Some((6, 3)));
+    screen.run_command("set autosave_secs 30").unwrap();
+    assert_eq!(screen.config.settings.autosave_secs, 30);
+
+    let err = screen.run_command("set colour blue").unwrap_err();
+    assert_eq!(err, "unknown setting: colour");
+    assert!(screen.run_command("set").is_err());
+    assert!(screen.run_command("set node_width").is_err());
+}
+
+#[test]
+fn test_complete_command() {
+    assert_eq!(complete_command("g"), vec!["goto"]);
+    assert_eq!(complete_command("q"), vec!["q", "q!"]);
+    assert!(complete_command("x").is_empty());
+}
+
+#[test]
+fn test_complete_cmd_cycles() {
+    let mut screen = Screen::default();
+    screen.enter_cmd();
+    screen.append_cmd('g');
+    screen.complete_cmd();
+    assert_eq!(screen.command.as_deref(), Some("goto"));
+
+    screen.enter_cmd();
+    screen.append_cmd(':');
+    screen.append_cmd('w');
+    screen.complete_cmd();
+    assert_eq!(screen.command.as_deref(), Some(":w"));
+    assert_eq!(screen.message.as_deref(), Some("w wq"));
+    screen.complete_cmd();
+    assert_eq!(screen.command.as_deref(), Some(":wq"));
+    screen.complete_cmd();
+    assert_eq!(screen.command.as_deref(), Some(":w"));
+
+    // arguments aren't completed
+    screen.enter_cmd();
+    for c in "set node".chars() {
+        screen.append_cmd(c);
+    }
+    screen.complete_cmd();
+    assert_eq!(screen.command.as_deref(), Some("set node"));
+}
+
+#[test]
+fn test_filter_by_tag() {
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    screen.create_anchor((1, 2));
+    screen.append('a');
+    let a = screen.selected.unwrap();
+    screen.create_child();
+    for c in "b #work".chars() {
+        screen.append(c);
+    }
+    let b = screen.selected.unwrap();
+    screen.create_sibling();
+    screen.append('c');
+    let c = screen.selected.unwrap();
+    screen.create_anchor((1, 10));
+    screen.append('d');
+    let d = screen.selected.unwrap();
+    screen.unselect();
+    assert_eq!(screen.nodes[&b].tags, vec!["work"]);
+
+    screen.run_command("filter #work").unwrap();
+    // the tagged node's parent stays to show where it is
+    assert!(!screen.hidden_by_tag_filter(a));
+    assert!(!screen.hidden_by_tag_filter(b));
+    assert!(screen.hidden_by_tag_filter(c));
+    assert!(screen.hidden_by_tag_filter(d));
+    assert_eq!(screen.drawable_subtree_dims(a), Some((11, 2)));
+    assert!(screen.status_line().contains("#work"));
+
+    // tagging another node shows it from the next draw on
+    screen.select_node(c);
+    for c in " #work".chars() {
+        screen.append(c);
+    }
+    screen.unselect();
+    assert_eq!(screen.nodes[&c].tags, vec!["work"]);
+    screen.draw_children_of_root();
+    assert!(!screen.hidden_by_tag_filter(c));
+    assert!(screen.hidden_by_tag_filter(d));
+
+    assert!(screen.run_command("filter #play").is_err());
+    assert_eq!(screen.tag_filter.as_deref(), Some("work"));
+    screen.run_command("filter").unwrap();
+    assert!(!screen.hidden_by_tag_filter(c));
+    assert!(!screen.hidden_by_tag_filter(d));
+}
+
+#[test]
+fn test_due_command() {
+    let mut screen = Screen::default();
+    assert_eq!(
+        screen.run_command("due 2024-06-01").unwrap_err(),
+        "no node is selected"
+    );
+    screen.create_anchor((1, 2));
+    screen.append('a');
+    let node_id = screen.selected.unwrap();
+
+    screen.run_command(":due 2024-06-01").unwrap();
+    assert_eq!(screen.nodes[&node_id].meta.due, Some(1_717_200_000));
+    assert!(screen.nodes[&node_id].is_overdue(today()));
+
+    let err = screen.run_command("due 2024-02-30").unwrap_err();
+    assert!(err.starts_with("not a date"));
+    assert_eq!(screen.nodes[&node_id].meta.due, Some(1_717_200_000));
+
+    screen.run_command("due").unwrap();
+    assert_eq!(screen.nodes[&node_id].meta.due, None);
+}
+
+#[test]
+fn test_sort_by_priority() {
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    screen.create_anchor((1, 2));
+    screen.append('p');
+    let parent = screen.selected.unwrap();
+    let mut children = vec![];
+    for &(c, priority) in &[('a', 0), ('b', 2), ('c', 1), ('d', 2), ('e', 0)] {
+        if children.is_empty() {
+            screen.create_child();
+        } else {
+            screen.create_sibling();
+        }
+        screen.append(c);
+        for _ in 0..priority {
+            screen.handle_action(Some(Action::RaisePriority));
+        }
+        children.push(screen.selected.unwrap());
+    }
+    assert_eq!(screen.nodes[&children[1]].priority, 2);
+
+    screen.select_node(parent);
+    screen.run_command("sort priority").unwrap();
+    let order: String = screen.nodes[&parent]
+        .children
+        .iter()
+        .map(|id| screen.nodes[id].content.clone())
+        .collect();
+    assert_eq!(order, "bdcae");
+
+    assert!(screen.run_command("sort size").is_err());
+}
+
+#[test]
+fn test_progress() {
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    screen.create_anchor((1, 2));
+    screen.append('p');
+    let parent = screen.selected.unwrap();
+    screen.create_child();
+    screen.append('a');
+    screen.toggle_stricken();
+    screen.create_sibling();
+    screen.append('b');
+    let b = screen.selected.unwrap();
+    // c holds two leaves of its own, one of them done
+    screen.create_sibling();
+    screen.append('c');
+    let c = screen.selected.unwrap();
+    screen.create_child();
+    screen.append('d');
+    screen.toggle_stricken();
+    screen.create_sibling();
+    screen.append('e');
+    screen.unselect();
+    assert_eq!(screen.progress(parent), (2, 4));
+    assert_eq!(screen.progress(c), (1, 2));
+    assert_eq!(screen.progress(b), (0, 0));
+
+    screen.select_node(b);
+    screen.toggle_stricken();
+    screen.unselect();
+    assert_eq!(screen.progress(parent), (3, 4));
+
+    // collapsed parents show it after their text
+    screen.with_node_mut_no_meta(parent, |n| n.collapsed = true);
+    let formatted = screen.format_node(&screen.nodes[&parent].clone());
+    assert_eq!(formatted.content, "p (3/4)");
+}
+
+#[test]
+fn test_cascade_complete() {
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    screen.create_anchor((1, 2));
+    screen.append('p');
+    let parent = screen.selected.unwrap();
+    screen.create_child();
+    screen.append('a');
+    let a = screen.selected.unwrap();
+    screen.create_child();
+    screen.append('b');
+    let b = screen.selected.unwrap();
+    screen.create_sibling();
+    screen.append('c');
+    let c = screen.selected.unwrap();
+    // b starts out done on its own
+    screen.select_node(b);
+    screen.toggle_stricken();
+    let stricken = |screen: &Screen| -> Vec<bool> {
+        [parent, a, b, c]
+            .iter()
+            .map(|id| screen.nodes[id].stricken)
+            .collect()
+    };
+
+    // off: only the selected node changes
+    screen.select_node(parent);
+    screen.toggle_stricken();
+    assert_eq!(stricken(&screen), vec![true, false, true, false]);
+    screen.toggle_stricken();
+
+    // on: the subtree follows, and b stays done rather than flipping
+    screen.run_command("set cascade_complete true").unwrap();
+    screen.toggle_stricken();
+    assert_eq!(stricken(&screen), vec![true, true, true, true]);
+    assert!(screen.nodes[&c].meta.finish_time.is_some());
+    screen.toggle_stricken();
+    assert_eq!(stricken(&screen), vec![false, false, false, false]);
+}
+
+#[test]
+fn test_auto_complete_parents() {
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    screen.config.settings.auto_complete_parents = true;
+    screen.create_anchor((1, 2));
+    screen.append('p');
+    let parent = screen.selected.unwrap();
+    screen.create_child();
+    screen.append('a');
+    let a = screen.selected.unwrap();
+    screen.create_child();
+    screen.append('b');
+    let b = screen.selected.unwrap();
+    screen.create_sibling();
+    screen.append('c');
+    let c = screen.selected.unwrap();
+
+    // bottom-up: a only completes with its last child, then p with a
+    screen.select_node(b);
+    screen.toggle_stricken();
+    assert!(!screen.nodes[&a].stricken);
+    screen.select_node(c);
+    screen.toggle_stricken();
+    assert!(screen.nodes[&a].stricken);
+    assert!(screen.nodes[&parent].stricken);
+
+    // reopening a leaf reopens the chain above it
+    screen.toggle_stricken();
+    assert!(!screen.nodes[&a].stricken);
+    assert!(!screen.nodes[&parent].stricken);
+    assert!(screen.nodes[&b].stricken);
+
+    // a free node has only the root above it, which is left alone
+    screen.select_node(parent);
+    screen.toggle_stricken();
+    assert!(!screen.nodes[&0].stricken);
+}
+
+#[test]
+fn test_chord_matched() {
+    use termion::event::Key::Char;
+
+    let mut screen = Screen {
+        config: Config::parse("normal toggle_show_logs: g\nnormal toggle_layout: gg").unwrap(),
+        ..Screen::default()
+    };
+    assert!(screen.handle_event_at(Event::Key(Char('g')), 1000));
+    assert_eq!(screen.pending_chord, vec![Char('g')]);
+    assert!(screen.status_line().ends_with("  g"));
+    assert!(screen.handle_event_at(Event::Key(Char('g')), 1200));
+    assert!(screen.pending_chord.is_empty());
+    assert_eq!(screen.layout, LayoutKind::Tree);
+    assert!(!screen.show_logs);
+}
+
+#[test]
+fn test_chord_timed_out() {
+    use termion::event::Key::{Char, Ctrl};
+
+    let mut screen = Screen {
+        config: Config::parse("normal toggle_show_logs: g\nnormal toggle_layout: gg").unwrap(),
+        ..Screen::default()
+    };
+    screen.handle_event_at(Event::Key(Char('g')), 1000);
+    // too late to finish the chord, so the g stands alone and the next g
+    // starts over
+    screen.handle_event_at(Event::Key(Char('g')), 1000 + CHORD_TIMEOUT_MS + 1);
+    assert!(screen.show_logs);
+    assert_eq!(screen.layout, LayoutKind::default());
+    assert_eq!(screen.pending_chord, vec![Char('g')]);
+
+    // a key that breaks the chord flushes the prefix before it
+    screen.handle_event_at(Event::Key(Ctrl('l')), 1000 + CHORD_TIMEOUT_MS + 2);
+    assert!(screen.show_logs);
+    assert!(screen.pending_chord.is_empty());
+}
+
+#[test]
+fn test_chord_times_out_without_input() {
+    use termion::event::Key::Char;
+
+    let mut screen = Screen {
+        config: Config::parse("normal toggle_show_logs: g\nnormal toggle_layout: gg").unwrap(),
+        ..Screen::default()
+    };
+    assert!(!screen.chord_timed_out(5000));
+    screen.handle_event_at(Event::Key(Char('g')), 1000);
+    assert!(!screen.chord_timed_out(1000 + CHORD_TIMEOUT_MS));
+    // the event loop flushes the prefix once it has waited too long
+    assert!(screen.chord_timed_out(1000 + CHORD_TIMEOUT_MS + 1));
+    assert!(screen.flush_chord());
+    assert!(screen.show_logs);
+    assert!(screen.pending_chord.is_empty());
+    assert!(!screen.chord_timed_out(1000 + CHORD_TIMEOUT_MS + 1));
+}
+
+#[test]
+fn test_leader_chord() {
+    use termion::event::Key::Char;
+
+    let mut screen = Screen {
+        config: Config::parse("normal toggle_show_logs: leader l").unwrap(),
+        ..Screen::default()
+    };
+    screen.handle_event_at(Event::Key(Char(' ')), 1000);
+    assert_eq!(screen.pending_chord, vec![Char(' ')]);
+    screen.handle_event_at(Event::Key(Char('l')), 1100);
+    assert!(screen.show_logs);
+    assert!(screen.pending_chord.is_empty());
+}
+
+#[test]
+fn test_chord_popup() {
+    use termion::event::Key::{Char, Esc};
+
+    let mut screen = Screen {
+        config: Config::parse("normal save: leader s\nnormal toggle_show_logs: leader l").unwrap(),
+        ..Screen::default()
+    };
+    assert!(screen.chord_popup_lines().is_empty());
+    screen.handle_event_at(Event::Key(Char(' ')), 1000);
+    assert_eq!(
+        screen.chord_popup_lines(),
+        vec![" l  toggle_show_logs ", " s  save "]
+    );
+    screen.handle_event_at(Event::Key(Esc), 1100);
+    assert!(screen.pending_chord.is_empty());
+    assert!(screen.chord_popup_lines().is_empty());
+    assert!(!screen.show_logs);
+}
+
+#[test]
+fn test_count_prefix() {
+    use termion::event::Key::{Char, Down};
+
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    let mut ids = vec![];
+    for y in &[14, 16, 18, 20, 22] {
+        screen.create_anchor((10, *y));
+        screen.append('x');
+        ids.push(screen.selected.unwrap());
+    }
+    screen.unselect();
+    screen.draw_children_of_root();
+
+    // the first move down starts from the middle of the screen
+    screen.handle_event(Event::Key(Char('3')));
+    assert_eq!(screen.count, Some(3));
+    assert!(screen.status_line().ends_with("  3"));
+    screen.handle_event(Event::Key(Down));
+    assert_eq!(screen.selected, Some(ids[2]));
+    assert_eq!(screen.count, None);
+
+    screen.unselect();
+    for c in "12345".chars() {
+        screen.handle_event(Event::Key(Char(c)));
+    }
+    assert_eq!(screen.count, Some(MAX_COUNT));
+}
+
+#[test]
+fn test_count_cleared_by_esc() {
+    use termion::event::Key::{Char, Esc};
+
+    let mut screen = Screen::default();
+    screen.handle_event(Event::Key(Char('4')));
+    screen.handle_event(Event::Key(Char('2')));
+    assert_eq!(screen.count, Some(42));
+    // esc in normal mode would otherwise quit
+    assert!(screen.handle_event(Event::Key(Esc)));
+    assert_eq!(screen.count, None);
+}
+
+#[test]
+fn test_select_line() {
+    let mut screen = Screen {
+        dims: (80, 24),
+        ..Screen::default()
+    };
+    // created out of order, then read top to bottom and left to right
+    let mut ids = vec![];
+    for &coords in &[(10, 8), (30, 4), (10, 4), (10, 12)] {
+        screen.create_anchor(coords);
+        screen.append('x');
+        ids.push(screen.selected.unwrap());
+    }
+    screen.unselect();
+    screen.draw_children_of_root();
+    assert_eq!(screen.visible_nodes(), vec![ids[2], ids[1], ids[0], ids[3]]);
+
+    screen.run_command(":2").unwrap();
+    assert_eq!(screen.selected, Some(ids[1]));
+    screen.run_command(":99").unwrap();
+    assert_eq!(screen.selected, Some(ids[3]));
+    screen.run_command(":0").unwrap();
+    assert_eq!(screen.selected, Some(ids[2]));
+    assert!(Screen::default().run_command(":3").is_err());
+}
+
+#[test]
+fn test_marks() {
+    let mut screen = Screen::default();
+    screen.create_anchor((10, 4));
+    screen.append('a');
+    let a = screen.selected.unwrap();
+    screen.create_anchor((10, 8));
+    screen.append('b');
+
+    assert_eq!(
+        screen.run_command(":'a"),
+        Err("mark a is not set".to_owned())
+    );
+    screen.select_node(a);
+    screen.run_command(":mark a").unwrap();
+    screen.unselect();
+    screen.run_command(":'a").unwrap();
+    assert_eq!(screen.selected, Some(a));
+
+    assert!(screen.run_command(":mark").is_err());
+    assert!(screen.run_command(":mark ab").is_err());
+}
+
+#[test]
+fn test_stale_mark() {
+    let mut screen = Screen::default();
+    screen.create_anchor((10, 4));
+    screen.append('a');
+    screen.run_command(":mark a").unwrap();
+    screen.delete_selected(false);
+
+    assert_eq!(
+        screen.run_command(":'a"),
+        Err("mark a was on a deleted node".to_owned())
+    );
+    // cleared, so it now reads as never set
+    assert!(screen.marks.is_empty());
+    assert_eq!(
+        screen.run_command(":'a"),
+        Err("mark a is not set".to_owned())
+    );
+}
+
+#[test]
+fn test_no_color() {
+    let mut screen = Screen {
+        config: Config::parse("theme.arrows: red\ntheme.background: blue").unwrap(),
+        ..Screen::default()
+    };
+    screen.create_anchor((10, 4));
+    screen.append('a');
+    screen.run_command(":due 2000-01-01").unwrap();
+    let node = screen.nodes[&screen.selected.unwrap()].clone();
+    assert!(screen.node_tint(&node).contains('\x1b'));
+    assert!(screen.arrow_color().contains('\x1b'));
+
+    screen.color_enabled = false;
+    // styles like the reset stay, but colors go
+    assert_eq!(screen.reset_style(), style::Reset.to_string());
+    assert_eq!(screen.node_tint(&node), "");
+    assert_eq!(screen.arrow_color(), "");
+    screen.message = Some("hi".to_owned());
+    assert!(!screen.status_line().contains('\x1b'));
+    assert!(!screen.config.to_string().contains('\x1b'));
+}
+
+#[test]
+fn test_erase_grapheme() {
+    let mut screen = Screen::default();
+    screen.create_anchor((1, 2));
+    // a family emoji is three emoji held together by zero width joiners
+    for c in "a👨\u{200d}👩\u{200d}👧".chars() {
+        screen.handle_event(Event::Key(Key::Char(c)));
+    }
+    let a = screen.selected.unwrap();
+    assert_eq!(screen.cursor_pos(), Some((a, 2)));
+    screen.backspace();
+    assert_eq!(screen.nodes[&a].content, "a");
+
+    // typing into the middle keeps the cursor after the grown emoji
+    for c in "👍b".chars() {
+        screen.append(c);
+    }
+    screen.move_cursor(|pos, _| pos - 1);
+    screen.append('\u{1f3fd}');
+    assert_eq!(screen.nodes[&a].content, "a👍\u{1f3fd}b");
+    assert_eq!(screen.cursor_pos(), Some((a, 2)));
+    screen.move_cursor(|pos, _| pos - 1);
+    screen.backspace();
+    assert_eq!(screen.nodes[&a].content, "👍\u{1f3fd}b");
+    screen.move_cursor(|pos, _| pos + 1);
+    screen.backspace();
+    assert_eq!(screen.nodes[&a].content, "b");
+}
+
+#[test]
+fn test_scroll_horizontally() {
+    let mut screen = Screen {
+        dims: (40, 24),
+        ..Screen::default()
+    };
+    screen.create_anchor((10, 4));
+    screen.append('a');
+    let a = screen.selected.unwrap();
+    screen.create_anchor((100, 6));
+    screen.append('b');
+    let b = screen.selected.unwrap();
+    screen.unselect();
+    screen.draw_children_of_root();
+    assert_eq!(screen.internal_to_screen_xy((10, 4)), Some((10, 4)));
+    assert_eq!(screen.internal_to_screen_xy((100, 6)), None);
+
+    screen.handle_action(Some(Action::ScrollRight));
+    assert_eq!(screen.view_x, 20);
+    assert_eq!(screen.internal_to_screen_xy((30, 4)), Some((10, 4)));
+    assert_eq!(screen.internal_to_screen_xy((10, 4)), None);
+    assert_eq!(screen.screen_to_internal_xy((10, 4)), (30, 4));
+
+    // b ends at column 101, so the view stops with it at the right edge
+    for _ in 0..10 {
+        screen.handle_action(Some(Action::ScrollRight));
+    }
+    assert_eq!(screen.view_x, 61);
+    screen.draw_children_of_root();
+    assert_eq!(
+        screen.lookup(screen.screen_to_internal_xy((40, 6))),
+        Some(&b)
+    );
+
+    screen.handle_action(Some(Action::ScrollLeft));
+    assert_eq!(screen.view_x, 41);
+    for _ in 0..10 {
+        screen.handle_action(Some(Action::ScrollLeft));
+    }
+    assert_eq!(screen.view_x, 0);
+    assert_eq!(screen.lookup((10, 4)), Some(&a));
+}
+
+#[test]
+fn test_minimap_cells() {
+    let mut screen = Screen {
+        dims: (40, 24),
+        ..Screen::default()
+    };
+    screen.create_anchor((1, 1));
+    screen.append('a');
+    screen.create_anchor((500, 300));
+    screen.append('b');
+    screen.create_anchor((250, 150));
+    screen.append('c');
+    screen.unselect();
+    screen.draw_children_of_root();
+
+    let cells = screen.minimap_cells(20, 8);
+    assert_eq!(cells.len(), 3);
+    assert!(cells.iter().all(|&(x, y)| x < 20 && y < 8));
+    assert_eq!(cells[0], (0, 0));
+    assert_eq!(cells[1], (9, 3));
+    assert_eq!(cells[2], (19, 7));
+    assert_eq!(screen.minimap_view(20, 8), ((0, 0), (1, 0)));
+
+    screen.view_x = 460;
+    screen.view_y = 276;
+    assert_eq!(screen.minimap_view(20, 8), ((18, 7), (19, 7)));
+}
+
+#[test]
+fn test_center_selection() {
+    let mut screen = Screen {
+        dims: (40, 24),
+        ..Screen::default()
+    };
+    screen.create_anchor((10, 4));
+    screen.append('a');
+    let a = screen.selected.unwrap();
+    screen.create_anchor((100, 60));
+    screen.append('b');
+    let b = screen.selected.unwrap();
+    screen.create_anchor((60, 30));
+    screen.append('c');
+    let c = screen.selected.unwrap();
+    screen.unselect();
+    screen.view_x = 50;
+    screen.view_y = 20;
+    screen.draw_children_of_root();
+
+    // c is drawn as " c", so its middle is column 61
+    screen.select_node(c);
+    screen.handle_action(Some(Action::CenterSelection));
+    assert_eq!((screen.view_x, screen.view_y), (41, 18));
+    assert_eq!(screen.internal_to_screen_xy((61, 30)), Some((20, 12)));
+
+    // toward a corner the view stops at the edge of what is drawn
+    screen.select_node(b);
+    screen.handle_action(Some(Action::CenterSelection));
+    assert_eq!((screen.view_x, screen.view_y), (61, 36));
+    screen.select_node(a);
+    screen.handle_action(Some(Action::CenterSelection));
+    assert_eq!((screen.view_x, screen.view_y), (0, 0));
+}
+
+#[test]
+fn test_ensure_visible() {
+    let mut screen = Screen {
+        dims: (40, 25),
+        ..Screen::default()
+    };
+    screen.create_anchor((10, 10));
+    screen.append('a');
+    let a = screen.selected.unwrap();
+    screen.create_anchor((10, 60));
+    screen.append('b');
+    let b = screen.selected.unwrap();
+    screen.create_anchor((70, 12));
+    screen.append('c');
+    let c = screen.selected.unwrap();
+    screen.unselect();
+    screen.draw_children_of_root();
+
+    // a is in view already, margin and all
+    assert!(!screen.ensure_visible(a));
+
+    // b ends up scrolloff rows above the status line
+    assert!(screen.ensure_visible(b));
+    assert_eq!(screen.view_y, 38);
+    assert_eq!(screen.internal_to_screen_xy((10, 60)), Some((10, 22)));
+
+    // going back up stops scrolloff rows below the top
+    assert!(screen.ensure_visible(a));
+    assert_eq!(screen.view_y, 7);
+    assert_eq!(screen.internal_to_screen_xy((10, 10)), Some((10, 3)));
+
+    // " c" takes columns 70 and 71
+    screen.draw_children_of_root();
+    screen.width_cache.insert(c, 2);
+    assert!(screen.ensure_visible(c));
+    assert_eq!((screen.view_x, screen.view_y), (31, 7));
+    assert_eq!(screen.internal_to_screen_xy((71, 12)), Some((40, 5)));
+}
+
+#[test]
+fn test_scrolloff() {
+    let mut screen = Screen {
+        dims: (40, 25),
+        ..Screen::default()
+    };
+    screen.create_anchor((10, 60));
+    screen.append('a');
+    let a = screen.selected.unwrap();
+    screen.unselect();
+    screen.draw_children_of_root();
+
+    assert!(screen.run_command("set scrolloff 3").is_ok());
+    assert!(screen.ensure_visible(a));
+    assert_eq!(screen.view_y, 39);
+    assert_eq!(screen.internal_to_screen_xy((10, 60)), Some((10, 21)));
+
+    // more than half the 24 rows above the status line centers a
+    assert!(screen.run_command("set scrolloff 20").is_ok());
+    assert!(screen.ensure_visible(a));
+    assert_eq!(screen.view_y, 47);
+    assert_eq!(screen.internal_to_screen_xy((10, 60)), Some((10, 13)));
+    assert!(!screen.ensure_visible(a));
+}
+
+#[test]
+fn test_help_text() {
+    use termion::event::Key::{Ctrl, Down, Esc};
+
+    let mut screen = Screen::default();
+    assert!(screen.help_text().contains("    save: C-x"));
+    screen.config = Config::parse("toggle_layout: C-x\nsave: C-s").unwrap();
+    let text = screen.help_text();
+    assert!(text.contains("  Normal mode:"), "{}", text);
+    assert!(text.contains("    save: C-s"), "{}", text);
+    assert!(text.contains("    toggle_layout: C-x"), "{}", text);
+    assert!(!text.contains("    save: C-x"), "{}", text);
+
+    // keys scroll the overlay rather than reach the map
+    screen.handle_action(Some(Action::Help));
+    assert!(screen.handle_event(Event::Key(Ctrl('n'))));
+    assert_eq!(screen.nodes.len(), Screen::default().nodes.len());
+    assert!(screen.handle_event(Event::Key(Down)));
+    assert_eq!(screen.help.as_ref().map(|h| h.scroll), Some(1));
+    assert!(screen.handle_event(Event::Key(Esc)));
+    assert!(screen.help.is_none());
+}
+
+#[test]
+fn test_filter_help() {
+    use termion::event::Key::{Backspace, Char, Esc};
+
+    let mut screen = Screen::default();
+    let lines = screen.filter_help("select");
+    assert!(
+        lines.contains(&"    select_up: up".to_owned()),
+        "{:?}",
+        lines
+    );
+    for line in &lines {
+        assert!(
+            line.ends_with("mode:") || line.contains("select"),
+            "{}",
+            line
+        );
+    }
+    assert_eq!(
+        screen.filter_help("").len(),
+        screen.help_text().lines().count()
+    );
+    assert!(screen.filter_help("no such binding").is_empty());
+
+    // typing filters, Esc clears the filter and then closes
+    screen.handle_action(Some(Action::Help));
+    for c in "selx".chars() {
+        screen.handle_event(Event::Key(Char(c)));
+    }
+    screen.handle_event(Event::Key(Backspace));
+    assert_eq!(screen.help.as_ref().unwrap().query, "sel");
+    screen.handle_event(Event::Key(Esc));
+    assert_eq!(screen.help.as_ref().unwrap().query, "");
+    screen.handle_event(Event::Key(Esc));
+    assert!(screen.help.is_none());
+}
+
+#[test]
+fn test_help_scroll_stops_at_end() {
+    use termion::event::Key::{Down, PageDown, Up};
+
+    let mut screen = Screen {
+        dims: (40, 10),
+        ..Screen::default()
+    };
+    let last_page = screen.filter_help("").len() - 8;
+    screen.handle_action(Some(Action::Help));
+    for _ in 0..3 {
+        screen.handle_event(Event::Key(PageDown));
+    }
+    for _ in 0..last_page {
+        screen.handle_event(Event::Key(Down));
+    }
+    assert_eq!(screen.help.as_ref().unwrap().scroll, last_page);
+    screen.handle_event(Event::Key(Up));
+    assert_eq!(screen.help.as_ref().unwrap().scroll, last_page - 1);
+}
+
+#[test]
+fn test_readonly() {
+    let mut screen = Screen::default();
+    screen.create_anchor((1, 1));
+    screen.append('a');
+    let a = screen.selected.unwrap();
+    screen.readonly = true;
+
+    assert!(screen.handle_action(Some(Action::DeleteSelected)));
+    assert!(screen.exists(a));
+    assert!(screen.status_line().contains("read only, delete is off"));
+    screen.handle_action(Some(Action::Char('b')));
+    assert_eq!(screen.nodes[&a].content, "a");
+    assert!(screen.run_command("sort priority").is_err());
+    let err = screen.run_command("import notes.md").unwrap_err();
+    assert_eq!(err, "read only, import is off");
+    screen.handle_action(Some(Action::FocusBranch));
+    assert!(screen.status_line().contains("read only, focus_branch is off"));
+    screen.message = None;
+    screen.handle_action(Some(Action::Save));
+    assert!(screen.status_line().contains("read only, save is off"));
+    screen.message = None;
+    screen.run_command("w").unwrap();
+    assert!(screen.status_line().contains("read only, save is off"));
+
+    // looking around still works
+    screen.handle_action(Some(Action::UnselectRet));
+    assert_eq!(screen.selected, None);
+    screen.handle_action(Some(Action::EnterCmd));
+    screen.handle_action(Some(Action::Char('w')));
+    assert_eq!(screen.command.as_deref(), Some("w"));
+}
+
+#[test]
+fn test_open_url_without_url() {
+    let mut screen = Screen {
+        is_test: true,
+        ..Screen::default()
+    };
+    screen.create_anchor((1, 1));
+    for c in "just words".chars() {
+        screen.append(c);
+    }
+    screen.handle_action(Some(Action::OpenUrl));
+    assert!(screen.status_line().contains("no URL in the selected node"));
+}
+
+#[test]
+fn test_editor_command() {
+    let _env = crate::config::ENV_LOCK
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    let home = env::var("HOME").unwrap_or_default();
+    let path = node_file_path(" ~/notes/todo.md ");
+    assert_eq!(path, format!("{}/notes/todo.md", home));
+    assert_eq!(
+        editor_command(Some("code --wait"), &path),
+        vec!["code".to_owned(), "--wait".to_owned(), path.clone()]
+    );
+    assert_eq!(
+        editor_command(None, "/etc/hosts"),
+        vec!["vi".to_owned(), "/etc/hosts".to_owned()]
+    );
+    assert_eq!(editor_command(Some(" "), "a")[0], "vi");
+
+    let mut screen = Screen {
+        is_test: true,
+        ..Screen::default()
+    };
+    screen.create_anchor((1, 1));
+    for c in "/surely/not/a/file".chars() {
+        screen.append(c);
+    }
+    screen.handle_action(Some(Action::EditInEditor));
+    assert!(screen
+        .status_line()
+        .contains("no file at /surely/not/a/file"));
+}
@@ -1,14 +1,34 @@
+use std::{fs, io};
+
 use protobuf::{self, Message};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "serde")]
+use crate::NodeID;
 use crate::{pb, random_fg_color, Meta, Node, Screen};
 
+// The in progress flag travels as a meta tag so the pb schema stays the same.
+const IN_PROGRESS_TAG: &str = "in_progress";
+const PRIORITY_TAG: &str = "priority";
+// Likewise the view, as tags on the root node.
+const DRAWING_ROOT_TAG: &str = "drawing_root";
+const VIEW_Y_TAG: &str = "view_y";
+const VIEW_X_TAG: &str = "view_x";
+
 pub fn serialize_screen(screen: &Screen) -> Vec<u8> {
     let mut screen_pb = pb::Screen::default();
     screen_pb.set_max_id(screen.max_id);
     let nodes = screen
         .nodes
         .iter()
-        .map(|(_, node)| serialize_node(node))
+        .map(|(_, node)| {
+            let mut node_pb = serialize_node(node);
+            if node.id == 0 {
+                serialize_view(screen, node_pb.mut_meta());
+            }
+            node_pb
+        })
         .collect();
     screen_pb.set_nodes(protobuf::RepeatedField::from_vec(nodes));
     let arrows = screen
@@ -25,6 +45,22 @@ pub fn serialize_screen(screen: &Screen) -> Vec<u8> {
     screen_pb.write_to_bytes().unwrap()
 }
 
+fn serialize_view(screen: &Screen, meta_pb: &mut pb::Meta) {
+    let view = [
+        (DRAWING_ROOT_TAG, screen.drawing_root),
+        (VIEW_Y_TAG, u64::from(screen.view_y)),
+        (VIEW_X_TAG, u64::from(screen.view_x)),
+    ];
+    for &(key, value) in &view {
+        if value != 0 {
+            let mut tag = pb::Tag::default();
+            tag.set_key(key.to_owned());
+            tag.set_value(value.to_string());
+            meta_pb.mut_tags().push(tag);
+        }
+    }
+}
+
 fn serialize_meta(meta: &Meta) -> pb::Meta {
     let mut meta_pb = pb::Meta::default();
     meta_pb.set_gps(pb::Gps::default());
@@ -55,7 +91,20 @@ fn serialize_node(node: &Node) -> pb::Node {
     node_pb.set_parent_id(node.parent_id);
     node_pb.set_x(u32::from(node.rooted_coords.0));
     node_pb.set_y(u32::from(node.rooted_coords.1));
-    node_pb.set_meta(serialize_meta(&node.meta));
+    let mut meta_pb = serialize_meta(&node.meta);
+    if node.in_progress {
+        let mut tag = pb::Tag::default();
+        tag.set_key(IN_PROGRESS_TAG.to_owned());
+        tag.set_value("true".to_owned());
+        meta_pb.mut_tags().push(tag);
+    }
+    if node.priority != 0 {
+        let mut tag = pb::Tag::default();
+        tag.set_key(PRIORITY_TAG.to_owned());
+        tag.set_value(node.priority.to_string());
+        meta_pb.mut_tags().push(tag);
+    }
+    node_pb.set_meta(meta_pb);
     node_pb.set_auto_arrange(node.auto_arrange);
     if let Some(ref free_text) = node.free_text {
         node_pb.set_free_text(free_text.to_owned());
@@ -86,16 +135,26 @@ fn deserialize_meta(meta_pb: &pb::Meta) -> Meta {
 }
 
 fn deserialize_node(node_pb: &pb::Node) -> Node {
-    Node {
+    let mut meta = deserialize_meta(node_pb.get_meta());
+    let in_progress = meta.tags.remove(IN_PROGRESS_TAG).is_some();
+    let priority = meta
+        .tags
+        .remove(PRIORITY_TAG)
+        .and_then(|raw| raw.parse().ok())
+        .unwrap_or(0);
+    let mut node = Node {
         parent_id: node_pb.get_parent_id(),
         rooted_coords: (node_pb.get_x() as u16, node_pb.get_y() as u16),
         content: node_pb.get_text().to_owned(),
+        tags: vec![],
         children: node_pb.get_children().to_vec(),
         selected: node_pb.get_selected(),
         collapsed: node_pb.get_collapsed(),
         stricken: node_pb.get_stricken(),
+        in_progress,
+        priority,
         hide_stricken: node_pb.get_hide_stricken(),
-        meta: deserialize_meta(node_pb.get_meta()),
+        meta,
         id: node_pb.get_id(),
         free_text: if node_pb.has_free_text() {
             Some(node_pb.get_free_text().to_owned())
@@ -104,7 +163,9 @@ fn deserialize_node(node_pb: &pb::Node) -> Node {
         },
         color: random_fg_color(),
         auto_arrange: node_pb.get_auto_arrange(),
-    }
+    };
+    node.parse_tags();
+    node
 }
 
 pub fn deserialize_screen(data: Vec<u8>) -> Result<Screen, protobuf::ProtobufError> {
@@ -116,10 +177,11 @@ pub fn deserialize_screen(data: Vec<u8>) -> Result<Screen, protobuf::ProtobufErr
         .iter()
         .map(|node_pb| {
             let node = deserialize_node(node_pb);
-            screen.tag_db.reindex(node.id, node.content.clone());
+            screen.tag_db.set_tags(node.id, &node.tags);
             (node.id, node)
         })
         .collect();
+    deserialize_view(&mut screen);
 
     screen.arrows = screen_pb
         .get_arrows()
@@ -132,3 +194,203 @@ pub fn deserialize_screen(data: Vec<u8>) -> Result<Screen, protobuf::ProtobufErr
         .collect();
     Ok(screen)
 }
+
+fn deserialize_view(screen: &mut Screen) {
+    let tags = match screen.nodes.get_mut(&0) {
+        Some(root) => &mut root.meta.tags,
+        None => return,
+    };
+    let drawing_root = tags.remove(DRAWING_ROOT_TAG).and_then(|v| v.parse().ok());
+    let view_y = tags.remove(VIEW_Y_TAG).and_then(|v| v.parse().ok());
+    let view_x = tags.remove(VIEW_X_TAG).and_then(|v| v.parse().ok());
+    match drawing_root {
+        // the offset was for a view that no longer exists
+        Some(root) if !screen.nodes.contains_key(&root) => return,
+        Some(root) => screen.drawing_root = root,
+        None => {}
+    }
+    screen.view_y = view_y.unwrap_or(0);
+    screen.view_x = view_x.unwrap_or(0);
+}
+
+// The JSON layout, with nodes ordered by id so the output is stable.
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct JsonScreen {
+    max_id: u64,
+    nodes: Vec<Node>,
+    arrows: Vec<(NodeID, NodeID)>,
+}
+
+#[cfg(feature = "serde")]
+impl Screen {
+    /// Serializes the nodes and arrows as JSON, for use by scripts.
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        let mut nodes: Vec<_> = self.nodes.values().cloned().collect();
+        nodes.sort_by_key(|n| n.id);
+        let json = JsonScreen {
+            max_id: self.max_id,
+            nodes,
+            arrows: self.arrows.clone(),
+        };
+        serde_json::to_string_pretty(&json)
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<Screen> {
+        let json: JsonScreen = serde_json::from_str(json)?;
+        let mut screen = Screen::default();
+        screen.max_id = json.max_id;
+        screen.arrows = json.arrows;
+        for mut node in json.nodes {
+            node.parse_tags();
+            screen.tag_db.set_tags(node.id, &node.tags);
+            screen.nodes.insert(node.id, node);
+        }
+        Ok(screen)
+    }
+}
+
+/// Opens the document at `path` to save back to it. A file that doesn't
+/// exist yet, or is empty, starts an empty tree.
+pub fn open_screen(path: &str) -> Result<Screen, protobuf::ProtobufError> {
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => vec![],
+        Err(e) => return Err(e.into()),
+    };
+    let mut screen = if data.is_empty() {
+        Screen::default()
+    } else {
+        deserialize_screen(data)?
+    };
+    screen.work_path = Some(path.to_owned());
+    Ok(screen)
+}
+
+#[test]
+fn test_in_progress_round_trip() {
+    let mut screen = Screen::default();
+    let node = Node {
+        id: 1,
+        in_progress: true,
+        ..Node::default()
+    };
+    screen.nodes.insert(1, node);
+    screen.max_id = 1;
+
+    let screen = deserialize_screen(serialize_screen(&screen)).unwrap();
+    let node = &screen.nodes[&1];
+    assert!(node.in_progress);
+    assert!(node.meta.tags.is_empty());
+    assert!(!screen.nodes[&0].in_progress);
+}
+
+#[test]
+fn test_priority_round_trip() {
+    let mut screen = Screen::default();
+    let node = Node {
+        id: 1,
+        priority: 2,
+        ..Node::default()
+    };
+    screen.nodes.insert(1, node);
+    screen.max_id = 1;
+
+    let screen = deserialize_screen(serialize_screen(&screen)).unwrap();
+    assert_eq!(screen.nodes[&1].priority, 2);
+    assert_eq!(screen.nodes[&0].priority, 0);
+}
+
+#[test]
+fn test_flags_and_view_round_trip() {
+    let mut screen = Screen::default();
+    let parent = Node {
+        id: 1,
+        children: vec![2],
+        collapsed: true,
+        ..Node::default()
+    };
+    let child = Node {
+        id: 2,
+        parent_id: 1,
+        stricken: true,
+        ..Node::default()
+    };
+    screen.nodes.get_mut(&0).unwrap().children.push(1);
+    screen.nodes.insert(1, parent);
+    screen.nodes.insert(2, child);
+    screen.max_id = 2;
+    screen.drawing_root = 1;
+    screen.view_y = 7;
+    screen.view_x = 3;
+
+    let path = std::env::temp_dir().join(format!("void_round_trip_{}", std::process::id()));
+    screen.work_path = Some(path.to_str().unwrap().to_owned());
+    screen.save();
+    let data = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let loaded = deserialize_screen(data).unwrap();
+    assert!(loaded.nodes[&1].collapsed);
+    assert!(!loaded.nodes[&1].stricken);
+    assert!(loaded.nodes[&2].stricken);
+    assert!(!loaded.nodes[&2].collapsed);
+    assert_eq!(loaded.drawing_root, 1);
+    assert_eq!(loaded.view_y, 7);
+    assert_eq!(loaded.view_x, 3);
+    assert!(loaded.nodes[&0].meta.tags.is_empty());
+}
+
+#[test]
+fn test_open_missing_screen() {
+    let path = std::env::temp_dir().join(format!("void_missing_{}", std::process::id()));
+    let path = path.to_str().unwrap();
+    let screen = open_screen(path).unwrap();
+    assert_eq!(screen.work_path.as_deref(), Some(path));
+    assert_eq!(screen.nodes.len(), Screen::default().nodes.len());
+    assert_eq!(screen.max_id, 0);
+    assert!(std::fs::metadata(path).is_err());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_json_round_trip() {
+    let mut screen = Screen::default();
+    let parent = Node {
+        id: 1,
+        content: "parent #tag".to_owned(),
+        children: vec![3, 2],
+        collapsed: true,
+        ..Node::default()
+    };
+    let first = Node {
+        id: 3,
+        parent_id: 1,
+        content: "first".to_owned(),
+        stricken: true,
+        ..Node::default()
+    };
+    let second = Node {
+        id: 2,
+        parent_id: 1,
+        content: "second".to_owned(),
+        ..Node::default()
+    };
+    screen.nodes.get_mut(&0).unwrap().children.push(1);
+    for node in [parent, first, second] {
+        screen.nodes.insert(node.id, node);
+    }
+    screen.max_id = 3;
+    screen.arrows = vec![(3, 2)];
+
+    let json = screen.to_json().unwrap();
+    let parsed = Screen::from_json(&json).unwrap();
+    assert_eq!(parsed.to_json().unwrap(), json);
+    assert_eq!(parsed.max_id, 3);
+    assert_eq!(parsed.arrows, vec![(3, 2)]);
+    assert_eq!(parsed.nodes[&1].children, vec![3, 2]);
+    assert!(parsed.nodes[&1].collapsed);
+    assert!(parsed.nodes[&3].stricken);
+    assert_eq!(parsed.nodes[&2].content, "second");
+    parsed.assert_node_consistency();
+}
@@ -2,7 +2,7 @@ use std::collections::{HashMap, HashSet};
 
 use regex::Regex;
 
-use crate::{re_matches, NodeID};
+use crate::NodeID;
 
 pub struct TagDB {
     node_to_tags: HashMap<NodeID, HashSet<String>>,
@@ -18,43 +18,40 @@ impl Default for TagDB {
     }
 }
 
-impl TagDB {
-    pub fn reindex(&mut self, node: NodeID, text: String) {
-        lazy_static! {
-            static ref RE_TAG_KEY: Regex = Regex::new(r"#([^\s=]+)*").unwrap();
-            static ref RE_TAG_KEY_VALUE: Regex = Regex::new(r"#(\S+)*").unwrap();
-        }
-
-        self.remove(node);
-        self.node_to_tags.insert(node, HashSet::new());
-        let tags = re_matches::<String>(&RE_TAG_KEY_VALUE, &*text);
-
-        for tag in &tags {
-            if let Some(tags) = self.node_to_tags.get_mut(&node) {
-                tags.insert(tag.clone());
-            }
-
-            let mut nodes = self.tag_to_nodes.remove(tag).unwrap_or_else(HashSet::new);
-
-            nodes.insert(node);
+/// The tags in `text`, without the `#`. A tag starts at a `#` that does not
+/// follow a letter or digit and ends at a space or at punctuation other than
+/// `-` and `_`. `#key=value` gives both `key` and `key=value`.
+pub fn parse_tags(text: &str) -> Vec<String> {
+    lazy_static! {
+        static ref RE_TAG: Regex =
+            Regex::new(r"\B#((?:[^\s\p{P}=]|[-_])+)(?:=((?:[^\s\p{P}=]|[-_])+))?").unwrap();
+    }
 
-            self.tag_to_nodes.insert(tag.clone(), nodes);
+    let mut tags = vec![];
+    for cap in RE_TAG.captures_iter(text) {
+        tags.push(cap[1].to_owned());
+        if let Some(value) = cap.get(2) {
+            tags.push(format!("{}={}", &cap[1], value.as_str()));
         }
+    }
+    tags
+}
 
-        if text.contains('=') {
-            let tags = re_matches::<String>(&RE_TAG_KEY, &*text);
-
-            for tag in &tags {
-                if let Some(tags) = self.node_to_tags.get_mut(&node) {
-                    tags.insert(tag.clone());
-                }
-
-                let mut nodes = self.tag_to_nodes.remove(tag).unwrap_or_else(HashSet::new);
-
-                nodes.insert(node);
+impl TagDB {
+    pub fn reindex(&mut self, node: NodeID, text: String) {
+        self.set_tags(node, &parse_tags(&text));
+    }
 
-                self.tag_to_nodes.insert(tag.clone(), nodes);
-            }
+    // for nodes whose tags are already parsed
+    pub fn set_tags(&mut self, node: NodeID, tags: &[String]) {
+        self.remove(node);
+        self.node_to_tags
+            .insert(node, tags.iter().cloned().collect());
+        for tag in tags {
+            self.tag_to_nodes
+                .entry(tag.clone())
+                .or_default()
+                .insert(node);
         }
     }
 
@@ -92,3 +89,16 @@ fn test_basic_func() {
     assert_eq!(tdb.tag_to_nodes("yes=ok"), vec![3]);
     assert_eq!(tdb.tag_to_nodes("$"), vec![4]);
 }
+
+#[test]
+fn test_parse_tags() {
+    assert_eq!(
+        parse_tags("#work, #to-do. (#home_office)"),
+        vec!["work", "to-do", "home_office"]
+    );
+    assert_eq!(
+        parse_tags("#due=2026-10-14;"),
+        vec!["due", "due=2026-10-14"]
+    );
+    assert!(parse_tags("C#x and a # sign and ##").is_empty());
+}